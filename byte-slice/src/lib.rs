@@ -1,5 +1,5 @@
 use anyhow::{Result, anyhow};
-use std::{fmt::{Debug, Write}, ops::{Index, Range, RangeFrom, RangeFull, RangeInclusive, RangeTo}};
+use std::{fmt::{Debug, Display}, ops::{Index, Range, RangeFrom, RangeFull, RangeInclusive, RangeTo}, str::FromStr};
 
 
 
@@ -29,7 +29,7 @@ impl Hex for &str {
 
 
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 pub struct Bytes<'a> {
     idx_first: usize, 
     idx_last: usize, 
@@ -74,6 +74,27 @@ impl<'a> Bytes<'a>{
         }
     }
 
+    // the bytes between the indices, for as long as the data they point into lives
+    pub fn as_slice(&self) -> &'a [u8] {
+        &self.data[self.idx_first..self.idx_last]
+    }
+
+    pub fn len(&self) -> usize {
+        self.idx_last - self.idx_first
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // returns an error if fewer than byte_count bytes are left to read
+    pub fn require(&self, byte_count: usize) -> Result<()> {
+        match self.len() >= byte_count {
+            true => Ok(()),
+            false => Err(anyhow!("Expected at least {byte_count} bytes but only {} are left", self.len()))
+        }
+    }
+
     pub fn reset(&mut self) {
         self.idx_first = 0;
         self.idx_last = self.data.len();
@@ -148,20 +169,6 @@ impl<'a> Index<RangeInclusive<usize>> for Bytes<'a> {
 }
 
 
-const DEFAULT_BYTES_SLICE: [u8; 0] = [];
-
-impl Default for &Bytes<'_> {
-    fn default() -> Self {
-        &Bytes {
-            idx_first: 0,
-            idx_last: 0,
-            data: &DEFAULT_BYTES_SLICE,
-
-            no_print: false,
-        }
-    }
-}
-
 impl<'a> Debug for Bytes<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.no_print {
@@ -192,7 +199,7 @@ macro_rules! slice_to_unsigned {
 }
 
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Ipv4addr(pub u32);
 
 impl Debug for Ipv4addr {
@@ -206,8 +213,25 @@ impl Debug for Ipv4addr {
     }
 }
 
+impl Display for Ipv4addr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&std::net::Ipv4Addr::from(self.0), f)
+    }
+}
+
+impl FromStr for Ipv4addr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let address: std::net::Ipv4Addr = s.trim().parse()
+            .map_err(|_| anyhow!("Invalid Ipv4 address: {s}"))?;
+
+        Ok(Self(address.into()))
+    }
+}
+
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Ipv6addr(pub u128);
 
 impl Debug for Ipv6addr {
@@ -225,10 +249,38 @@ impl Debug for Ipv6addr {
     }
 }
 
+impl Display for Ipv6addr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&std::net::Ipv6Addr::from(self.0), f)
+    }
+}
+
+impl FromStr for Ipv6addr {
+    type Err = anyhow::Error;
 
-#[derive(Default)]
+    fn from_str(s: &str) -> Result<Self> {
+        let address: std::net::Ipv6Addr = s.trim().parse()
+            .map_err(|_| anyhow!("Invalid Ipv6 address: {s}"))?;
+
+        Ok(Self(address.into()))
+    }
+}
+
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct MacAddress(u16, u32); // 48 bit address so a u16 and u32 will pack the values together
 
+impl MacAddress {
+    pub fn to_u64(&self) -> u64 {
+        ((self.0 as u64) << 32) | self.1 as u64
+    }
+
+    pub fn octets(&self) -> [u8; 6] {
+        let [_, _, octets @ ..] = self.to_u64().to_be_bytes();
+        octets
+    }
+}
+
 impl From<u64> for MacAddress {
     fn from(value: u64) -> Self {
         Self(
@@ -272,6 +324,31 @@ impl Debug for MacAddress {
     }
 }
 
+impl Display for MacAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [f1, f2, f3, f4, f5, f6] = self.octets();
+
+        f.write_fmt(format_args!("{f1:02x}:{f2:02x}:{f3:02x}:{f4:02x}:{f5:02x}:{f6:02x}"))
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let octets = s.trim()
+            .split([':', '-'])
+            .map(|octet_str| u8::from_str_radix(octet_str, 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| anyhow!("Invalid MacAddress: {s}"))?;
+
+        match octets.len() == 6 {
+            true => Ok(Self::from(octets.to_u64())),
+            false => Err(anyhow!("Invalid MacAddress: {s}"))
+        }
+    }
+}
+
 
 
 pub trait SliceToUnsigned {
@@ -0,0 +1,463 @@
+use anyhow::Result;
+
+use crate::packet::{data_link::{DataLinkLayer, MacHeader, Tag802_1Q, VlanTag, TPID_802_1AD, TPID_802_1Q}, network::{Ipv4, Ipv6, NetworkLayer, ARP}, transport::{TransportLayer, ICMP, TCP, UDP}, Layer, Packet};
+
+
+// Scapy style packet construction on top of the layer structs
+//
+// let crafted = Craft::new()
+//     .ether(MacHeader { address_dst: "ff:ff:ff:ff:ff:ff".parse()?, ..Default::default() })
+//     .ipv4(Ipv4 { address_dst: "10.0.0.1".parse()?, ..Default::default() })
+//     .udp(UDP { port_src: 5353, port_dst: 5353, ..Default::default() })
+//     .payload(b"hello")
+//     .build();
+//
+// Unless `verbatim` is set, fields that are left at 0 and can be derived are filled in when the
+// bytes are written: ethertypes, protocol numbers, versions, header and payload lengths, checksums,
+// the Ipv4/Ipv6 hop limit (64) and the ARP hardware/protocol description (Ethernet/Ipv4 request).
+// The next_layer and payload members of the wrapped structs are ignored, the stack order defines nesting.
+
+
+
+// ones' complement sum of 16 bit words, an odd trailing byte is padded with 0
+fn ones_complement_sum(data: &[u8], initial: u32) -> u32 {
+    let mut sum = data
+        .chunks(2)
+        .fold(initial as u64, |sum, word| {
+            sum + ((word[0] as u64) << 8 | *word.get(1).unwrap_or(&0) as u64)
+        });
+
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    sum as u32
+}
+
+// checksum used by Ipv4, ICMP, TCP and UDP
+pub fn internet_checksum(data: &[u8]) -> u16 {
+    !(ones_complement_sum(data, 0) as u16)
+}
+
+// adjusts a checksum for `old` bytes of the covered data being replaced by `new`, RFC 1624
+pub fn update_checksum(checksum: u16, old: &[u8], new: &[u8]) -> u16 {
+    let old_sum = !(ones_complement_sum(old, 0) as u16);
+    !(ones_complement_sum(new, (!checksum) as u32 + old_sum as u32) as u16)
+}
+
+
+
+#[derive(Debug)]
+pub enum CraftLayer<'a> {
+    Ether(MacHeader),
+    Dot1Q(VlanTag),
+    Ipv4(Ipv4<'a>),
+    Ipv6(Ipv6<'a>),
+    Arp(ARP),
+    Tcp(TCP<'a>),
+    Udp(UDP<'a>),
+    Icmp(ICMP<'a>),
+    Raw(Vec<u8>),
+}
+
+impl<'a> CraftLayer<'a> {
+    // the value a mac header uses to announce this layer
+    fn ethertype(&self) -> Option<u16> {
+        match self {
+            CraftLayer::Dot1Q(tag) => Some(tag.tpid),
+            CraftLayer::Ipv4(_) => Some(0x0800),
+            CraftLayer::Ipv6(_) => Some(0x86dd),
+            CraftLayer::Arp(_) => Some(0x0806),
+            _ => None,
+        }
+    }
+
+    // the value an ip header uses to announce this layer
+    fn ip_protocol(&self) -> Option<u8> {
+        match self {
+            CraftLayer::Ipv4(_) => Some(4),
+            CraftLayer::Ipv6(_) => Some(41),
+            CraftLayer::Icmp(_) => Some(1),
+            CraftLayer::Tcp(_) => Some(6),
+            CraftLayer::Udp(_) => Some(17),
+            _ => None,
+        }
+    }
+}
+
+
+
+#[derive(Debug, Default)]
+pub struct Craft<'a> {
+    pub layers: Vec<CraftLayer<'a>>,
+    // write every field exactly as given instead of filling in derived values
+    pub verbatim: bool,
+}
+
+impl<'a> Craft<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn layer(mut self, layer: CraftLayer<'a>) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    pub fn ether(self, mac_header: MacHeader) -> Self {
+        self.layer(CraftLayer::Ether(mac_header))
+    }
+
+    pub fn dot1q(self, tag: Tag802_1Q) -> Self {
+        self.vlan(VlanTag::new(TPID_802_1Q, tag))
+    }
+
+    // the service tag of a QinQ frame, followed by the customer tag with dot1q
+    pub fn dot1ad(self, tag: Tag802_1Q) -> Self {
+        self.vlan(VlanTag::new(TPID_802_1AD, tag))
+    }
+
+    pub fn vlan(self, tag: VlanTag) -> Self {
+        self.layer(CraftLayer::Dot1Q(tag))
+    }
+
+    pub fn ipv4(self, ipv4: Ipv4<'a>) -> Self {
+        self.layer(CraftLayer::Ipv4(ipv4))
+    }
+
+    pub fn ipv6(self, ipv6: Ipv6<'a>) -> Self {
+        self.layer(CraftLayer::Ipv6(ipv6))
+    }
+
+    pub fn arp(self, arp: ARP) -> Self {
+        self.layer(CraftLayer::Arp(arp))
+    }
+
+    pub fn tcp(self, tcp: TCP<'a>) -> Self {
+        self.layer(CraftLayer::Tcp(tcp))
+    }
+
+    pub fn udp(self, udp: UDP<'a>) -> Self {
+        self.layer(CraftLayer::Udp(udp))
+    }
+
+    pub fn icmp(self, icmp: ICMP<'a>) -> Self {
+        self.layer(CraftLayer::Icmp(icmp))
+    }
+
+    pub fn payload(self, payload: impl AsRef<[u8]>) -> Self {
+        self.layer(CraftLayer::Raw(payload.as_ref().to_vec()))
+    }
+
+    pub fn verbatim(mut self) -> Self {
+        self.verbatim = true;
+        self
+    }
+
+    pub fn build(&self) -> Crafted {
+        Crafted {
+            bytes: self.to_bytes()
+        }
+    }
+
+    // layers are written back to front so every header knows the payload it carries
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut frame = Vec::new();
+
+        for idx in (0..self.layers.len()).rev() {
+            let mut header = self.header_bytes(idx, &frame);
+            header.append(&mut frame);
+            frame = header;
+        }
+
+        frame
+    }
+
+    fn next_layer(&self, idx: usize) -> Option<&CraftLayer<'a>> {
+        self.layers.get(idx + 1)
+    }
+
+    // the type that follows a mac address pair or vlan tag at `idx`
+    fn ethertype_after(&self, idx: usize) -> u16 {
+        let mac_header = self.layers[..=idx]
+            .iter()
+            .rev()
+            .find_map(|layer| match layer {
+                CraftLayer::Ether(mac_header) => Some(mac_header),
+                _ => None,
+            });
+        let ethertype = mac_header.map_or(0, |mac_header| mac_header.ethertype);
+
+        match self.next_layer(idx) {
+            Some(CraftLayer::Dot1Q(tag)) => tag.tpid,
+            _ if self.verbatim || ethertype != 0 => ethertype,
+            next_layer => next_layer.and_then(CraftLayer::ethertype).unwrap_or(0),
+        }
+    }
+
+    fn ip_protocol_after(&self, idx: usize, protocol: u8, no_next_header: u8) -> u8 {
+        match self.verbatim || protocol != 0 {
+            true => protocol,
+            false => self.next_layer(idx).map_or(Some(no_next_header), CraftLayer::ip_protocol).unwrap_or(protocol),
+        }
+    }
+
+    // ones' complement sum of the pseudo header of the closest ip layer below `idx`
+    fn pseudo_header_sum(&self, idx: usize, protocol: u8, length: usize) -> u32 {
+        let mut pseudo_header = Vec::with_capacity(40);
+
+        match self.layers[..idx].iter().rev().find(|layer| matches!(layer, CraftLayer::Ipv4(_) | CraftLayer::Ipv6(_))) {
+            Some(CraftLayer::Ipv4(ipv4)) => {
+                pseudo_header.extend(ipv4.address_src.0.to_be_bytes());
+                pseudo_header.extend(ipv4.address_dst.0.to_be_bytes());
+                pseudo_header.extend([0, protocol]);
+                pseudo_header.extend((length as u16).to_be_bytes());
+            },
+            Some(CraftLayer::Ipv6(ipv6)) => {
+                pseudo_header.extend(ipv6.address_src.0.to_be_bytes());
+                pseudo_header.extend(ipv6.address_dst.0.to_be_bytes());
+                pseudo_header.extend((length as u32).to_be_bytes());
+                pseudo_header.extend([0, 0, 0, protocol]);
+            },
+            _ => {},
+        }
+
+        ones_complement_sum(&pseudo_header, 0)
+    }
+
+    fn header_bytes(&self, idx: usize, payload: &[u8]) -> Vec<u8> {
+        let auto = !self.verbatim;
+
+        match &self.layers[idx] {
+            CraftLayer::Ether(mac_header) => {
+                let mut mac_header = mac_header.clone();
+                mac_header.ethertype = self.ethertype_after(idx);
+                mac_header.to_bytes()
+            },
+            CraftLayer::Dot1Q(tag) => {
+                let mut bytes = tag.tci.0.to_be_bytes().to_vec();
+                bytes.extend(self.ethertype_after(idx).to_be_bytes());
+                bytes
+            },
+            CraftLayer::Ipv4(ipv4) => {
+                let mut ipv4 = ipv4.clone();
+                ipv4.protocol = self.ip_protocol_after(idx, ipv4.protocol, 0);
+                if auto {
+                    if ipv4.version == 0 { ipv4.version = 4 }
+                    if ipv4.IHL == 0 { ipv4.IHL = 5 }
+                    if ipv4.ttl == 0 { ipv4.ttl = 64 }
+                    if ipv4.total_length == 0 {
+                        ipv4.total_length = ((ipv4.IHL as usize * 4).max(20) + payload.len()) as u16;
+                    }
+                }
+
+                let mut bytes = ipv4.to_bytes();
+                if auto && ipv4.header_checksum == 0 {
+                    let checksum = internet_checksum(&bytes);
+                    bytes[10..12].copy_from_slice(&checksum.to_be_bytes());
+                }
+                bytes
+            },
+            CraftLayer::Ipv6(ipv6) => {
+                let mut ipv6 = ipv6.clone();
+                ipv6.next_header = self.ip_protocol_after(idx, ipv6.next_header, 59);
+                if auto {
+                    if ipv6.version == 0 { ipv6.version = 6 }
+                    if ipv6.payload_length == 0 { ipv6.payload_length = payload.len() as u16 }
+                    if ipv6.hop_limit == 0 { ipv6.hop_limit = 64 }
+                }
+                ipv6.to_bytes()
+            },
+            CraftLayer::Arp(arp) => {
+                let mut arp = arp.clone();
+                if auto {
+                    if arp.hardware_type == 0 { arp.hardware_type = 1 }
+                    if arp.protocol_type == 0 { arp.protocol_type = 0x0800 }
+                    if arp.hardware_len == 0 { arp.hardware_len = 6 }
+                    if arp.protocol_len == 0 { arp.protocol_len = 4 }
+                    if arp.operation == 0 { arp.operation = 1 }
+                }
+                arp.to_bytes()
+            },
+            CraftLayer::Tcp(tcp) => {
+                let mut tcp = tcp.clone();
+                if auto && tcp.data_offset == 0 {
+                    tcp.data_offset = 5;
+                }
+
+                let mut bytes = tcp.to_bytes();
+                if auto && tcp.checksum == 0 {
+                    let length = bytes.len() + payload.len();
+                    let sum = ones_complement_sum(payload, ones_complement_sum(&bytes, self.pseudo_header_sum(idx, 6, length)));
+                    bytes[16..18].copy_from_slice(&(!(sum as u16)).to_be_bytes());
+                }
+                bytes
+            },
+            CraftLayer::Udp(udp) => {
+                let mut udp = udp.clone();
+                if auto && udp.length == 0 {
+                    udp.length = (8 + payload.len()) as u16;
+                }
+
+                let mut bytes = udp.to_bytes();
+                if auto && udp.checksum == 0 {
+                    let length = bytes.len() + payload.len();
+                    let sum = ones_complement_sum(payload, ones_complement_sum(&bytes, self.pseudo_header_sum(idx, 17, length)));
+                    // a computed checksum of 0 is sent as all ones, 0 means no checksum
+                    let checksum = match !(sum as u16) {
+                        0 => 0xffff,
+                        checksum => checksum,
+                    };
+                    bytes[6..8].copy_from_slice(&checksum.to_be_bytes());
+                }
+                bytes
+            },
+            CraftLayer::Icmp(icmp) => {
+                let mut bytes = icmp.to_bytes();
+                if auto && icmp.checksum == 0 {
+                    let sum = ones_complement_sum(payload, ones_complement_sum(&bytes, 0));
+                    bytes[2..4].copy_from_slice(&(!(sum as u16)).to_be_bytes());
+                }
+                bytes
+            },
+            CraftLayer::Raw(payload) => payload.clone(),
+        }
+    }
+}
+
+
+
+impl Craft<'static> {
+    // turns a decoded packet back into a layer stack, every field keeps its decoded value
+    pub fn from_packet(packet: &Packet) -> Self {
+        let mut craft = Self::new();
+        craft.push_decoded(&packet.layer);
+        craft
+    }
+
+    fn push_mac_header(&mut self, mac_header: &MacHeader) {
+        let mut mac_header = mac_header.clone();
+        let tags = std::mem::take(&mut mac_header.vlan_tags);
+        self.layers.push(CraftLayer::Ether(mac_header));
+        self.layers.extend(tags.into_iter().map(CraftLayer::Dot1Q));
+    }
+
+    fn push_decoded(&mut self, layer: &Layer) {
+        match layer {
+            Layer::NoLayer => {},
+            Layer::Data(bytes) => self.push_raw(&bytes[..]),
+            Layer::DataLinkLayer(DataLinkLayer::ETHII(ethii)) => {
+                self.push_mac_header(&ethii.mac_header);
+                self.push_decoded(&ethii.next_layer);
+            },
+            // the length stays as decoded, the llc and snap headers are kept as raw bytes
+            Layer::DataLinkLayer(DataLinkLayer::IEEE802_3(frame)) => {
+                self.push_mac_header(&frame.mac_header);
+                let mut llc = frame.llc.map(|llc| llc.to_bytes()).unwrap_or_default();
+                llc.extend(frame.snap.map(|snap| snap.to_bytes()).unwrap_or_default());
+                self.push_raw(&llc);
+                self.push_decoded(&frame.next_layer);
+            },
+            Layer::DataLinkLayer(DataLinkLayer::UndefinedData(bytes)) => self.push_raw(&bytes[..]),
+            Layer::DataLinkLayer(_) => {},
+            Layer::NetworkLayer(NetworkLayer::Ipv4(ipv4)) => {
+                self.layers.push(CraftLayer::Ipv4(Ipv4 {
+                    version: ipv4.version,
+                    IHL: ipv4.IHL,
+                    DSPC: ipv4.DSPC,
+                    total_length: ipv4.total_length,
+                    identification: ipv4.identification,
+                    flags: ipv4.flags,
+                    fragment_offset: ipv4.fragment_offset,
+                    ttl: ipv4.ttl,
+                    protocol: ipv4.protocol,
+                    header_checksum: ipv4.header_checksum,
+                    address_src: ipv4.address_src,
+                    address_dst: ipv4.address_dst,
+                    ..Default::default()
+                }));
+                self.push_decoded(&ipv4.next_layer);
+            },
+            Layer::NetworkLayer(NetworkLayer::Ipv6(ipv6)) => {
+                self.layers.push(CraftLayer::Ipv6(Ipv6 {
+                    version: ipv6.version,
+                    traffic_class: ipv6.traffic_class,
+                    flow_label: ipv6.flow_label,
+                    payload_length: ipv6.payload_length,
+                    next_header: ipv6.next_header,
+                    hop_limit: ipv6.hop_limit,
+                    address_src: ipv6.address_src,
+                    address_dst: ipv6.address_dst,
+                    ..Default::default()
+                }));
+                self.push_decoded(&ipv6.next_layer);
+            },
+            Layer::NetworkLayer(NetworkLayer::ARP(arp)) => self.layers.push(CraftLayer::Arp(arp.clone())),
+            Layer::NetworkLayer(NetworkLayer::UndefinedData(bytes)) => self.push_raw(&bytes[..]),
+            Layer::NetworkLayer(_) => {},
+            Layer::TransportLayer(TransportLayer::TCP(tcp)) => {
+                self.layers.push(CraftLayer::Tcp(TCP {
+                    port_src: tcp.port_src,
+                    port_dst: tcp.port_dst,
+                    sequence_num: tcp.sequence_num,
+                    acknowledgement_num: tcp.acknowledgement_num,
+                    data_offset: tcp.data_offset,
+                    reserved: tcp.reserved,
+                    control_bits: tcp.control_bits,
+                    window: tcp.window,
+                    checksum: tcp.checksum,
+                    urgent_ptr: tcp.urgent_ptr,
+                    ..Default::default()
+                }));
+                self.push_raw(&tcp.payload[..]);
+            },
+            Layer::TransportLayer(TransportLayer::UDP(udp)) => {
+                self.layers.push(CraftLayer::Udp(UDP {
+                    port_src: udp.port_src,
+                    port_dst: udp.port_dst,
+                    length: udp.length,
+                    checksum: udp.checksum,
+                    ..Default::default()
+                }));
+                self.push_raw(&udp.payload[..]);
+            },
+            Layer::TransportLayer(TransportLayer::ICMP(icmp)) => {
+                self.layers.push(CraftLayer::Icmp(ICMP {
+                    message_type: icmp.message_type,
+                    code: icmp.code,
+                    checksum: icmp.checksum,
+                    rest_of_header: icmp.rest_of_header,
+                    ..Default::default()
+                }));
+                self.push_raw(&icmp.payload[..]);
+            },
+            Layer::TransportLayer(TransportLayer::UndefinedData(bytes)) => self.push_raw(&bytes[..]),
+            // sctp and igmp are not crafted
+            Layer::TransportLayer(TransportLayer::NULL | TransportLayer::SCTP(_) | TransportLayer::IGMP(_)) => {},
+            // payloads are taken from the transport layer as they are
+            Layer::ApplicationLayer(_) => {},
+            // the header of a custom layer is not known, only what it carries can be kept
+            Layer::Custom(custom) => self.push_decoded(&custom.next_layer),
+        }
+    }
+
+    fn push_raw(&mut self, bytes: &[u8]) {
+        if !bytes.is_empty() {
+            self.layers.push(CraftLayer::Raw(bytes.to_vec()));
+        }
+    }
+}
+
+
+
+#[derive(Debug, Default, Clone)]
+pub struct Crafted {
+    pub bytes: Vec<u8>,
+}
+
+impl Crafted {
+    // decodes the crafted frame as an ethernet frame
+    pub fn packet(&self) -> Result<Packet<'_>> {
+        Packet::from_slice(&self.bytes)
+    }
+}
@@ -0,0 +1,331 @@
+use std::{ffi::CString, fs::File, io::Write, os::fd::{AsRawFd, FromRawFd, OwnedFd}, str::FromStr, thread, time::{Duration, Instant}};
+
+use anyhow::{anyhow, Result};
+
+
+
+// where frames are written to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    // an existing interface, frames go out on the wire through an AF_PACKET socket
+    Interface(String),
+    // a TAP device that is created if it does not exist, frames show up as received on it
+    Tap(String),
+}
+
+// interfaces are given by name, TAP devices are prefixed with tap:
+impl FromStr for Target {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let name = s.strip_prefix("tap:").unwrap_or(s);
+
+        if name.is_empty() || name.len() >= libc::IFNAMSIZ {
+            return Err(anyhow!("Invalid interface name {s}"));
+        }
+
+        Ok(
+            match s.starts_with("tap:") {
+                true => Self::Tap(name.to_owned()),
+                false => Self::Interface(name.to_owned()),
+            }
+        )
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Interface(name) => f.write_str(name),
+            Self::Tap(name) => write!(f, "tap:{name}"),
+        }
+    }
+}
+
+
+
+// anything that frames can be written to, the injector and a Vec of frames for testing
+pub trait FrameSink {
+    fn send_frame(&mut self, frame: &[u8]) -> Result<()>;
+}
+
+impl FrameSink for Vec<Vec<u8>> {
+    fn send_frame(&mut self, frame: &[u8]) -> Result<()> {
+        self.push(frame.to_vec());
+        Ok(())
+    }
+}
+
+
+enum Device {
+    Socket(OwnedFd),
+    Tap(File),
+}
+
+pub struct Injector {
+    target: Target,
+    device: Device,
+}
+
+impl Injector {
+    pub fn open(target: &Target) -> Result<Self> {
+        let device = match target {
+            Target::Interface(name) => Device::Socket(open_packet_socket(name)?),
+            Target::Tap(name) => Device::Tap(open_tap(name)?),
+        };
+
+        Ok(
+            Self {
+                target: target.clone(),
+                device,
+            }
+        )
+    }
+
+    pub fn target(&self) -> &Target {
+        &self.target
+    }
+}
+
+impl FrameSink for Injector {
+    fn send_frame(&mut self, frame: &[u8]) -> Result<()> {
+        match &mut self.device {
+            Device::Socket(fd) => {
+                let sent = unsafe { libc::send(fd.as_raw_fd(), frame.as_ptr().cast(), frame.len(), 0) };
+                match sent < 0 {
+                    true => Err(anyhow!("Failed to send on {}: {}", self.target, std::io::Error::last_os_error())),
+                    false => Ok(()),
+                }
+            },
+            Device::Tap(file) => file.write_all(frame).map_err(|error| anyhow!("Failed to write to {}: {error}", self.target)),
+        }
+    }
+}
+
+fn open_packet_socket(name: &str) -> Result<OwnedFd> {
+    let c_name = CString::new(name)?;
+    let ifindex = unsafe { libc::if_nametoindex(c_name.as_ptr()) };
+    if ifindex == 0 {
+        return Err(anyhow!("No interface named {name}"));
+    }
+
+    // the protocol is 0 so the socket only sends and never queues received frames
+    let fd = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW, 0) };
+    if fd < 0 {
+        return Err(anyhow!("Failed to open a packet socket: {}", std::io::Error::last_os_error()));
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+    address.sll_family = libc::AF_PACKET as u16;
+    address.sll_ifindex = ifindex as i32;
+
+    let bound = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            (&address as *const libc::sockaddr_ll).cast(),
+            std::mem::size_of::<libc::sockaddr_ll>() as u32,
+        )
+    };
+    match bound < 0 {
+        true => Err(anyhow!("Failed to bind to {name}: {}", std::io::Error::last_os_error())),
+        false => Ok(fd),
+    }
+}
+
+fn open_tap(name: &str) -> Result<File> {
+    let file = File::options()
+        .read(true)
+        .write(true)
+        .open("/dev/net/tun")
+        .map_err(|error| anyhow!("Failed to open /dev/net/tun: {error}"))?;
+
+    let mut request: libc::ifreq = unsafe { std::mem::zeroed() };
+    for (dst, src) in request.ifr_name.iter_mut().zip(name.bytes()) {
+        *dst = src as libc::c_char;
+    }
+    // IFF_NO_PI so every write is exactly one ethernet frame
+    request.ifr_ifru.ifru_flags = (libc::IFF_TAP | libc::IFF_NO_PI) as libc::c_short;
+
+    if unsafe { libc::ioctl(file.as_raw_fd(), libc::TUNSETIFF, &request) } < 0 {
+        return Err(anyhow!("Failed to attach to TAP device {name}: {}", std::io::Error::last_os_error()));
+    }
+
+    // the kernel refuses writes while the device is down
+    bring_up(&mut request).map_err(|error| anyhow!("Failed to bring up TAP device {name}: {error}"))?;
+
+    Ok(file)
+}
+
+fn bring_up(request: &mut libc::ifreq) -> std::io::Result<()> {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    unsafe {
+        if libc::ioctl(fd.as_raw_fd(), libc::SIOCGIFFLAGS, &mut *request) < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        request.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short;
+        if libc::ioctl(fd.as_raw_fd(), libc::SIOCSIFFLAGS, &*request) < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rate {
+    // packets per second
+    Pps(f64),
+    // megabits per second, counting the bytes of each frame
+    Mbps(f64),
+}
+
+impl Rate {
+    // how long a frame of `length` bytes occupies at this rate
+    pub fn gap(&self, length: usize) -> Result<Duration> {
+        let seconds = match self {
+            Rate::Pps(pps) => 1.0 / pps,
+            Rate::Mbps(mbps) => length as f64 * 8.0 / (mbps * 1_000_000.0),
+        };
+
+        Duration::try_from_secs_f64(seconds).map_err(|_| anyhow!("{self:?} is too slow to send at"))
+    }
+}
+
+
+pub(crate) fn positive_number(key: &str, value: &str) -> Result<f64> {
+    value.parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite() && *number >= 0.0)
+        .ok_or(anyhow!("{key} has to be a positive number"))
+}
+
+pub(crate) fn number_above_zero(key: &str, value: &str) -> Result<f64> {
+    Some(positive_number(key, value)?)
+        .filter(|number| *number > 0.0)
+        .ok_or(anyhow!("{key} has to be above 0"))
+}
+
+// milliseconds as a duration, rejecting what does not fit one
+pub(crate) fn milliseconds(key: &str, value: &str) -> Result<Duration> {
+    Duration::try_from_secs_f64(positive_number(key, value)? / 1000.0).map_err(|_| anyhow!("{key} is too long"))
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SendOptions {
+    pub count: u64,
+    // minimum time between the start of two frames
+    pub interval: Duration,
+    pub rate: Option<Rate>,
+}
+
+impl Default for SendOptions {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            interval: Duration::ZERO,
+            rate: None,
+        }
+    }
+}
+
+impl SendOptions {
+    // reads count=<n> interval=<ms> pps=<n> mbps=<n> arguments as given to the builder's send command
+    pub fn from_args<'a>(args: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let mut options = Self::default();
+
+        for arg in args {
+            let (key, value) = arg.split_once('=').ok_or(anyhow!("Expected key=value but got {arg}"))?;
+            match key {
+                "count" => options.count = value.parse().map_err(|_| anyhow!("count has to be a whole number"))?,
+                "interval" => options.interval = milliseconds(key, value)?,
+                "pps" => options.rate = Some(Rate::Pps(number_above_zero(key, value)?)),
+                "mbps" => options.rate = Some(Rate::Mbps(number_above_zero(key, value)?)),
+                _ => return Err(anyhow!("Unknown send option {key}")),
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn gap(&self, length: usize) -> Result<Duration> {
+        Ok(self.interval.max(self.rate.map_or(Ok(Duration::ZERO), |rate| rate.gap(length))?))
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SendStats {
+    pub frames: u64,
+    pub bytes: u64,
+    // records that were not sent, like replayed records of a link type other than ethernet
+    pub skipped: u64,
+}
+
+
+// keeps frames on a schedule measured from the first frame, so time spent sending does not add up as drift
+pub struct Pacer {
+    start: Instant,
+    due: Duration,
+}
+
+impl Default for Pacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pacer {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            due: Duration::ZERO,
+        }
+    }
+
+    // blocks until the next frame is due then schedules the one after it `gap` later, fails when that is
+    // further away than the clock can tell
+    pub fn wait(&mut self, gap: Duration) -> Result<()> {
+        let elapsed = self.start.elapsed();
+        if self.due > elapsed {
+            thread::sleep(self.due - elapsed);
+        }
+        self.due = self.due
+            .checked_add(gap)
+            .filter(|due| self.start.checked_add(*due).is_some())
+            .ok_or(anyhow!("The next frame would be due too far in the future"))?;
+
+        Ok(())
+    }
+}
+
+
+// writes `frame` options.count times
+pub fn send(sink: &mut impl FrameSink, frame: &[u8], options: &SendOptions) -> Result<SendStats> {
+    let mut stats = SendStats::default();
+    let mut pacer = Pacer::new();
+    let gap = options.gap(frame.len())?;
+
+    for _ in 0..options.count {
+        pacer.wait(gap)?;
+        sink.send_frame(frame)?;
+
+        stats.frames += 1;
+        stats.bytes += frame.len() as u64;
+    }
+
+    Ok(stats)
+}
+
+pub fn send_to(target: &Target, frame: &[u8], options: &SendOptions) -> Result<SendStats> {
+    send(&mut Injector::open(target)?, frame, options)
+}
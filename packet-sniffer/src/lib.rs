@@ -1,2 +1,9 @@
+pub mod craft;
+pub mod inject;
+pub mod multicast;
+pub mod neighbors;
 pub mod packet;
-pub mod packet_builder;
\ No newline at end of file
+pub mod packet_builder;
+pub mod pcap;
+pub mod reassembly;
+pub mod replay;
\ No newline at end of file
@@ -0,0 +1,277 @@
+use std::{collections::HashMap, fmt::Display, path::Path, time::Duration};
+
+use anyhow::Result;
+use byte_slice::{Bytes, Ipv4addr};
+
+use crate::{packet::{network::NetworkLayer, transport::{igmp::{record_type, GroupRecord, IgmpMessage, IGMP}, TransportLayer}, Layer, Packet}, pcap};
+
+
+// Which hosts are members of which multicast groups, learned passively from igmp. Hosts join with reports and
+// leave with v2 leaves or v3 records that switch to an empty include list, members that stop answering
+// queries are dropped after the group membership interval. Every change is kept in order for reporting.
+
+// robustness 2 times the 125 second query interval plus the 10 second max response time (RFC 3376 8.4)
+pub const MEMBERSHIP_INTERVAL: Duration = Duration::from_secs(260);
+
+
+
+// include lists the only sources the host wants traffic from, exclude the sources it does not
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    Include,
+    // v1 and v2 members receive every source
+    #[default]
+    Exclude,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Membership {
+    // the igmp version of the last report
+    pub version: u8,
+    pub filter_mode: FilterMode,
+    pub sources: Vec<Ipv4addr>,
+    pub joined: Duration,
+    pub last_report: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Joined,
+    Left,
+    SourcesChanged,
+    // no report came within the membership interval
+    Expired,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MembershipEvent {
+    pub timestamp: Duration,
+    pub host: Ipv4addr,
+    pub group: Ipv4addr,
+    pub change: Change,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Querier {
+    pub version: u8,
+    pub last_query: Duration,
+}
+
+
+
+#[derive(Debug, Default, Clone)]
+pub struct MembershipTable {
+    memberships: HashMap<(Ipv4addr, Ipv4addr), Membership>,
+    queriers: HashMap<Ipv4addr, Querier>,
+    events: Vec<MembershipEvent>,
+    first_seen: Option<Duration>,
+}
+
+impl MembershipTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // returns whether the packet was igmp
+    pub fn observe(&mut self, packet: &Packet, timestamp: Duration) -> bool {
+        // members that stopped reporting before this packet left before it too
+        self.expire(timestamp);
+
+        let mut host = None;
+        let mut igmp = None;
+        for layer in packet.layers() {
+            match layer {
+                Layer::NetworkLayer(NetworkLayer::Ipv4(ipv4)) => host = Some(ipv4.address_src),
+                Layer::TransportLayer(TransportLayer::IGMP(message)) => igmp = Some(message),
+                _ => {},
+            }
+        }
+        let (Some(host), Some(igmp)) = (host, igmp) else {
+            return false;
+        };
+
+        self.first_seen.get_or_insert(timestamp);
+        self.apply(host, igmp, timestamp);
+
+        true
+    }
+
+    fn apply(&mut self, host: Ipv4addr, igmp: &IGMP, timestamp: Duration) {
+        match &igmp.message {
+            IgmpMessage::Query(query) => {
+                self.queriers.insert(host, Querier { version: query.version, last_query: timestamp });
+            },
+            IgmpMessage::ReportV1(group) => self.join(host, *group, 1, FilterMode::Exclude, Vec::new(), timestamp),
+            IgmpMessage::ReportV2(group) => self.join(host, *group, 2, FilterMode::Exclude, Vec::new(), timestamp),
+            IgmpMessage::Leave(group) => self.leave(host, *group, timestamp),
+            IgmpMessage::ReportV3(records) => {
+                for record in records {
+                    self.apply_record(host, record, timestamp);
+                }
+            },
+            IgmpMessage::Unknown(_) => {},
+        }
+    }
+
+    fn apply_record(&mut self, host: Ipv4addr, record: &GroupRecord, timestamp: Duration) {
+        let group = record.group;
+        let current = self.memberships.get(&(host, group)).map(|membership| (membership.filter_mode, membership.sources.clone()));
+        let (filter_mode, mut sources) = current.clone().unwrap_or((FilterMode::Include, Vec::new()));
+
+        let (filter_mode, sources) = match record.record_type {
+            record_type::MODE_IS_INCLUDE | record_type::CHANGE_TO_INCLUDE_MODE => (FilterMode::Include, record.sources.clone()),
+            record_type::MODE_IS_EXCLUDE | record_type::CHANGE_TO_EXCLUDE_MODE => (FilterMode::Exclude, record.sources.clone()),
+            // allowing a source adds it to an include list and takes it off an exclude list, blocking the reverse
+            record_type::ALLOW_NEW_SOURCES | record_type::BLOCK_OLD_SOURCES => {
+                let adds = (record.record_type == record_type::ALLOW_NEW_SOURCES) == (filter_mode == FilterMode::Include);
+                match adds {
+                    true => for source in &record.sources {
+                        if !sources.contains(source) {
+                            sources.push(*source);
+                        }
+                    },
+                    false => sources.retain(|source| !record.sources.contains(source)),
+                }
+                (filter_mode, sources)
+            },
+            _ => return,
+        };
+
+        // an empty include list is no membership at all
+        if filter_mode == FilterMode::Include && sources.is_empty() {
+            return self.leave(host, group, timestamp);
+        }
+        let changed = current.is_some_and(|current| current != (filter_mode, sources.clone()));
+        self.join(host, group, 3, filter_mode, sources, timestamp);
+        if changed {
+            self.record(host, group, Change::SourcesChanged, timestamp);
+        }
+    }
+
+    fn join(&mut self, host: Ipv4addr, group: Ipv4addr, version: u8, filter_mode: FilterMode, sources: Vec<Ipv4addr>, timestamp: Duration) {
+        match self.memberships.get_mut(&(host, group)) {
+            Some(membership) => {
+                membership.version = version;
+                membership.filter_mode = filter_mode;
+                membership.sources = sources;
+                membership.last_report = timestamp;
+            },
+            None => {
+                self.memberships.insert((host, group), Membership { version, filter_mode, sources, joined: timestamp, last_report: timestamp });
+                self.record(host, group, Change::Joined, timestamp);
+            },
+        }
+    }
+
+    fn leave(&mut self, host: Ipv4addr, group: Ipv4addr, timestamp: Duration) {
+        if self.memberships.remove(&(host, group)).is_some() {
+            self.record(host, group, Change::Left, timestamp);
+        }
+    }
+
+    fn record(&mut self, host: Ipv4addr, group: Ipv4addr, change: Change, timestamp: Duration) {
+        self.events.push(MembershipEvent { timestamp, host, group, change });
+    }
+
+    // drops members that did not report within the membership interval before `now`
+    pub fn expire(&mut self, now: Duration) {
+        let mut expired: Vec<_> = self.memberships
+            .iter()
+            .filter(|(_, membership)| membership.last_report + MEMBERSHIP_INTERVAL < now)
+            .map(|(key, membership)| (*key, membership.last_report + MEMBERSHIP_INTERVAL))
+            .collect();
+        expired.sort_by_key(|((host, group), expires)| (*expires, group.0, host.0));
+
+        for ((host, group), expires) in expired {
+            self.memberships.remove(&(host, group));
+            self.record(host, group, Change::Expired, expires);
+        }
+    }
+
+    pub fn get(&self, host: Ipv4addr, group: Ipv4addr) -> Option<&Membership> {
+        self.memberships.get(&(host, group))
+    }
+
+    // the hosts that are members of the group, ordered by address
+    pub fn members(&self, group: Ipv4addr) -> Vec<(Ipv4addr, &Membership)> {
+        let mut members: Vec<_> = self.memberships
+            .iter()
+            .filter(|((_, member_group), _)| *member_group == group)
+            .map(|((host, _), membership)| (*host, membership))
+            .collect();
+        members.sort_by_key(|(host, _)| host.0);
+        members
+    }
+
+    // ordered by group, then host
+    pub fn iter(&self) -> impl Iterator<Item = (Ipv4addr, Ipv4addr, &Membership)> {
+        let mut memberships: Vec<_> = self.memberships.iter().map(|((host, group), membership)| (*group, *host, membership)).collect();
+        memberships.sort_by_key(|(group, host, _)| (group.0, host.0));
+        memberships.into_iter()
+    }
+
+    pub fn queriers(&self) -> &HashMap<Ipv4addr, Querier> {
+        &self.queriers
+    }
+
+    // every join, leave and change in the order it happened
+    pub fn events(&self) -> &[MembershipEvent] {
+        &self.events
+    }
+
+    pub fn len(&self) -> usize {
+        self.memberships.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.memberships.is_empty()
+    }
+}
+
+impl Display for MembershipTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // times are seconds since the first igmp packet
+        let start = self.first_seen.unwrap_or_default();
+        let seconds = |timestamp: Duration| timestamp.saturating_sub(start).as_secs_f64();
+
+        writeln!(f, "{:<15}  {:<15}  {:<7}  {:<7}  {:>10}  {:>11}  Sources", "Group", "Host", "Version", "Mode", "Joined", "Last report")?;
+        for (group, host, membership) in self.iter() {
+            writeln!(
+                f,
+                "{:<15}  {:<15}  {:<7}  {:<7}  {:>10.3}  {:>11.3}  {}",
+                group.to_string(),
+                host.to_string(),
+                membership.version,
+                format!("{:?}", membership.filter_mode),
+                seconds(membership.joined),
+                seconds(membership.last_report),
+                membership.sources.iter().map(ToString::to_string).collect::<Vec<_>>().join(","),
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{:>10}  {:<15}  {:<15}  Change", "Time", "Group", "Host")?;
+        for event in &self.events {
+            writeln!(f, "{:>10.3}  {:<15}  {:<15}  {:?}", seconds(event.timestamp), event.group.to_string(), event.host.to_string(), event.change)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+// the memberships of a capture as they were when its last frame was captured, with their history
+pub fn from_file(path: impl AsRef<Path>) -> Result<MembershipTable> {
+    let mut table = MembershipTable::new();
+    let mut last_timestamp = Duration::ZERO;
+
+    for record in pcap::read_file(path)? {
+        if let Ok(packet) = Packet::from_link_type(record.link_type, &mut Bytes::from_slice(&record.data)) {
+            table.observe(&packet, record.timestamp);
+        }
+        last_timestamp = last_timestamp.max(record.timestamp);
+    }
+    table.expire(last_timestamp);
+
+    Ok(table)
+}
@@ -0,0 +1,198 @@
+use std::{collections::HashMap, fmt::Display, path::Path, time::Duration};
+
+use anyhow::Result;
+use byte_slice::{Bytes, MacAddress};
+
+use crate::{packet::{data_link::{cdp::{CdpTlv, CDP}, lldp::{Dot1Tlv, Tlv, LLDP}, DataLinkLayer}, Layer, Packet}, pcap};
+
+
+
+// what a switch or router announced about itself and the port the frame left on
+#[derive(Debug, Clone, PartialEq)]
+pub struct Neighbor {
+    pub protocol: &'static str,
+    pub chassis_id: String,
+    pub port_id: String,
+    pub port_description: Option<String>,
+    pub system_name: Option<String>,
+    pub system_description: Option<String>,
+    // the hardware model, only cdp sends it
+    pub platform: Option<String>,
+    pub capabilities: Vec<&'static str>,
+    pub management_addresses: Vec<String>,
+    pub native_vlan: Option<u16>,
+    pub ttl: Duration,
+    pub last_seen: Duration,
+}
+
+impl Neighbor {
+    fn from_lldp(lldp: &LLDP, timestamp: Duration) -> Self {
+        let mut neighbor = Self::new("LLDP", timestamp);
+        neighbor.chassis_id = lldp.chassis_id().map(ToString::to_string).unwrap_or_default();
+        neighbor.port_id = lldp.port_id().map(ToString::to_string).unwrap_or_default();
+        neighbor.ttl = Duration::from_secs(lldp.ttl().unwrap_or_default() as u64);
+
+        for tlv in &lldp.tlvs {
+            match tlv {
+                Tlv::PortDescription(description) => neighbor.port_description = Some(description.clone()),
+                Tlv::SystemName(name) => neighbor.system_name = Some(name.clone()),
+                Tlv::SystemDescription(description) => neighbor.system_description = Some(description.clone()),
+                Tlv::Capabilities { enabled, .. } => neighbor.capabilities = enabled.names(),
+                Tlv::ManagementAddress { address, .. } => neighbor.management_addresses.push(address.to_string()),
+                Tlv::Ieee802_1(Dot1Tlv::PortVlanId(vlan)) => neighbor.native_vlan = Some(*vlan),
+                _ => {},
+            }
+        }
+
+        neighbor
+    }
+
+    fn from_cdp(cdp: &CDP, timestamp: Duration) -> Self {
+        let mut neighbor = Self::new("CDP", timestamp);
+        neighbor.ttl = Duration::from_secs(cdp.ttl as u64);
+
+        for tlv in &cdp.tlvs {
+            match tlv {
+                CdpTlv::DeviceId(device_id) => {
+                    neighbor.chassis_id = device_id.clone();
+                    neighbor.system_name = Some(device_id.clone());
+                },
+                CdpTlv::PortId(port_id) => neighbor.port_id = port_id.clone(),
+                CdpTlv::Capabilities(capabilities) => neighbor.capabilities = capabilities.names(),
+                CdpTlv::SoftwareVersion(version) => neighbor.system_description = Some(version.clone()),
+                CdpTlv::Platform(platform) => neighbor.platform = Some(platform.clone()),
+                CdpTlv::ManagementAddresses(addresses) => {
+                    neighbor.management_addresses = addresses.iter().map(ToString::to_string).collect();
+                },
+                // the interface addresses only count when there are no management addresses
+                CdpTlv::Addresses(addresses) if neighbor.management_addresses.is_empty() => {
+                    neighbor.management_addresses = addresses.iter().map(ToString::to_string).collect();
+                },
+                CdpTlv::NativeVlan(vlan) => neighbor.native_vlan = Some(*vlan),
+                _ => {},
+            }
+        }
+
+        neighbor
+    }
+
+    fn new(protocol: &'static str, last_seen: Duration) -> Self {
+        Self {
+            protocol,
+            chassis_id: String::new(),
+            port_id: String::new(),
+            port_description: None,
+            system_name: None,
+            system_description: None,
+            platform: None,
+            capabilities: Vec::new(),
+            management_addresses: Vec::new(),
+            native_vlan: None,
+            ttl: Duration::ZERO,
+            last_seen,
+        }
+    }
+
+    pub fn expires(&self) -> Duration {
+        self.last_seen + self.ttl
+    }
+}
+
+
+// neighbors learned passively from lldp and cdp frames, keyed by the source address of the frame
+#[derive(Debug, Default, Clone)]
+pub struct NeighborTable {
+    neighbors: HashMap<MacAddress, Neighbor>,
+}
+
+impl NeighborTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // returns whether the packet announced or withdrew a neighbor
+    pub fn observe(&mut self, packet: &Packet, timestamp: Duration) -> bool {
+        let (address_src, next_layer) = match &packet.layer {
+            Layer::DataLinkLayer(DataLinkLayer::ETHII(ethii)) => (ethii.mac_header.address_src, ethii.next_layer.as_ref()),
+            Layer::DataLinkLayer(DataLinkLayer::IEEE802_3(frame)) => (frame.mac_header.address_src, frame.next_layer.as_ref()),
+            _ => return false,
+        };
+
+        let neighbor = match next_layer {
+            Layer::DataLinkLayer(DataLinkLayer::LLDP(lldp)) => Neighbor::from_lldp(lldp, timestamp),
+            Layer::DataLinkLayer(DataLinkLayer::CDP(cdp)) => Neighbor::from_cdp(cdp, timestamp),
+            _ => return false,
+        };
+
+        // a ttl of 0 is sent when a port shuts down
+        match neighbor.ttl.is_zero() {
+            true => { self.neighbors.remove(&address_src); },
+            false => { self.neighbors.insert(address_src, neighbor); },
+        }
+
+        true
+    }
+
+    // drops neighbors whose ttl ran out before `now`
+    pub fn expire(&mut self, now: Duration) {
+        self.neighbors.retain(|_, neighbor| neighbor.expires() >= now);
+    }
+
+    pub fn get(&self, address: &MacAddress) -> Option<&Neighbor> {
+        self.neighbors.get(address)
+    }
+
+    pub fn len(&self) -> usize {
+        self.neighbors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.neighbors.is_empty()
+    }
+
+    // ordered by source address
+    pub fn iter(&self) -> impl Iterator<Item = (&MacAddress, &Neighbor)> {
+        let mut neighbors: Vec<_> = self.neighbors.iter().collect();
+        neighbors.sort_by_key(|(address, _)| address.to_u64());
+        neighbors.into_iter()
+    }
+}
+
+impl Display for NeighborTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:<17}  {:<5}  {:<24}  {:<20}  {:<6}  {:<20}  Capabilities", "Source", "Proto", "System", "Port", "VLAN", "Address")?;
+
+        for (address, neighbor) in self.iter() {
+            writeln!(
+                f,
+                "{:<17}  {:<5}  {:<24}  {:<20}  {:<6}  {:<20}  {}",
+                address.to_string(),
+                neighbor.protocol,
+                neighbor.system_name.as_deref().unwrap_or(&neighbor.chassis_id),
+                neighbor.port_id,
+                neighbor.native_vlan.map(|vlan| vlan.to_string()).unwrap_or_default(),
+                neighbor.management_addresses.first().map(String::as_str).unwrap_or_default(),
+                neighbor.capabilities.join(","),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+
+// the neighbors of a capture as they were when its last frame was captured
+pub fn from_file(path: impl AsRef<Path>) -> Result<NeighborTable> {
+    let mut table = NeighborTable::new();
+    let mut last_timestamp = Duration::ZERO;
+
+    for record in pcap::read_file(path)? {
+        if let Ok(packet) = Packet::from_link_type(record.link_type, &mut Bytes::from_slice(&record.data)) {
+            table.observe(&packet, record.timestamp);
+        }
+        last_timestamp = last_timestamp.max(record.timestamp);
+    }
+    table.expire(last_timestamp);
+
+    Ok(table)
+}
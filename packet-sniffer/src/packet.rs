@@ -1,10 +1,15 @@
 use byte_slice::Bytes;
+use anyhow::Result;
 use strum_macros::AsRefStr;
+pub mod application;
 pub mod data_link;
+pub mod decode_as;
+pub mod dissector;
 pub mod network;
+pub mod reader;
 pub mod transport;
 
-#[derive(Debug, Default, AsRefStr)]
+#[derive(Debug, Default, Clone, AsRefStr)]
 pub enum Layer<'a> {
     #[default]
     NoLayer,
@@ -14,14 +19,92 @@ pub enum Layer<'a> {
     DataLinkLayer(data_link::DataLinkLayer<'a>),
     NetworkLayer(network::NetworkLayer<'a>),
     TransportLayer(transport::TransportLayer<'a>),
+    ApplicationLayer(application::ApplicationLayer<'a>),
+
+    // produced by dissectors registered outside this crate
+    Custom(CustomLayer<'a>),
+}
+
+impl<'a> Layer<'a> {
+    // the layer this one carries, None for layers that end the packet. Layers decrypted from esp borrow the
+    // plaintext the esp layer owns rather than the captured bytes, so they live as long as the layer does.
+    pub fn next_layer(&self) -> Option<&Layer<'_>> {
+        use data_link::DataLinkLayer;
+        use network::NetworkLayer;
+        use transport::TransportLayer;
+
+        match self {
+            Layer::DataLinkLayer(DataLinkLayer::ETHII(layer)) => Some(&layer.next_layer),
+            Layer::DataLinkLayer(DataLinkLayer::IEEE802_3(layer)) => Some(&layer.next_layer),
+            Layer::DataLinkLayer(DataLinkLayer::PPP(layer)) => Some(&layer.next_layer),
+            Layer::DataLinkLayer(DataLinkLayer::PPPoE(layer)) => Some(&layer.next_layer),
+            Layer::DataLinkLayer(DataLinkLayer::HDLC(layer)) => Some(&layer.next_layer),
+            Layer::DataLinkLayer(DataLinkLayer::CiscoHDLC(layer)) => Some(&layer.next_layer),
+            Layer::NetworkLayer(NetworkLayer::Ipv4(layer)) => Some(&layer.next_layer),
+            Layer::NetworkLayer(NetworkLayer::Ipv6(layer)) => Some(&layer.next_layer),
+            Layer::NetworkLayer(NetworkLayer::IPX(layer)) => Some(&layer.next_layer),
+            Layer::NetworkLayer(NetworkLayer::NetBIOS(layer)) => Some(&layer.next_layer),
+            Layer::NetworkLayer(NetworkLayer::MPLS(layer)) => Some(&layer.next_layer),
+            Layer::NetworkLayer(NetworkLayer::AH(layer)) => Some(&layer.next_layer),
+            Layer::NetworkLayer(NetworkLayer::ESP(layer)) => layer.decrypted.as_ref().map(|decrypted| decrypted.layer()),
+            Layer::NetworkLayer(NetworkLayer::GRE(layer)) => Some(&layer.next_layer),
+            Layer::NetworkLayer(NetworkLayer::ERSPAN(layer)) => Some(&layer.next_layer),
+            Layer::NetworkLayer(NetworkLayer::VXLAN(layer)) => Some(&layer.next_layer),
+            Layer::NetworkLayer(NetworkLayer::Geneve(layer)) => Some(&layer.next_layer),
+            Layer::TransportLayer(TransportLayer::TCP(layer)) => Some(&layer.next_layer),
+            Layer::TransportLayer(TransportLayer::UDP(layer)) => Some(&layer.next_layer),
+            Layer::Custom(layer) => Some(&layer.next_layer),
+            _ => None,
+        }
+    }
 }
 
 pub trait LayerTrait {
-    fn next_layer(&self) -> &Layer;
+    fn next_layer(&self) -> &Layer<'_>;
 }
 
 
-#[derive(Debug, Default)]
+// a protocol the layer enums do not know about, described by its decoded fields
+#[derive(Debug, Default, Clone)]
+pub struct CustomLayer<'a> {
+    pub protocol: String,
+    pub fields: Vec<(String, String)>,
+    pub next_layer: Box<Layer<'a>>,
+}
+
+impl<'a> LayerTrait for CustomLayer<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+
+#[derive(Debug, Default, Clone)]
 pub struct Packet<'a> {
     pub layer: Layer<'a>
-}
\ No newline at end of file
+}
+
+impl<'a> Packet<'a> {
+    // decodes an ethernet frame
+    pub fn from_slice(slice: &'a [u8]) -> Result<Self> {
+        Self::from_bytes(&mut Bytes::from_slice(slice))
+    }
+
+    pub fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        Self::from_link_type(crate::pcap::LINKTYPE_ETHERNET, bytes)
+    }
+
+    // every layer from the outermost in, tunnels repeat the layers they carry
+    pub fn layers(&self) -> impl Iterator<Item = &Layer<'_>> {
+        std::iter::successors(Some(&self.layer), |layer| layer.next_layer())
+    }
+
+    // decodes a frame of any link type that has a dissector registered
+    pub fn from_link_type(link_type: u16, bytes: &mut Bytes<'a>) -> Result<Self> {
+        Ok(
+            Self {
+                layer: dissector::dispatch(dissector::Key::LinkType(link_type), bytes)?
+            }
+        )
+    }
+}
@@ -0,0 +1,30 @@
+use byte_slice::Bytes;
+
+pub mod bgp;
+pub mod dns;
+pub mod http;
+pub mod rip;
+pub mod tls;
+use dns::DNS;
+
+
+// https://www.iana.org/assignments/service-names-port-numbers/service-names-port-numbers.xhtml
+
+
+#[derive(Debug, Default, Clone)]
+pub enum ApplicationLayer<'a> {
+    #[default]
+    NULL,
+
+    UndefinedData(Bytes<'a>),
+    // dns over tcp can carry several messages in one segment, over udp there is always one
+    DNS(Vec<DNS>),
+    // several messages per segment as well, see bgp::BgpSessions for messages that span segments
+    BGP(Vec<bgp::BgpMessage>),
+    RIP(rip::RIP),
+    RIPng(rip::RIPng),
+    // the complete messages of a segment, see http::HttpTransactions for messages that span segments
+    HTTP(Vec<http::HttpMessage>),
+    // the complete records of a segment, see tls::TlsSessions for records and handshakes that span segments
+    TLS(Vec<tls::TlsRecord>),
+}
@@ -0,0 +1,636 @@
+use std::{collections::HashMap, fmt::Display};
+
+use anyhow::{anyhow, Result};
+use byte_slice::{Bytes, Ipv4addr, Ipv6addr, MacAddress, SliceToUnsigned};
+
+use crate::{packet::{dissector::{Context, Dissector}, network::IpAddress, reader::Reader, Layer, Packet}, reassembly::{Flow, TcpReassembler}};
+
+use super::ApplicationLayer;
+
+
+// BGP-4 (RFC 4271) on tcp port 179. Every message starts with 16 bytes of ones, its length and its type.
+// Two things an update is decoded with are negotiated in the open messages: the size of as numbers (RFC 6793)
+// and whether prefixes carry path identifiers (RFC 7911). A segment alone is decoded with 4 byte as numbers
+// when the as path fits them and without path identifiers, BgpSessions follows the opens of each connection.
+
+
+
+pub const PORT: u16 = 179;
+const MARKER: [u8; 16] = [0xff; 16];
+const HEADER_LENGTH: usize = 19;
+
+pub mod message_type {
+    pub const OPEN: u8 = 1;
+    pub const UPDATE: u8 = 2;
+    pub const NOTIFICATION: u8 = 3;
+    pub const KEEPALIVE: u8 = 4;
+    pub const ROUTE_REFRESH: u8 = 5;
+}
+
+pub mod afi {
+    pub const IPV4: u16 = 1;
+    pub const IPV6: u16 = 2;
+    pub const L2VPN: u16 = 25;
+}
+
+pub mod safi {
+    pub const UNICAST: u8 = 1;
+    pub const MULTICAST: u8 = 2;
+    pub const EVPN: u8 = 70;
+}
+
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Capability {
+    Multiprotocol { afi: u16, safi: u8 },
+    RouteRefresh,
+    ExtendedMessage,
+    GracefulRestart { flags: u8, restart_time: u16 },
+    FourOctetAs(u32),
+    // 1 receive, 2 send, 3 both
+    AddPath(Vec<(u16, u8, u8)>),
+    EnhancedRouteRefresh,
+    Unknown { code: u8, value: Vec<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Open {
+    pub version: u8,
+    // AS_TRANS (23456) when the real as number needs 4 bytes
+    pub my_as: u16,
+    pub hold_time: u16,
+    pub bgp_identifier: Ipv4addr,
+    pub capabilities: Vec<Capability>,
+}
+
+impl Open {
+    pub fn asn(&self) -> u32 {
+        self.capabilities
+            .iter()
+            .find_map(|capability| match capability {
+                Capability::FourOctetAs(asn) => Some(*asn),
+                _ => None,
+            })
+            .unwrap_or(self.my_as as u32)
+    }
+
+    pub fn has(&self, matches: impl Fn(&Capability) -> bool) -> bool {
+        self.capabilities.iter().any(matches)
+    }
+
+    // the address families the speaker can send (2) or receive (1) path identifiers for
+    fn add_path(&self, direction: u8) -> impl Iterator<Item = (u16, u8)> + '_ {
+        self.capabilities
+            .iter()
+            .filter_map(|capability| match capability {
+                Capability::AddPath(families) => Some(families),
+                _ => None,
+            })
+            .flatten()
+            .filter(move |(_, _, send_receive)| send_receive & direction != 0)
+            .map(|(afi, safi, _)| (*afi, *safi))
+    }
+}
+
+
+// what the opens of a connection negotiated for the updates of one direction
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SessionOptions {
+    // None until both opens were seen
+    pub four_octet_as: Option<bool>,
+    pub add_path: Vec<(u16, u8)>,
+}
+
+impl SessionOptions {
+    // `sender` is the open of the speaker the updates come from
+    pub fn negotiate(sender: &Open, receiver: &Open) -> Self {
+        let four_octet_as = |open: &Open| open.has(|capability| matches!(capability, Capability::FourOctetAs(_)));
+        let receives: Vec<_> = receiver.add_path(1).collect();
+
+        Self {
+            four_octet_as: Some(four_octet_as(sender) && four_octet_as(receiver)),
+            add_path: sender.add_path(2).filter(|family| receives.contains(family)).collect(),
+        }
+    }
+
+    fn has_path_id(&self, afi: u16, safi: u8) -> bool {
+        self.add_path.contains(&(afi, safi))
+    }
+}
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Prefix {
+    pub path_id: Option<u32>,
+    pub address: IpAddress,
+    pub length: u8,
+}
+
+impl Display for Prefix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.address, self.length)
+    }
+}
+
+
+// RFC 4364, shown as administrator:assigned number
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RouteDistinguisher(pub [u8; 8]);
+
+impl Display for RouteDistinguisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = &self.0;
+        match value[0..2].to_u16() {
+            0 => write!(f, "{}:{}", value[2..4].to_u16(), value[4..8].to_u32()),
+            1 => write!(f, "{}:{}", Ipv4addr(value[2..6].to_u32()), value[6..8].to_u16()),
+            _ => write!(f, "{}:{}", value[2..6].to_u32(), value[6..8].to_u16()),
+        }
+    }
+}
+
+// RFC 7432 and RFC 9136, labels are the 20 bit mpls label or the vni of vxlan
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvpnRoute {
+    EthernetAutoDiscovery { rd: RouteDistinguisher, esi: [u8; 10], ethernet_tag: u32, label: u32 },
+    MacIpAdvertisement { rd: RouteDistinguisher, esi: [u8; 10], ethernet_tag: u32, mac: MacAddress, ip: Option<IpAddress>, labels: Vec<u32> },
+    InclusiveMulticast { rd: RouteDistinguisher, ethernet_tag: u32, originating_router: IpAddress },
+    EthernetSegment { rd: RouteDistinguisher, esi: [u8; 10], originating_router: IpAddress },
+    IpPrefix { rd: RouteDistinguisher, esi: [u8; 10], ethernet_tag: u32, prefix: Prefix, gateway: IpAddress, label: u32 },
+    Unknown { route_type: u8, value: Vec<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Nlri {
+    Prefixes(Vec<Prefix>),
+    Evpn(Vec<EvpnRoute>),
+    Unknown(Vec<u8>),
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsPathSegment {
+    // 1 AS_SET, 2 AS_SEQUENCE
+    pub segment_type: u8,
+    pub asns: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LargeCommunity {
+    pub global_administrator: u32,
+    pub local_data_1: u32,
+    pub local_data_2: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathAttribute {
+    // 0 IGP, 1 EGP, 2 INCOMPLETE
+    Origin(u8),
+    AsPath(Vec<AsPathSegment>),
+    NextHop(Ipv4addr),
+    MultiExitDisc(u32),
+    LocalPref(u32),
+    AtomicAggregate,
+    Aggregator { asn: u32, address: Ipv4addr },
+    Communities(Vec<u32>),
+    OriginatorId(Ipv4addr),
+    ClusterList(Vec<Ipv4addr>),
+    MpReachNlri { afi: u16, safi: u8, next_hops: Vec<IpAddress>, nlri: Nlri },
+    MpUnreachNlri { afi: u16, safi: u8, withdrawn: Nlri },
+    ExtendedCommunities(Vec<u64>),
+    As4Path(Vec<AsPathSegment>),
+    LargeCommunities(Vec<LargeCommunity>),
+    Unknown { type_code: u8, value: Vec<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    // optional, transitive, partial and extended length
+    pub flags: u8,
+    pub attribute: PathAttribute,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Update {
+    pub withdrawn_routes: Vec<Prefix>,
+    pub attributes: Vec<Attribute>,
+    pub nlri: Vec<Prefix>,
+}
+
+impl Update {
+    pub fn attribute(&self, matches: impl Fn(&PathAttribute) -> bool) -> Option<&PathAttribute> {
+        self.attributes.iter().map(|attribute| &attribute.attribute).find(|attribute| matches(attribute))
+    }
+
+    // an update without routes or attributes marks the end of the initial routing table
+    pub fn is_end_of_rib(&self) -> bool {
+        self.withdrawn_routes.is_empty() && self.nlri.is_empty() && self.attributes.is_empty()
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub code: u8,
+    pub subcode: u8,
+    pub data: Vec<u8>,
+}
+
+impl Notification {
+    pub fn code_name(&self) -> &'static str {
+        match self.code {
+            1 => "Message Header Error",
+            2 => "OPEN Message Error",
+            3 => "UPDATE Message Error",
+            4 => "Hold Timer Expired",
+            5 => "Finite State Machine Error",
+            6 => "Cease",
+            7 => "ROUTE-REFRESH Message Error",
+            _ => "Unknown",
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BgpMessage {
+    Open(Open),
+    Update(Update),
+    Notification(Notification),
+    Keepalive,
+    // RFC 7313 uses the subtype for the beginning and end of an enhanced refresh
+    RouteRefresh { afi: u16, subtype: u8, safi: u8 },
+}
+
+impl BgpMessage {
+    // a segment can carry several messages, one that continues in the next segment is left in `bytes`
+    pub fn from_tcp_bytes(bytes: &mut Bytes) -> Result<Vec<Self>> {
+        let mut messages = Vec::new();
+
+        while let Some(length) = message_length(&bytes[..])? {
+            if bytes.len() < length {
+                break;
+            }
+            messages.push(Self::from_message(&bytes[..length], &SessionOptions::default())?);
+            bytes.shift_first(length)?;
+        }
+
+        match messages.is_empty() {
+            true => Err(anyhow!("No complete BGP message in the segment")),
+            false => Ok(messages),
+        }
+    }
+
+    // one message including its header
+    pub fn from_message(message: &[u8], options: &SessionOptions) -> Result<Self> {
+        let length = message_length(message)?.ok_or(anyhow!("BGP message is shorter than its header"))?;
+        let mut reader = Reader::new("BGP", message.get(HEADER_LENGTH..length).ok_or(anyhow!("BGP message is truncated"))?);
+
+        let message = match message[18] {
+            message_type::OPEN => BgpMessage::Open(reader.open()?),
+            message_type::UPDATE => BgpMessage::Update(reader.update(options)?),
+            message_type::NOTIFICATION => BgpMessage::Notification(Notification { code: reader.u8()?, subcode: reader.u8()?, data: reader.rest().to_vec() }),
+            message_type::KEEPALIVE => BgpMessage::Keepalive,
+            message_type::ROUTE_REFRESH => BgpMessage::RouteRefresh { afi: reader.u16()?, subtype: reader.u8()?, safi: reader.u8()? },
+            message_type => return Err(anyhow!("Unknown BGP message type {message_type}")),
+        };
+
+        Ok(message)
+    }
+}
+
+// the length of the message a stream starts with, None while its header is incomplete
+pub fn message_length(stream: &[u8]) -> Result<Option<usize>> {
+    if stream.len() < HEADER_LENGTH {
+        return Ok(None);
+    }
+    if stream[0..16] != MARKER {
+        return Err(anyhow!("BGP message does not start with the marker"));
+    }
+
+    // up to 4096 bytes, or 65535 with RFC 8654 extended messages
+    let length = stream[16..18].to_u16() as usize;
+    match length >= HEADER_LENGTH {
+        true => Ok(Some(length)),
+        false => Err(anyhow!("BGP message length {length} is shorter than its header")),
+    }
+}
+
+
+
+// follows the bgp connections of a capture and decodes their messages once all their segments arrived
+#[derive(Debug, Default, Clone)]
+pub struct BgpSessions {
+    reassembler: TcpReassembler,
+    opens: HashMap<Flow, Open>,
+}
+
+impl BgpSessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the messages the packet completed, tcp segments of other ports are ignored. A malformed message is
+    // returned as its error, the ones after it still decode since the length framed them. A stream that
+    // fails to frame ends with the error after the messages framed before it.
+    pub fn observe(&mut self, packet: &Packet) -> Vec<(Flow, Result<BgpMessage>)> {
+        let Some((flow, _)) = Flow::of_segment(packet).filter(|(flow, _)| flow.port_src == PORT || flow.port_dst == PORT) else {
+            return Vec::new();
+        };
+        self.reassembler.observe(packet);
+
+        let mut messages = Vec::new();
+        let (framed, error) = self.reassembler.messages(&flow, message_length);
+        for message in framed {
+            let message = BgpMessage::from_message(&message, &self.options(&flow));
+            if let Ok(BgpMessage::Open(open)) = &message {
+                self.opens.insert(flow, open.clone());
+            }
+            messages.push((flow, message));
+        }
+        messages.extend(error.map(|error| (flow, Err(error))));
+
+        if self.reassembler.is_closed(&flow) && self.reassembler.is_closed(&flow.reversed()) {
+            for flow in [flow, flow.reversed()] {
+                self.reassembler.remove(&flow);
+                self.opens.remove(&flow);
+            }
+        }
+
+        messages
+    }
+
+    // what the updates of the flow are decoded with
+    pub fn options(&self, flow: &Flow) -> SessionOptions {
+        match (self.opens.get(flow), self.opens.get(&flow.reversed())) {
+            (Some(sender), Some(receiver)) => SessionOptions::negotiate(sender, receiver),
+            _ => SessionOptions::default(),
+        }
+    }
+}
+
+
+
+impl Reader<'_> {
+    fn address(&mut self, length: usize) -> Result<IpAddress> {
+        match length {
+            4 => Ok(IpAddress::V4(Ipv4addr(self.u32()?))),
+            16 => Ok(IpAddress::V6(Ipv6addr(self.take(16)?.to_u128()))),
+            length => Err(anyhow!("BGP address of {length} bytes")),
+        }
+    }
+
+
+    fn open(&mut self) -> Result<Open> {
+        let version = self.u8()?;
+        let my_as = self.u16()?;
+        let hold_time = self.u16()?;
+        let bgp_identifier = Ipv4addr(self.u32()?);
+        let parameters_length = self.u8()? as usize;
+        let mut parameters = self.sub_reader(parameters_length)?;
+
+        // RFC 5492, every capability parameter can hold several capabilities
+        let mut capabilities = Vec::new();
+        while !parameters.is_empty() {
+            let parameter_type = parameters.u8()?;
+            let length = parameters.u8()? as usize;
+            let mut parameter = parameters.sub_reader(length)?;
+            if parameter_type != 2 {
+                continue;
+            }
+
+            while !parameter.is_empty() {
+                let code = parameter.u8()?;
+                let length = parameter.u8()? as usize;
+                let mut value = parameter.sub_reader(length)?;
+                capabilities.push(value.capability(code)?);
+            }
+        }
+
+        Ok(Open { version, my_as, hold_time, bgp_identifier, capabilities })
+    }
+
+    fn capability(&mut self, code: u8) -> Result<Capability> {
+        Ok(
+            match code {
+                1 => {
+                    let afi = self.u16()?;
+                    self.u8()?;
+                    Capability::Multiprotocol { afi, safi: self.u8()? }
+                },
+                2 => Capability::RouteRefresh,
+                6 => Capability::ExtendedMessage,
+                64 => {
+                    let flags_and_time = self.u16()?;
+                    self.rest();
+                    Capability::GracefulRestart { flags: (flags_and_time >> 12) as u8, restart_time: flags_and_time & 0x0fff }
+                },
+                65 => Capability::FourOctetAs(self.u32()?),
+                69 => Capability::AddPath(self.until_empty(|reader| Ok((reader.u16()?, reader.u8()?, reader.u8()?)))?),
+                70 => Capability::EnhancedRouteRefresh,
+                code => Capability::Unknown { code, value: self.rest().to_vec() },
+            }
+        )
+    }
+
+    fn update(&mut self, options: &SessionOptions) -> Result<Update> {
+        let path_id = options.has_path_id(afi::IPV4, safi::UNICAST);
+
+        let withdrawn_length = self.u16()? as usize;
+        let withdrawn_routes = self.sub_reader(withdrawn_length)?.until_empty(|reader| reader.prefix(afi::IPV4, path_id))?;
+        let attributes_length = self.u16()? as usize;
+        let attributes = self.sub_reader(attributes_length)?.until_empty(|reader| reader.attribute(options))?;
+        let nlri = self.until_empty(|reader| reader.prefix(afi::IPV4, path_id))?;
+
+        Ok(Update { withdrawn_routes, attributes, nlri })
+    }
+
+    // only as many bytes of the address as its length needs
+    fn prefix(&mut self, afi: u16, path_id: bool) -> Result<Prefix> {
+        let path_id = match path_id {
+            true => Some(self.u32()?),
+            false => None,
+        };
+        let length = self.u8()?;
+        let size = match afi {
+            afi::IPV6 => 16,
+            _ => 4,
+        };
+        if length as usize > size * 8 {
+            return Err(anyhow!("BGP prefix length {length} is too long for its address family"));
+        }
+
+        let bytes = self.take((length as usize).div_ceil(8))?;
+        let mut address = [0; 16];
+        address[..bytes.len()].copy_from_slice(bytes);
+        let address = match size {
+            16 => IpAddress::V6(Ipv6addr(u128::from_be_bytes(address))),
+            _ => IpAddress::V4(Ipv4addr(address[0..4].to_u32())),
+        };
+
+        Ok(Prefix { path_id, address, length })
+    }
+
+    fn attribute(&mut self, options: &SessionOptions) -> Result<Attribute> {
+        const EXTENDED_LENGTH: u8 = 0x10;
+
+        let flags = self.u8()?;
+        let type_code = self.u8()?;
+        let length = match flags & EXTENDED_LENGTH {
+            0 => self.u8()? as usize,
+            _ => self.u16()? as usize,
+        };
+        let mut value = self.sub_reader(length)?;
+
+        let attribute = match type_code {
+            1 => PathAttribute::Origin(value.u8()?),
+            2 => PathAttribute::AsPath(value.as_path(options.four_octet_as)?),
+            3 => PathAttribute::NextHop(Ipv4addr(value.u32()?)),
+            4 => PathAttribute::MultiExitDisc(value.u32()?),
+            5 => PathAttribute::LocalPref(value.u32()?),
+            6 => PathAttribute::AtomicAggregate,
+            7 => {
+                let asn = match length {
+                    8 => value.u32()?,
+                    _ => value.u16()? as u32,
+                };
+                PathAttribute::Aggregator { asn, address: Ipv4addr(value.u32()?) }
+            },
+            8 => PathAttribute::Communities(value.until_empty(Self::u32)?),
+            9 => PathAttribute::OriginatorId(Ipv4addr(value.u32()?)),
+            10 => PathAttribute::ClusterList(value.until_empty(|reader| Ok(Ipv4addr(reader.u32()?)))?),
+            14 => {
+                let afi = value.u16()?;
+                let safi = value.u8()?;
+                let next_hop_length = value.u8()? as usize;
+                let mut next_hop = value.sub_reader(next_hop_length)?;
+                // ipv6 next hops can be a global and a link local address
+                let next_hops = match next_hop_length {
+                    32 => vec![next_hop.address(16)?, next_hop.address(16)?],
+                    length => vec![next_hop.address(length)?],
+                };
+                value.u8()?;
+                let nlri = value.nlri(afi, safi, options)?;
+                PathAttribute::MpReachNlri { afi, safi, next_hops, nlri }
+            },
+            15 => {
+                let afi = value.u16()?;
+                let safi = value.u8()?;
+                PathAttribute::MpUnreachNlri { afi, safi, withdrawn: value.nlri(afi, safi, options)? }
+            },
+            16 => PathAttribute::ExtendedCommunities(value.until_empty(|reader| Ok(reader.take(8)?.to_u64()))?),
+            17 => PathAttribute::As4Path(value.as_path(Some(true))?),
+            32 => PathAttribute::LargeCommunities(value.until_empty(|reader| {
+                Ok(LargeCommunity { global_administrator: reader.u32()?, local_data_1: reader.u32()?, local_data_2: reader.u32()? })
+            })?),
+            type_code => PathAttribute::Unknown { type_code, value: value.rest().to_vec() },
+        };
+
+        Ok(Attribute { flags, attribute })
+    }
+
+    // without a negotiated size the as numbers are 4 bytes when the segments add up with them
+    fn as_path(&mut self, four_octet_as: Option<bool>) -> Result<Vec<AsPathSegment>> {
+        let four_octet_as = four_octet_as.unwrap_or_else(|| {
+            let mut offset = 0;
+            while let Some([_, count, ..]) = self.message().get(self.offset() + offset..) {
+                offset += 2 + *count as usize * 4;
+            }
+            self.offset() + offset == self.message().len()
+        });
+        let asn_length = match four_octet_as {
+            true => 4,
+            false => 2,
+        };
+
+        self.until_empty(|reader| {
+            let segment_type = reader.u8()?;
+            let count = reader.u8()?;
+            let asns = (0..count).map(|_| Ok(reader.take(asn_length)?.to_u32())).collect::<Result<_>>()?;
+            Ok(AsPathSegment { segment_type, asns })
+        })
+    }
+
+    fn nlri(&mut self, afi: u16, safi: u8, options: &SessionOptions) -> Result<Nlri> {
+        let path_id = options.has_path_id(afi, safi);
+
+        Ok(
+            match (afi, safi) {
+                (afi::IPV4 | afi::IPV6, safi::UNICAST | safi::MULTICAST) => Nlri::Prefixes(self.until_empty(|reader| reader.prefix(afi, path_id))?),
+                (afi::L2VPN, safi::EVPN) => Nlri::Evpn(self.until_empty(|reader| {
+                    if path_id {
+                        reader.u32()?;
+                    }
+                    reader.evpn_route()
+                })?),
+                _ => Nlri::Unknown(self.rest().to_vec()),
+            }
+        )
+    }
+
+    fn evpn_route(&mut self) -> Result<EvpnRoute> {
+        let route_type = self.u8()?;
+        let length = self.u8()? as usize;
+        let mut value = self.sub_reader(length)?;
+
+        let rd = |reader: &mut Self| Ok::<_, anyhow::Error>(RouteDistinguisher(reader.take(8)?.try_into()?));
+        let esi = |reader: &mut Self| Ok::<_, anyhow::Error>(<[u8; 10]>::try_from(reader.take(10)?)?);
+        // the length of an address is given in bits
+        let address = |reader: &mut Self| {
+            let bits = reader.u8()? as usize;
+            reader.address(bits / 8)
+        };
+        let label = |reader: &mut Self| Ok::<_, anyhow::Error>(reader.u24()? >> 4);
+
+        Ok(
+            match route_type {
+                1 => EvpnRoute::EthernetAutoDiscovery { rd: rd(&mut value)?, esi: esi(&mut value)?, ethernet_tag: value.u32()?, label: label(&mut value)? },
+                2 => {
+                    let rd = rd(&mut value)?;
+                    let esi = esi(&mut value)?;
+                    let ethernet_tag = value.u32()?;
+                    value.u8()?;
+                    let mac = MacAddress::from(value.take(6)?.to_u64());
+                    let ip = match value.u8()? {
+                        0 => None,
+                        bits => Some(value.address(bits as usize / 8)?),
+                    };
+                    let labels = value.until_empty(label)?;
+                    EvpnRoute::MacIpAdvertisement { rd, esi, ethernet_tag, mac, ip, labels }
+                },
+                3 => EvpnRoute::InclusiveMulticast { rd: rd(&mut value)?, ethernet_tag: value.u32()?, originating_router: address(&mut value)? },
+                4 => EvpnRoute::EthernetSegment { rd: rd(&mut value)?, esi: esi(&mut value)?, originating_router: address(&mut value)? },
+                // the address family follows from the length of the route
+                5 => {
+                    let size = match length {
+                        58 => 16,
+                        _ => 4,
+                    };
+                    let rd = rd(&mut value)?;
+                    let esi = esi(&mut value)?;
+                    let ethernet_tag = value.u32()?;
+                    let prefix_length = value.u8()?;
+                    let prefix = Prefix { path_id: None, address: value.address(size)?, length: prefix_length };
+                    let gateway = value.address(size)?;
+                    EvpnRoute::IpPrefix { rd, esi, ethernet_tag, prefix, gateway, label: label(&mut value)? }
+                },
+                route_type => EvpnRoute::Unknown { route_type, value: value.rest().to_vec() },
+            }
+        )
+    }
+}
+
+
+
+pub struct BgpDissector;
+
+impl Dissector for BgpDissector {
+    fn name(&self) -> &str {
+        "bgp"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::ApplicationLayer(ApplicationLayer::BGP(BgpMessage::from_tcp_bytes(bytes)?)))
+    }
+}
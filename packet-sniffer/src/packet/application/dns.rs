@@ -0,0 +1,462 @@
+use byte_slice::{Bytes, Ipv4addr, Ipv6addr, SliceToUnsigned};
+use crate::packet::{application::ApplicationLayer, dissector::{Context, Dissector, Key}, reader::Reader, Layer};
+use anyhow::{anyhow, Result};
+
+
+// https://www.rfc-editor.org/rfc/rfc1035
+// https://www.rfc-editor.org/rfc/rfc6762 mDNS
+// https://www.rfc-editor.org/rfc/rfc9460 SVCB and HTTPS
+
+
+const MAX_NAME_LENGTH: usize = 255;
+// compression pointers that loop are cut off after this many jumps
+const MAX_POINTER_JUMPS: usize = 128;
+
+
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub struct DnsFlags(pub u16);
+
+impl DnsFlags {
+    // false for queries, true for responses
+    pub fn qr(&self) -> bool {
+        self.0 & 0x8000 != 0
+    }
+
+    pub fn opcode(&self) -> u8 {
+        ((self.0 >> 11) & 0x0f) as u8
+    }
+
+    pub fn authoritative(&self) -> bool {
+        self.0 & 0x0400 != 0
+    }
+
+    pub fn truncated(&self) -> bool {
+        self.0 & 0x0200 != 0
+    }
+
+    pub fn recursion_desired(&self) -> bool {
+        self.0 & 0x0100 != 0
+    }
+
+    pub fn recursion_available(&self) -> bool {
+        self.0 & 0x0080 != 0
+    }
+
+    pub fn authentic_data(&self) -> bool {
+        self.0 & 0x0020 != 0
+    }
+
+    pub fn checking_disabled(&self) -> bool {
+        self.0 & 0x0010 != 0
+    }
+
+    // the low 4 bits, an OPT record extends it with 8 more
+    pub fn rcode(&self) -> u8 {
+        (self.0 & 0x000f) as u8
+    }
+}
+
+impl std::fmt::Debug for DnsFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DnsFlags")
+            .field("QR", &self.qr())
+            .field("opcode", &self.opcode())
+            .field("AA", &self.authoritative())
+            .field("TC", &self.truncated())
+            .field("RD", &self.recursion_desired())
+            .field("RA", &self.recursion_available())
+            .field("AD", &self.authentic_data())
+            .field("CD", &self.checking_disabled())
+            .field("rcode", &self.rcode())
+            .finish()
+    }
+}
+
+
+
+// https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-4
+pub mod record_type {
+    pub const A: u16 = 1;
+    pub const NS: u16 = 2;
+    pub const CNAME: u16 = 5;
+    pub const SOA: u16 = 6;
+    pub const PTR: u16 = 12;
+    pub const MX: u16 = 15;
+    pub const TXT: u16 = 16;
+    pub const AAAA: u16 = 28;
+    pub const SRV: u16 = 33;
+    pub const OPT: u16 = 41;
+    pub const SVCB: u16 = 64;
+    pub const HTTPS: u16 = 65;
+    pub const ANY: u16 = 255;
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Question {
+    pub name: String,
+    pub qtype: u16,
+    pub class: u16,
+    // mDNS uses the top bit of the class to ask for a unicast response
+    pub unicast_response: bool,
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SvcParam {
+    Mandatory(Vec<u16>),
+    Alpn(Vec<String>),
+    NoDefaultAlpn,
+    Port(u16),
+    Ipv4Hint(Vec<Ipv4addr>),
+    Ech(Vec<u8>),
+    Ipv6Hint(Vec<Ipv6addr>),
+    Unknown(u16, Vec<u8>),
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordData {
+    A(Ipv4addr),
+    AAAA(Ipv6addr),
+    NS(String),
+    CNAME(String),
+    PTR(String),
+    MX {
+        preference: u16,
+        exchange: String,
+    },
+    SRV {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    TXT(Vec<String>),
+    SOA {
+        mname: String,
+        rname: String,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+    },
+    // EDNS0 reuses the class and ttl of the record, they are split out here
+    OPT {
+        udp_payload_size: u16,
+        extended_rcode: u8,
+        version: u8,
+        dnssec_ok: bool,
+        options: Vec<(u16, Vec<u8>)>,
+    },
+    // SVCB and HTTPS share one format
+    SVCB {
+        priority: u16,
+        target: String,
+        params: Vec<SvcParam>,
+    },
+    Unknown(Vec<u8>),
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceRecord {
+    pub name: String,
+    pub rtype: u16,
+    pub class: u16,
+    // mDNS uses the top bit of the class to mark records that replace cached ones
+    pub cache_flush: bool,
+    pub ttl: u32,
+    pub data: RecordData,
+}
+
+
+
+#[derive(Debug, Default, Clone)]
+pub struct DNS {
+    pub transaction_id: u16,
+    pub flags: DnsFlags,
+    pub questions: Vec<Question>,
+    pub answers: Vec<ResourceRecord>,
+    pub authorities: Vec<ResourceRecord>,
+    pub additionals: Vec<ResourceRecord>,
+}
+
+impl DNS {
+    // `multicast` reads the top bit of the class as the mDNS unicast response or cache flush bit
+    pub fn from_bytes(bytes: &mut Bytes, multicast: bool) -> Result<Self> {
+        let message = &bytes[..];
+        let dns = Self::from_message(message, multicast)?;
+        bytes.shift_first(message.len())?;
+
+        Ok(dns)
+    }
+
+    // over tcp every message is preceded by its length, a segment can carry several of them
+    // a message that continues in the next segment is left in `bytes`
+    pub fn from_tcp_bytes(bytes: &mut Bytes) -> Result<Vec<Self>> {
+        let mut messages = Vec::new();
+
+        while bytes.len() >= 2 {
+            let length = bytes[0..2].to_u16() as usize;
+            if bytes.len() < 2 + length {
+                break;
+            }
+            messages.push(Self::from_message(&bytes[2..2+length], false)?);
+            bytes.shift_first(2 + length)?;
+        }
+
+        match messages.is_empty() {
+            true => Err(anyhow!("No complete DNS message in the segment")),
+            false => Ok(messages),
+        }
+    }
+
+    fn from_message(message: &[u8], multicast: bool) -> Result<Self> {
+        let mut reader = Reader::new("DNS", message);
+
+        let transaction_id = reader.u16()?;
+        let flags = DnsFlags(reader.u16()?);
+        let question_count = reader.u16()?;
+        let answer_count = reader.u16()?;
+        let authority_count = reader.u16()?;
+        let additional_count = reader.u16()?;
+
+        let questions = (0..question_count)
+            .map(|_| {
+                let name = reader.name()?;
+                let qtype = reader.u16()?;
+                let class = reader.u16()?;
+
+                Ok(
+                    Question {
+                        name,
+                        qtype,
+                        class: if multicast { class & 0x7fff } else { class },
+                        unicast_response: multicast && class & 0x8000 != 0,
+                    }
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut records = |count: u16| (0..count).map(|_| reader.record(multicast)).collect::<Result<Vec<_>>>();
+        let answers = records(answer_count)?;
+        let authorities = records(authority_count)?;
+        let additionals = records(additional_count)?;
+
+        Ok(
+            Self {
+                transaction_id,
+                flags,
+                questions,
+                answers,
+                authorities,
+                additionals,
+            }
+        )
+    }
+
+    // every record of the answer, authority and additional sections
+    pub fn records(&self) -> impl Iterator<Item = &ResourceRecord> {
+        self.answers.iter().chain(&self.authorities).chain(&self.additionals)
+    }
+}
+
+
+
+impl Reader<'_> {
+    // reads a possibly compressed name, the offset ends up after the first pointer or the root label
+    fn name(&mut self) -> Result<String> {
+        let mut labels: Vec<String> = Vec::new();
+        let mut length = 0;
+        let mut jumps = 0;
+        let mut position = self.offset();
+        let mut end = None;
+
+        loop {
+            let label_length = *self.message().get(position).ok_or(anyhow!("DNS name runs past the message"))? as usize;
+
+            match label_length & 0xc0 {
+                0x00 if label_length == 0 => {
+                    end.get_or_insert(position + 1);
+                    break;
+                },
+                0x00 => {
+                    let label = self.message()
+                        .get(position+1..position+1+label_length)
+                        .ok_or(anyhow!("DNS label runs past the message"))?;
+                    length += label_length + 1;
+                    if length > MAX_NAME_LENGTH {
+                        return Err(anyhow!("DNS name is longer than {MAX_NAME_LENGTH} bytes"));
+                    }
+                    labels.push(String::from_utf8_lossy(label).into_owned());
+                    position += 1 + label_length;
+                },
+                0xc0 => {
+                    let pointer = self.message()
+                        .get(position..position+2)
+                        .ok_or(anyhow!("DNS compression pointer runs past the message"))?
+                        .to_u16() as usize & 0x3fff;
+                    jumps += 1;
+                    if jumps > MAX_POINTER_JUMPS {
+                        return Err(anyhow!("DNS compression pointers loop"));
+                    }
+                    end.get_or_insert(position + 2);
+                    position = pointer;
+                },
+                _ => return Err(anyhow!("Unsupported DNS label type {label_length:#x}")),
+            }
+        }
+
+        self.seek(end.unwrap_or(position + 1));
+
+        Ok(
+            match labels.is_empty() {
+                true => ".".to_owned(),
+                false => labels.join("."),
+            }
+        )
+    }
+
+    fn record(&mut self, multicast: bool) -> Result<ResourceRecord> {
+        let name = self.name()?;
+        let rtype = self.u16()?;
+        let class = self.u16()?;
+        let ttl = self.u32()?;
+        let data_length = self.u16()? as usize;
+
+        let data_start = self.offset();
+        self.take(data_length)?;
+        // names inside the data can point back into the rest of the message, so only its end is cut off
+        let mut data_reader = Reader::at("DNS", &self.message()[..data_start + data_length], data_start);
+        let data = data_reader.record_data(rtype, class, ttl)?;
+
+        let flush_bit = multicast && rtype != record_type::OPT;
+
+        Ok(
+            ResourceRecord {
+                name,
+                rtype,
+                class: if flush_bit { class & 0x7fff } else { class },
+                cache_flush: flush_bit && class & 0x8000 != 0,
+                ttl,
+                data,
+            }
+        )
+    }
+
+    fn record_data(&mut self, rtype: u16, class: u16, ttl: u32) -> Result<RecordData> {
+        Ok(
+            match rtype {
+                record_type::A => RecordData::A(Ipv4addr(self.u32()?)),
+                record_type::AAAA => RecordData::AAAA(Ipv6addr(self.take(16)?.to_u128())),
+                record_type::NS => RecordData::NS(self.name()?),
+                record_type::CNAME => RecordData::CNAME(self.name()?),
+                record_type::PTR => RecordData::PTR(self.name()?),
+                record_type::MX => RecordData::MX {
+                    preference: self.u16()?,
+                    exchange: self.name()?,
+                },
+                record_type::SRV => RecordData::SRV {
+                    priority: self.u16()?,
+                    weight: self.u16()?,
+                    port: self.u16()?,
+                    target: self.name()?,
+                },
+                record_type::TXT => {
+                    let mut strings = Vec::new();
+                    while self.remaining() > 0 {
+                        let length = self.u8()? as usize;
+                        strings.push(String::from_utf8_lossy(self.take(length)?).into_owned());
+                    }
+                    RecordData::TXT(strings)
+                },
+                record_type::SOA => RecordData::SOA {
+                    mname: self.name()?,
+                    rname: self.name()?,
+                    serial: self.u32()?,
+                    refresh: self.u32()?,
+                    retry: self.u32()?,
+                    expire: self.u32()?,
+                    minimum: self.u32()?,
+                },
+                record_type::OPT => {
+                    let mut options = Vec::new();
+                    while self.remaining() > 0 {
+                        let code = self.u16()?;
+                        let length = self.u16()? as usize;
+                        options.push((code, self.take(length)?.to_vec()));
+                    }
+                    RecordData::OPT {
+                        udp_payload_size: class,
+                        extended_rcode: (ttl >> 24) as u8,
+                        version: (ttl >> 16) as u8,
+                        dnssec_ok: ttl & 0x8000 != 0,
+                        options,
+                    }
+                },
+                record_type::SVCB | record_type::HTTPS => {
+                    let priority = self.u16()?;
+                    let target = self.name()?;
+                    let mut params = Vec::new();
+                    while self.remaining() > 0 {
+                        let key = self.u16()?;
+                        let length = self.u16()? as usize;
+                        params.push(svc_param(key, self.take(length)?)?);
+                    }
+                    RecordData::SVCB {
+                        priority,
+                        target,
+                        params,
+                    }
+                },
+                _ => RecordData::Unknown(self.take(self.remaining())?.to_vec()),
+            }
+        )
+    }
+}
+
+fn svc_param(key: u16, value: &[u8]) -> Result<SvcParam> {
+    let mut reader = Reader::new("DNS", value);
+
+    Ok(
+        match key {
+            0 => SvcParam::Mandatory(value.chunks_exact(2).map(|key| key.to_u16()).collect()),
+            1 => {
+                let mut protocols = Vec::new();
+                while reader.remaining() > 0 {
+                    let length = reader.u8()? as usize;
+                    protocols.push(String::from_utf8_lossy(reader.take(length)?).into_owned());
+                }
+                SvcParam::Alpn(protocols)
+            },
+            2 => SvcParam::NoDefaultAlpn,
+            3 => SvcParam::Port(reader.u16()?),
+            4 => SvcParam::Ipv4Hint(value.chunks_exact(4).map(|address| Ipv4addr(address.to_u32())).collect()),
+            5 => SvcParam::Ech(value.to_vec()),
+            6 => SvcParam::Ipv6Hint(value.chunks_exact(16).map(|address| Ipv6addr(address.to_u128())).collect()),
+            _ => SvcParam::Unknown(key, value.to_vec()),
+        }
+    )
+}
+
+
+
+pub struct DnsDissector;
+
+impl Dissector for DnsDissector {
+    fn name(&self) -> &str {
+        "dns"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, context: &Context) -> Result<Layer<'a>> {
+        let messages = match context.key {
+            Some(Key::TcpPort(_)) => DNS::from_tcp_bytes(bytes)?,
+            key => vec![DNS::from_bytes(bytes, key == Some(Key::UdpPort(5353)))?],
+        };
+
+        Ok(Layer::ApplicationLayer(ApplicationLayer::DNS(messages)))
+    }
+}
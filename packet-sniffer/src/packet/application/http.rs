@@ -0,0 +1,424 @@
+use std::{collections::{HashMap, VecDeque}, fmt::Display, path::Path, time::Duration};
+
+use anyhow::{anyhow, Result};
+use byte_slice::Bytes;
+
+use crate::{packet::{dissector::{Context, Dissector}, Layer, Packet}, pcap, reassembly::{Flow, TcpReassembler}};
+
+use super::ApplicationLayer;
+
+
+// HTTP/1.0 and HTTP/1.1 (RFC 9112). A message is a start line and headers up to an empty line, then a body
+// framed by Transfer-Encoding: chunked, by Content-Length or, for responses without either, by the server
+// closing the connection. A segment alone is decoded as far as it carries complete messages, HttpTransactions
+// follows whole connections and pairs the responses with the requests they answer, in order since clients
+// may pipeline requests without waiting.
+
+
+
+pub const PORTS: [u16; 3] = [80, 8000, 8080];
+const METHODS: [&str; 9] = ["GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH"];
+// a head that did not end within this many bytes is not http
+const MAX_HEAD_LENGTH: usize = 64 * 1024;
+
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub target: String,
+    pub version: String,
+    pub headers: Vec<(String, String)>,
+    // without the chunked transfer coding
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub version: String,
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HttpMessage {
+    Request(Request),
+    Response(Response),
+}
+
+impl Request {
+    // the first value of the header, names are case insensitive
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+
+    // informational, no content and not modified responses never have a body
+    fn has_body(&self, method: Option<&str>) -> bool {
+        method != Some("HEAD") && !matches!(self.status, 100..=199 | 204 | 304)
+    }
+}
+
+fn header<'h>(headers: &'h [(String, String)], name: &str) -> Option<&'h str> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+// whether the bytes start like a request or a response
+pub fn is_http(data: &[u8]) -> bool {
+    data.starts_with(b"HTTP/1.") || METHODS.iter().any(|method| data.starts_with(method.as_bytes()) && data.get(method.len()) == Some(&b' '))
+}
+
+
+
+impl HttpMessage {
+    // the first message of `data` and its length, None while it is incomplete. The body of a response depends
+    // on the method it answers and may last until the connection is `closed`.
+    pub fn parse(data: &[u8], method: Option<&str>, closed: bool) -> Result<Option<(Self, usize)>> {
+        let Some(head_length) = head_length(data)? else {
+            return Ok(None);
+        };
+
+        let head = String::from_utf8_lossy(&data[..head_length]);
+        let mut lines = head.lines();
+        let start_line = lines.next().unwrap_or_default();
+        let headers = lines
+            .take_while(|line| !line.is_empty())
+            .map(|line| {
+                let (name, value) = line.split_once(':').ok_or(anyhow!("HTTP header line {line:?} has no colon"))?;
+                Ok((name.trim().to_owned(), value.trim().to_owned()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut message = match start_line.starts_with("HTTP/") {
+            true => {
+                let mut parts = start_line.splitn(3, ' ');
+                let version = parts.next().unwrap_or_default().to_owned();
+                let status = parts.next().and_then(|status| status.parse().ok()).ok_or(anyhow!("HTTP status line {start_line:?} is invalid"))?;
+                HttpMessage::Response(Response { version, status, reason: parts.next().unwrap_or_default().to_owned(), headers, body: Vec::new() })
+            },
+            false => {
+                let [method, target, version] = start_line.split(' ').collect::<Vec<_>>()[..] else {
+                    return Err(anyhow!("HTTP request line {start_line:?} is invalid"));
+                };
+                if !version.starts_with("HTTP/") {
+                    return Err(anyhow!("HTTP request line {start_line:?} has no version"));
+                }
+                HttpMessage::Request(Request { method: method.to_owned(), target: target.to_owned(), version: version.to_owned(), headers, body: Vec::new() })
+            },
+        };
+
+        let (headers, has_body, until_close) = match &message {
+            HttpMessage::Request(request) => (&request.headers, true, false),
+            HttpMessage::Response(response) => (&response.headers, response.has_body(method), true),
+        };
+        let chunked = header(headers, "Transfer-Encoding").is_some_and(|coding| coding.to_ascii_lowercase().trim_end().ends_with("chunked"));
+        let content_length = header(headers, "Content-Length")
+            .map(|length| length.parse::<usize>().map_err(|_| anyhow!("HTTP Content-Length {length:?} is invalid")))
+            .transpose()?;
+
+        let rest = &data[head_length..];
+        let framed = match (has_body, chunked, content_length) {
+            (false, _, _) => Some((Vec::new(), 0)),
+            (true, true, _) => dechunk(rest)?,
+            (true, false, Some(length)) => rest.get(..length).map(|body| (body.to_vec(), length)),
+            (true, false, None) if until_close => closed.then(|| (rest.to_vec(), rest.len())),
+            (true, false, None) => Some((Vec::new(), 0)),
+        };
+        let Some((body, body_length)) = framed else {
+            return Ok(None);
+        };
+
+        match &mut message {
+            HttpMessage::Request(request) => request.body = body,
+            HttpMessage::Response(response) => response.body = body,
+        }
+
+        Ok(Some((message, head_length + body_length)))
+    }
+
+    // the complete messages of a segment, a message that continues in the next segment is left in `bytes`
+    pub fn from_tcp_bytes(bytes: &mut Bytes) -> Result<Vec<Self>> {
+        let mut messages = Vec::new();
+
+        // without the request a response body can only be taken to last as long as the segment
+        while let Some((message, length)) = Self::parse(&bytes[..], None, true)? {
+            messages.push(message);
+            bytes.shift_first(length)?;
+            if bytes.is_empty() {
+                break;
+            }
+        }
+
+        match messages.is_empty() {
+            true => Err(anyhow!("No complete HTTP message in the segment")),
+            false => Ok(messages),
+        }
+    }
+}
+
+// the length of the start line and headers including the empty line after them
+fn head_length(data: &[u8]) -> Result<Option<usize>> {
+    let end = data
+        .windows(2)
+        .position(|window| window == b"\n\n")
+        .map(|position| position + 2)
+        .into_iter()
+        .chain(data.windows(4).position(|window| window == b"\r\n\r\n").map(|position| position + 4))
+        .min();
+
+    match end {
+        Some(end) => Ok(Some(end)),
+        None if data.len() > MAX_HEAD_LENGTH => Err(anyhow!("HTTP head is longer than {MAX_HEAD_LENGTH} bytes")),
+        None => Ok(None),
+    }
+}
+
+// the body of a chunked message and how many bytes it took, None while chunks are missing
+fn dechunk(data: &[u8]) -> Result<Option<(Vec<u8>, usize)>> {
+    let mut body = Vec::new();
+    let mut offset = 0;
+
+    loop {
+        let Some(line_length) = data[offset..].iter().position(|byte| *byte == b'\n') else {
+            return Ok(None);
+        };
+        let line = String::from_utf8_lossy(&data[offset..offset + line_length]);
+        // chunk extensions follow a semicolon
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| anyhow!("HTTP chunk size {size:?} is invalid"))?;
+        offset += line_length + 1;
+
+        if size == 0 {
+            break;
+        }
+        let end = offset.checked_add(size).ok_or(anyhow!("HTTP chunk size {size:x} is invalid"))?;
+        let Some(chunk) = data.get(offset..end) else {
+            return Ok(None);
+        };
+        body.extend_from_slice(chunk);
+        offset += size;
+
+        // the line break after the chunk
+        match data.get(offset..offset + 2) {
+            Some(b"\r\n") => offset += 2,
+            Some([b'\n', _]) => offset += 1,
+            Some(_) => return Err(anyhow!("HTTP chunk of {size} bytes is not followed by a line break")),
+            None => return Ok(None),
+        }
+    }
+
+    // trailer fields up to an empty line
+    loop {
+        let Some(line_length) = data[offset..].iter().position(|byte| *byte == b'\n') else {
+            return Ok(None);
+        };
+        let empty = data[offset..offset + line_length].iter().all(|byte| *byte == b'\r');
+        offset += line_length + 1;
+        if empty {
+            return Ok(Some((body, offset)));
+        }
+    }
+}
+
+
+
+// a request and the response that answered it, either may be missing when the capture missed it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    // from the client to the server
+    pub flow: Flow,
+    pub request: Option<Request>,
+    pub response: Option<Response>,
+    // when the last segment of the request and of the response was captured
+    pub request_time: Option<Duration>,
+    pub response_time: Option<Duration>,
+}
+
+impl Transaction {
+    // how long the server took to answer
+    pub fn elapsed(&self) -> Option<Duration> {
+        Some(self.response_time?.saturating_sub(self.request_time?))
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct Connection {
+    // requests waiting for their responses, oldest first
+    pending: VecDeque<(Request, Duration)>,
+}
+
+// follows the http connections of a capture, keyed by the flow from the client to the server
+#[derive(Debug, Default, Clone)]
+pub struct HttpTransactions {
+    reassembler: TcpReassembler,
+    connections: HashMap<Flow, Connection>,
+    transactions: Vec<Transaction>,
+}
+
+impl HttpTransactions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the transactions the packet completed, connections on other ports are recognized by their first request or response
+    pub fn observe(&mut self, packet: &Packet, timestamp: Duration) -> Result<Vec<Transaction>> {
+        let Some((flow, tcp)) = Flow::of_segment(packet) else {
+            return Ok(Vec::new());
+        };
+
+        let client = match (self.connections.contains_key(&flow), self.connections.contains_key(&flow.reversed())) {
+            (true, _) => flow,
+            (_, true) => flow.reversed(),
+            // the handshake of the known ports is followed too, it tells where the streams start
+            _ if PORTS.contains(&flow.port_dst) => flow,
+            _ if PORTS.contains(&flow.port_src) => flow.reversed(),
+            _ if tcp.payload.is_empty() || !is_http(&tcp.payload[..]) => return Ok(Vec::new()),
+            _ if tcp.payload[..].starts_with(b"HTTP/") => flow.reversed(),
+            _ => flow,
+        };
+        self.connections.entry(client).or_default();
+        self.reassembler.observe(packet);
+
+        let result = match flow == client {
+            true => self.requests(client, timestamp).map(|_| Vec::new()),
+            false => self.responses(client, timestamp),
+        };
+        if result.is_err() {
+            self.reassembler.consume(&flow, usize::MAX);
+        }
+
+        if self.reassembler.is_closed(&flow) && self.reassembler.is_closed(&flow.reversed()) {
+            self.reassembler.remove(&flow);
+            self.reassembler.remove(&flow.reversed());
+            // requests that were never answered
+            if let Some(connection) = self.connections.remove(&client) {
+                for (request, request_time) in connection.pending {
+                    self.transactions.push(Transaction { flow: client, request: Some(request), response: None, request_time: Some(request_time), response_time: None });
+                }
+            }
+        }
+
+        result
+    }
+
+    fn requests(&mut self, client: Flow, timestamp: Duration) -> Result<()> {
+        while let Some((message, length)) = HttpMessage::parse(self.reassembler.data(&client), None, false)? {
+            self.reassembler.consume(&client, length);
+            match message {
+                HttpMessage::Request(request) => self.connections.entry(client).or_default().pending.push_back((request, timestamp)),
+                HttpMessage::Response(_) => return Err(anyhow!("HTTP response sent by the client")),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn responses(&mut self, client: Flow, timestamp: Duration) -> Result<Vec<Transaction>> {
+        let server = client.reversed();
+        let mut completed = Vec::new();
+
+        loop {
+            let connection = self.connections.entry(client).or_default();
+            let method = connection.pending.front().map(|(request, _)| request.method.clone());
+            let Some((message, length)) = HttpMessage::parse(self.reassembler.data(&server), method.as_deref(), self.reassembler.is_closed(&server))? else {
+                break;
+            };
+            self.reassembler.consume(&server, length);
+
+            let HttpMessage::Response(response) = message else {
+                return Err(anyhow!("HTTP request sent by the server"));
+            };
+            // interim responses like 100 Continue come before the final one
+            if (100..200).contains(&response.status) && response.status != 101 {
+                continue;
+            }
+
+            let (request, request_time) = connection.pending.pop_front().unzip();
+            completed.push(Transaction { flow: client, request, response: Some(response), request_time, response_time: Some(timestamp) });
+        }
+
+        self.transactions.extend(completed.iter().cloned());
+        Ok(completed)
+    }
+
+    // every completed transaction and the requests still waiting for an answer, in the order of their requests
+    pub fn transactions(&self) -> Vec<Transaction> {
+        let mut transactions = self.transactions.clone();
+        for (client, connection) in &self.connections {
+            for (request, request_time) in &connection.pending {
+                transactions.push(Transaction { flow: *client, request: Some(request.clone()), response: None, request_time: Some(*request_time), response_time: None });
+            }
+        }
+        transactions.sort_by_key(|transaction| transaction.request_time.or(transaction.response_time));
+
+        transactions
+    }
+}
+
+impl Display for HttpTransactions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:<22}  {:<22}  {:<7}  {:<40}  {:<6}  {:>10}  {:>9}", "Client", "Server", "Method", "Target", "Status", "Body", "Time (ms)")?;
+
+        for transaction in self.transactions() {
+            let request = transaction.request.as_ref();
+            let response = transaction.response.as_ref();
+            let endpoint = |address, port| format!("{address}:{port}");
+            writeln!(
+                f,
+                "{:<22}  {:<22}  {:<7}  {:<40}  {:<6}  {:>10}  {:>9}",
+                endpoint(transaction.flow.address_src, transaction.flow.port_src),
+                endpoint(transaction.flow.address_dst, transaction.flow.port_dst),
+                request.map_or("-", |request| &request.method),
+                request.map_or("-", |request| &request.target),
+                response.map_or("-".to_owned(), |response| response.status.to_string()),
+                response.map_or("-".to_owned(), |response| response.body.len().to_string()),
+                transaction.elapsed().map_or("-".to_owned(), |elapsed| format!("{:.3}", elapsed.as_secs_f64() * 1000.0)),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+
+// the transactions of a capture
+pub fn from_file(path: impl AsRef<Path>) -> Result<HttpTransactions> {
+    let mut transactions = HttpTransactions::new();
+
+    for record in pcap::read_file(path)? {
+        if let Ok(packet) = Packet::from_link_type(record.link_type, &mut Bytes::from_slice(&record.data)) {
+            // a connection that fails to parse only loses what was buffered for it
+            let _ = transactions.observe(&packet, record.timestamp);
+        }
+    }
+
+    Ok(transactions)
+}
+
+
+
+pub struct HttpDissector;
+
+impl Dissector for HttpDissector {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    fn probe(&self, bytes: &Bytes, _: &Context) -> bool {
+        is_http(&bytes[..])
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::ApplicationLayer(ApplicationLayer::HTTP(HttpMessage::from_tcp_bytes(bytes)?)))
+    }
+}
@@ -0,0 +1,253 @@
+use anyhow::{anyhow, Result};
+use byte_slice::{Bytes, Ipv4addr, Ipv6addr, SliceToUnsigned};
+
+use crate::packet::{dissector::{Context, Dissector}, Layer};
+
+use super::ApplicationLayer;
+
+
+// RIPv1 (RFC 1058) and RIPv2 (RFC 2453) on udp port 520, RIPng (RFC 2080) on 521. A 4 byte header is
+// followed by 20 byte entries. RIPv2 uses an entry with the address family 0xffff for authentication
+// (RFC 2082, RFC 4822), keyed digests put their digest in a trailer after the last route.
+
+
+
+pub const PORT: u16 = 520;
+pub const PORT_RIPNG: u16 = 521;
+// a metric of 16 is unreachable
+pub const INFINITY: u32 = 16;
+const ENTRY_LENGTH: usize = 20;
+const AUTHENTICATION_FAMILY: u16 = 0xffff;
+
+pub mod command {
+    pub const REQUEST: u8 = 1;
+    pub const RESPONSE: u8 = 2;
+}
+
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Authentication {
+    // padded with zeros
+    Password([u8; 16]),
+    // keyed md5 and hmac, the digest is in the trailer
+    Keyed { packet_length: u16, key_id: u8, data_length: u8, sequence: u32 },
+    Trailer(Vec<u8>),
+    Unknown { auth_type: u16, data: Vec<u8> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RouteEntry {
+    // 2 for ip, 0 in requests for the whole table
+    pub address_family: u16,
+    // v2 only
+    pub route_tag: u16,
+    pub address: Ipv4addr,
+    // v2 only
+    pub mask: Option<Ipv4addr>,
+    // v2 only, 0.0.0.0 is the sender of the response
+    pub next_hop: Option<Ipv4addr>,
+    pub metric: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RipEntry {
+    Route(RouteEntry),
+    Authentication(Authentication),
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RIP {
+    pub command: u8,
+    pub version: u8,
+    pub entries: Vec<RipEntry>,
+}
+
+impl RIP {
+    pub fn from_bytes(bytes: &mut Bytes) -> Result<Self> {
+        bytes.require(4)?;
+
+        let command = bytes[0];
+        let version = bytes[1];
+        if !matches!(command, command::REQUEST | command::RESPONSE) || version == 0 {
+            return Err(anyhow!("RIP command {command} version {version} is invalid"));
+        }
+        bytes.shift_first(4)?;
+
+        let mut entries = Vec::new();
+        while !bytes.is_empty() {
+            let family = bytes[..].get(0..2).map(|family| family.to_u16());
+            entries.push(
+                match (family, version) {
+                    (Some(AUTHENTICATION_FAMILY), 2..) => RipEntry::Authentication(Self::authentication(bytes)?),
+                    _ => RipEntry::Route(Self::route(bytes, version)?),
+                }
+            );
+        }
+
+        Ok(
+            Self {
+                command,
+                version,
+                entries,
+            }
+        )
+    }
+
+    fn route(bytes: &mut Bytes, version: u8) -> Result<RouteEntry> {
+        bytes.require(ENTRY_LENGTH)?;
+
+        let (route_tag, mask, next_hop) = match version {
+            1 => (0, None, None),
+            _ => (bytes[2..4].to_u16(), Some(Ipv4addr(bytes[8..12].to_u32())), Some(Ipv4addr(bytes[12..16].to_u32()))),
+        };
+        let route = RouteEntry {
+            address_family: bytes[0..2].to_u16(),
+            route_tag,
+            address: Ipv4addr(bytes[4..8].to_u32()),
+            mask,
+            next_hop,
+            metric: bytes[16..20].to_u32(),
+        };
+        bytes.shift_first(ENTRY_LENGTH)?;
+
+        Ok(route)
+    }
+
+    fn authentication(bytes: &mut Bytes) -> Result<Authentication> {
+        bytes.require(4)?;
+
+        // the trailer takes the rest of the packet
+        let auth_type = bytes[2..4].to_u16();
+        let length = match auth_type {
+            1 => bytes.len(),
+            _ => ENTRY_LENGTH,
+        };
+        bytes.require(length)?;
+
+        let data = &bytes[4..length];
+        let authentication = match auth_type {
+            1 => Authentication::Trailer(data.to_vec()),
+            2 => Authentication::Password(data.try_into()?),
+            3 => Authentication::Keyed { packet_length: data[0..2].to_u16(), key_id: data[2], data_length: data[3], sequence: data[4..8].to_u32() },
+            auth_type => Authentication::Unknown { auth_type, data: data.to_vec() },
+        };
+        bytes.shift_first(length)?;
+
+        Ok(authentication)
+    }
+
+    pub fn routes(&self) -> impl Iterator<Item = &RouteEntry> {
+        self.entries.iter().filter_map(|entry| match entry {
+            RipEntry::Route(route) => Some(route),
+            RipEntry::Authentication(_) => None,
+        })
+    }
+
+    pub fn authentication_entries(&self) -> impl Iterator<Item = &Authentication> {
+        self.entries.iter().filter_map(|entry| match entry {
+            RipEntry::Authentication(authentication) => Some(authentication),
+            RipEntry::Route(_) => None,
+        })
+    }
+
+    // a request with a single entry of family 0 and an infinite metric asks for every route
+    pub fn is_whole_table_request(&self) -> bool {
+        let routes: Vec<_> = self.routes().collect();
+        self.command == command::REQUEST && matches!(routes[..], [route] if route.address_family == 0 && route.metric == INFINITY)
+    }
+}
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RipngEntry {
+    Route { prefix: Ipv6addr, route_tag: u16, prefix_length: u8, metric: u8 },
+    // the next hop of the routes after it, :: is the sender
+    NextHop(Ipv6addr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RIPng {
+    pub command: u8,
+    pub version: u8,
+    pub entries: Vec<RipngEntry>,
+}
+
+impl RIPng {
+    const NEXT_HOP_METRIC: u8 = 0xff;
+
+    pub fn from_bytes(bytes: &mut Bytes) -> Result<Self> {
+        bytes.require(4)?;
+
+        let command = bytes[0];
+        let version = bytes[1];
+        if !matches!(command, command::REQUEST | command::RESPONSE) {
+            return Err(anyhow!("RIPng command {command} is invalid"));
+        }
+        bytes.shift_first(4)?;
+
+        let mut entries = Vec::new();
+        while !bytes.is_empty() {
+            bytes.require(ENTRY_LENGTH)?;
+            let prefix = Ipv6addr(bytes[0..16].to_u128());
+            entries.push(
+                match bytes[19] {
+                    Self::NEXT_HOP_METRIC => RipngEntry::NextHop(prefix),
+                    metric => RipngEntry::Route { prefix, route_tag: bytes[16..18].to_u16(), prefix_length: bytes[18], metric },
+                }
+            );
+            bytes.shift_first(ENTRY_LENGTH)?;
+        }
+
+        Ok(
+            Self {
+                command,
+                version,
+                entries,
+            }
+        )
+    }
+
+    // the routes with the next hop that applies to them
+    pub fn routes(&self) -> Vec<(Ipv6addr, &RipngEntry)> {
+        let mut next_hop = Ipv6addr(0);
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                RipngEntry::NextHop(address) => {
+                    next_hop = *address;
+                    None
+                },
+                route => Some((next_hop, route)),
+            })
+            .collect()
+    }
+}
+
+
+
+pub struct RipDissector;
+
+impl Dissector for RipDissector {
+    fn name(&self) -> &str {
+        "rip"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::ApplicationLayer(ApplicationLayer::RIP(RIP::from_bytes(bytes)?)))
+    }
+}
+
+pub struct RipngDissector;
+
+impl Dissector for RipngDissector {
+    fn name(&self) -> &str {
+        "ripng"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::ApplicationLayer(ApplicationLayer::RIPng(RIPng::from_bytes(bytes)?)))
+    }
+}
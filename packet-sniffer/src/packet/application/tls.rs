@@ -0,0 +1,821 @@
+use std::{collections::HashMap, fmt::Display, path::Path, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use byte_slice::{Bytes, SliceToUnsigned};
+
+use crate::{packet::{dissector::{Context, Dissector}, reader::Reader, Layer, Packet}, pcap, reassembly::{Flow, TcpReassembler}};
+
+use super::ApplicationLayer;
+
+pub mod fingerprint;
+pub mod x509;
+
+
+// TLS 1.0 to 1.3 (RFC 5246, RFC 8446) and SSL 3.0 records. Every record has a 5 byte header with its content
+// type, version and length, handshake messages have a 4 byte header of their own and may be split over
+// records or share one. What follows a ChangeCipherSpec is encrypted, as is everything after the hellos in
+// TLS 1.3 where the records pretend to be application data. A segment alone is decoded as far as it carries
+// complete records, TlsSessions follows whole connections and keeps the hellos and certificates of each. The
+// fingerprints of the hellos are in fingerprint.rs.
+
+
+
+pub const PORTS: [u16; 7] = [443, 465, 636, 853, 993, 995, 8443];
+const RECORD_HEADER_LENGTH: usize = 5;
+// 2^14 bytes of plaintext plus what compression and encryption may add
+const MAX_RECORD_LENGTH: usize = (1 << 14) + 2048;
+// the random of a ServerHello that is a HelloRetryRequest, the sha-256 of "HelloRetryRequest"
+const HELLO_RETRY_REQUEST: [u8; 32] = [
+    0xcf, 0x21, 0xad, 0x74, 0xe5, 0x9a, 0x61, 0x11, 0xbe, 0x1d, 0x8c, 0x02, 0x1e, 0x65, 0xb8, 0x91,
+    0xc2, 0xa2, 0x11, 0x16, 0x7a, 0xbb, 0x8c, 0x5e, 0x07, 0x9e, 0x09, 0xe2, 0xc8, 0xa8, 0x33, 0x9c,
+];
+
+pub mod content_type {
+    pub const CHANGE_CIPHER_SPEC: u8 = 20;
+    pub const ALERT: u8 = 21;
+    pub const HANDSHAKE: u8 = 22;
+    pub const APPLICATION_DATA: u8 = 23;
+    pub const HEARTBEAT: u8 = 24;
+}
+
+pub mod handshake_type {
+    pub const HELLO_REQUEST: u8 = 0;
+    pub const CLIENT_HELLO: u8 = 1;
+    pub const SERVER_HELLO: u8 = 2;
+    pub const NEW_SESSION_TICKET: u8 = 4;
+    pub const ENCRYPTED_EXTENSIONS: u8 = 8;
+    pub const CERTIFICATE: u8 = 11;
+    pub const SERVER_KEY_EXCHANGE: u8 = 12;
+    pub const CERTIFICATE_REQUEST: u8 = 13;
+    pub const SERVER_HELLO_DONE: u8 = 14;
+    pub const CERTIFICATE_VERIFY: u8 = 15;
+    pub const CLIENT_KEY_EXCHANGE: u8 = 16;
+    pub const FINISHED: u8 = 20;
+}
+
+// https://www.iana.org/assignments/tls-extensiontype-values/tls-extensiontype-values.xhtml
+pub mod extension_type {
+    pub const SERVER_NAME: u16 = 0;
+    pub const SUPPORTED_GROUPS: u16 = 10;
+    pub const EC_POINT_FORMATS: u16 = 11;
+    pub const SIGNATURE_ALGORITHMS: u16 = 13;
+    pub const ALPN: u16 = 16;
+    pub const SUPPORTED_VERSIONS: u16 = 43;
+    pub const KEY_SHARE: u16 = 51;
+}
+
+pub mod version {
+    pub const SSL_3_0: u16 = 0x0300;
+    pub const TLS_1_0: u16 = 0x0301;
+    pub const TLS_1_1: u16 = 0x0302;
+    pub const TLS_1_2: u16 = 0x0303;
+    pub const TLS_1_3: u16 = 0x0304;
+}
+
+pub fn version_name(version: u16) -> &'static str {
+    match version {
+        version::SSL_3_0 => "SSL 3.0",
+        version::TLS_1_0 => "TLS 1.0",
+        version::TLS_1_1 => "TLS 1.1",
+        version::TLS_1_2 => "TLS 1.2",
+        version::TLS_1_3 => "TLS 1.3",
+        _ => "Unknown",
+    }
+}
+
+// reserved values like 0x0a0a and 0xfafa clients sprinkle over their lists to keep servers tolerant (RFC 8701)
+pub fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyShareEntry {
+    pub group: u16,
+    // empty in a HelloRetryRequest, which only names the group
+    pub key_exchange: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExtensionValue {
+    // host names, empty in a ServerHello that accepted the name
+    ServerName(Vec<String>),
+    SupportedGroups(Vec<u16>),
+    EcPointFormats(Vec<u8>),
+    SignatureAlgorithms(Vec<u16>),
+    // every protocol the client offers, the one the server picked
+    Alpn(Vec<String>),
+    // every version the client offers, the one the server picked
+    SupportedVersions(Vec<u16>),
+    KeyShare(Vec<KeyShareEntry>),
+    Unknown(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extension {
+    pub extension_type: u16,
+    pub value: ExtensionValue,
+}
+
+impl Extension {
+    fn from_reader(reader: &mut Reader, handshake_type: u8) -> Result<Self> {
+        let extension_type = reader.u16()?;
+        let length = reader.u16()? as usize;
+        let mut data = reader.sub_reader(length)?;
+        let client = handshake_type == handshake_type::CLIENT_HELLO;
+
+        let value = match extension_type {
+            extension_type::SERVER_NAME => {
+                let mut names = Vec::new();
+                if !data.is_empty() {
+                    let length = data.u16()? as usize;
+                    let mut list = data.sub_reader(length)?;
+                    while !list.is_empty() {
+                        // 0 is the only name type there is, a host name
+                        let _name_type = list.u8()?;
+                        let length = list.u16()? as usize;
+                        names.push(String::from_utf8_lossy(list.take(length)?).into_owned());
+                    }
+                }
+                ExtensionValue::ServerName(names)
+            },
+            extension_type::SUPPORTED_GROUPS => ExtensionValue::SupportedGroups(data.u16_list()?),
+            extension_type::EC_POINT_FORMATS => {
+                let length = data.u8()? as usize;
+                ExtensionValue::EcPointFormats(data.take(length)?.to_vec())
+            },
+            extension_type::SIGNATURE_ALGORITHMS => ExtensionValue::SignatureAlgorithms(data.u16_list()?),
+            extension_type::ALPN => {
+                let length = data.u16()? as usize;
+                let mut list = data.sub_reader(length)?;
+                ExtensionValue::Alpn(list.until_empty(|list| {
+                    let length = list.u8()? as usize;
+                    Ok(String::from_utf8_lossy(list.take(length)?).into_owned())
+                })?)
+            },
+            extension_type::SUPPORTED_VERSIONS => match client {
+                true => {
+                    let length = data.u8()? as usize;
+                    let mut list = data.sub_reader(length)?;
+                    ExtensionValue::SupportedVersions(list.until_empty(Reader::u16)?)
+                },
+                false => ExtensionValue::SupportedVersions(vec![data.u16()?]),
+            },
+            extension_type::KEY_SHARE => {
+                let key_share = |reader: &mut Reader| -> Result<KeyShareEntry> {
+                    let group = reader.u16()?;
+                    let length = reader.u16()? as usize;
+                    Ok(KeyShareEntry { group, key_exchange: reader.take(length)?.to_vec() })
+                };
+                match (client, length) {
+                    (true, _) => {
+                        let length = data.u16()? as usize;
+                        ExtensionValue::KeyShare(data.sub_reader(length)?.until_empty(key_share)?)
+                    },
+                    // a HelloRetryRequest asks for a group
+                    (false, 2) => ExtensionValue::KeyShare(vec![KeyShareEntry { group: data.u16()?, key_exchange: Vec::new() }]),
+                    (false, _) => ExtensionValue::KeyShare(vec![key_share(&mut data)?]),
+                }
+            },
+            _ => ExtensionValue::Unknown(data.rest().to_vec()),
+        };
+
+        Ok(
+            Self {
+                extension_type,
+                value,
+            }
+        )
+    }
+}
+
+fn extensions(reader: &mut Reader, handshake_type: u8) -> Result<Vec<Extension>> {
+    // extensions are optional before TLS 1.2
+    if reader.is_empty() {
+        return Ok(Vec::new());
+    }
+    let length = reader.u16()? as usize;
+    reader.sub_reader(length)?.until_empty(|reader| Extension::from_reader(reader, handshake_type))
+}
+
+fn extension(extensions: &[Extension], extension_type: u16) -> Option<&ExtensionValue> {
+    extensions
+        .iter()
+        .find(|extension| extension.extension_type == extension_type)
+        .map(|extension| &extension.value)
+}
+
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientHello {
+    // the legacy version, 0x0303 for TLS 1.3 which is offered in supported_versions instead
+    pub version: u16,
+    pub random: [u8; 32],
+    pub session_id: Vec<u8>,
+    pub cipher_suites: Vec<u16>,
+    pub compression_methods: Vec<u8>,
+    pub extensions: Vec<Extension>,
+}
+
+impl ClientHello {
+    fn from_reader(reader: &mut Reader) -> Result<Self> {
+        let version = reader.u16()?;
+        let random = reader.take(32)?.try_into()?;
+        let length = reader.u8()? as usize;
+        let session_id = reader.take(length)?.to_vec();
+        let length = reader.u16()? as usize;
+        let cipher_suites = reader.sub_reader(length)?.until_empty(Reader::u16)?;
+        let length = reader.u8()? as usize;
+        let compression_methods = reader.take(length)?.to_vec();
+        let extensions = extensions(reader, handshake_type::CLIENT_HELLO)?;
+
+        Ok(
+            Self {
+                version,
+                random,
+                session_id,
+                cipher_suites,
+                compression_methods,
+                extensions,
+            }
+        )
+    }
+
+    pub fn extension(&self, extension_type: u16) -> Option<&ExtensionValue> {
+        extension(&self.extensions, extension_type)
+    }
+
+    pub fn server_name(&self) -> Option<&str> {
+        match self.extension(extension_type::SERVER_NAME) {
+            Some(ExtensionValue::ServerName(names)) => names.first().map(String::as_str),
+            _ => None,
+        }
+    }
+
+    pub fn alpn(&self) -> &[String] {
+        match self.extension(extension_type::ALPN) {
+            Some(ExtensionValue::Alpn(protocols)) => protocols,
+            _ => &[],
+        }
+    }
+
+    pub fn supported_versions(&self) -> &[u16] {
+        match self.extension(extension_type::SUPPORTED_VERSIONS) {
+            Some(ExtensionValue::SupportedVersions(versions)) => versions,
+            _ => &[],
+        }
+    }
+
+    // the highest version offered
+    pub fn max_version(&self) -> u16 {
+        self.supported_versions()
+            .iter()
+            .copied()
+            .filter(|version| !is_grease(*version))
+            .max()
+            .unwrap_or(self.version)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerHello {
+    pub version: u16,
+    pub random: [u8; 32],
+    pub session_id: Vec<u8>,
+    pub cipher_suite: u16,
+    pub compression_method: u8,
+    pub extensions: Vec<Extension>,
+}
+
+impl ServerHello {
+    fn from_reader(reader: &mut Reader) -> Result<Self> {
+        let version = reader.u16()?;
+        let random = reader.take(32)?.try_into()?;
+        let length = reader.u8()? as usize;
+        let session_id = reader.take(length)?.to_vec();
+        let cipher_suite = reader.u16()?;
+        let compression_method = reader.u8()?;
+        let extensions = extensions(reader, handshake_type::SERVER_HELLO)?;
+
+        Ok(
+            Self {
+                version,
+                random,
+                session_id,
+                cipher_suite,
+                compression_method,
+                extensions,
+            }
+        )
+    }
+
+    pub fn extension(&self, extension_type: u16) -> Option<&ExtensionValue> {
+        extension(&self.extensions, extension_type)
+    }
+
+    // the version of the connection
+    pub fn selected_version(&self) -> u16 {
+        match self.extension(extension_type::SUPPORTED_VERSIONS) {
+            Some(ExtensionValue::SupportedVersions(versions)) => versions.first().copied().unwrap_or(self.version),
+            _ => self.version,
+        }
+    }
+
+    pub fn alpn(&self) -> Option<&str> {
+        match self.extension(extension_type::ALPN) {
+            Some(ExtensionValue::Alpn(protocols)) => protocols.first().map(String::as_str),
+            _ => None,
+        }
+    }
+
+    pub fn is_hello_retry_request(&self) -> bool {
+        self.random == HELLO_RETRY_REQUEST
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Handshake {
+    ClientHello(ClientHello),
+    ServerHello(ServerHello),
+    // the chain as sent, the server's own certificate first
+    Certificate(Vec<x509::Certificate>),
+    Other { handshake_type: u8, body: Vec<u8> },
+}
+
+impl Handshake {
+    fn from_message(handshake_type: u8, body: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new("TLS", body);
+
+        Ok(
+            match handshake_type {
+                handshake_type::CLIENT_HELLO => Handshake::ClientHello(ClientHello::from_reader(&mut reader)?),
+                handshake_type::SERVER_HELLO => Handshake::ServerHello(ServerHello::from_reader(&mut reader)?),
+                handshake_type::CERTIFICATE => Handshake::Certificate(Self::certificates(&mut reader)?),
+                handshake_type => Handshake::Other { handshake_type, body: body.to_vec() },
+            }
+        )
+    }
+
+    // TLS 1.3 puts a request context before the list and extensions after every certificate, the list length
+    // alone tells the formats apart
+    fn certificates(reader: &mut Reader) -> Result<Vec<x509::Certificate>> {
+        let tls12 = reader.message().get(..3).is_some_and(|length| length.to_u32() as usize + 3 == reader.message().len());
+        let tls13 = !tls12;
+        if tls13 {
+            let length = reader.u8()? as usize;
+            reader.take(length)?;
+        }
+
+        let length = reader.u24()? as usize;
+        reader.sub_reader(length)?.until_empty(|list| {
+            let length = list.u24()? as usize;
+            let certificate = x509::Certificate::from_der(list.take(length)?)?;
+            if tls13 {
+                let length = list.u16()? as usize;
+                list.take(length)?;
+            }
+            Ok(certificate)
+        })
+    }
+
+    pub fn handshake_type(&self) -> u8 {
+        match self {
+            Handshake::ClientHello(_) => handshake_type::CLIENT_HELLO,
+            Handshake::ServerHello(_) => handshake_type::SERVER_HELLO,
+            Handshake::Certificate(_) => handshake_type::CERTIFICATE,
+            Handshake::Other { handshake_type, .. } => *handshake_type,
+        }
+    }
+}
+
+// the handshake messages at the start of `data` and how many bytes they took, a message that is not complete
+// yet is left over. Decoding stops at the first message that fails, the ones before it are kept.
+fn handshake_messages(data: &[u8]) -> (Vec<Handshake>, usize, Option<anyhow::Error>) {
+    let mut messages = Vec::new();
+    let mut offset = 0;
+
+    while let Some(header) = data.get(offset..offset + 4) {
+        let length = header[1..4].to_u32() as usize;
+        let Some(body) = data.get(offset + 4..offset + 4 + length) else {
+            break;
+        };
+        match Handshake::from_message(header[0], body) {
+            Ok(message) => messages.push(message),
+            Err(error) => return (messages, offset, Some(error)),
+        }
+        offset += 4 + length;
+    }
+
+    (messages, offset, None)
+}
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Alert {
+    // 1 warning, 2 fatal
+    pub level: u8,
+    pub description: u8,
+}
+
+impl Alert {
+    pub fn description_name(&self) -> &'static str {
+        match self.description {
+            0 => "close_notify",
+            10 => "unexpected_message",
+            20 => "bad_record_mac",
+            22 => "record_overflow",
+            40 => "handshake_failure",
+            42 => "bad_certificate",
+            43 => "unsupported_certificate",
+            44 => "certificate_revoked",
+            45 => "certificate_expired",
+            46 => "certificate_unknown",
+            47 => "illegal_parameter",
+            48 => "unknown_ca",
+            49 => "access_denied",
+            50 => "decode_error",
+            51 => "decrypt_error",
+            70 => "protocol_version",
+            71 => "insufficient_security",
+            80 => "internal_error",
+            86 => "inappropriate_fallback",
+            90 => "user_canceled",
+            109 => "missing_extension",
+            110 => "unsupported_extension",
+            112 => "unrecognized_name",
+            116 => "certificate_required",
+            120 => "no_application_protocol",
+            _ => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordBody {
+    ChangeCipherSpec,
+    Alert(Alert),
+    // the messages the record completed
+    Handshake(Vec<Handshake>),
+    ApplicationData(Vec<u8>),
+    Heartbeat(Vec<u8>),
+    // encrypted handshake messages and alerts, or handshake messages split over records of another segment
+    Opaque(Vec<u8>),
+    Unknown(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsRecord {
+    pub content_type: u8,
+    pub version: u16,
+    pub body: RecordBody,
+}
+
+impl TlsRecord {
+    // the complete records of a segment, a record that continues in the next segment is left in `bytes`
+    pub fn from_tcp_bytes(bytes: &mut Bytes) -> Result<Vec<Self>> {
+        let mut records = Vec::new();
+        let mut encrypted = false;
+
+        while let Some(length) = record_length(&bytes[..])?.filter(|length| *length <= bytes.len()) {
+            let record = &bytes[..length];
+            let body = match record[0] {
+                content_type::HANDSHAKE if !encrypted => match handshake_messages(&record[RECORD_HEADER_LENGTH..]) {
+                    (messages, length, None) if length == record.len() - RECORD_HEADER_LENGTH => RecordBody::Handshake(messages),
+                    _ => RecordBody::Opaque(record[RECORD_HEADER_LENGTH..].to_vec()),
+                },
+                content_type => body(content_type, &record[RECORD_HEADER_LENGTH..], encrypted),
+            };
+            encrypted |= body == RecordBody::ChangeCipherSpec;
+            records.push(Self { content_type: record[0], version: record[1..3].to_u16(), body });
+            bytes.shift_first(length)?;
+        }
+
+        match records.is_empty() {
+            true => Err(anyhow!("No complete TLS record in the segment")),
+            false => Ok(records),
+        }
+    }
+}
+
+// the body of every record but handshakes
+fn body(content_type: u8, fragment: &[u8], encrypted: bool) -> RecordBody {
+    match (content_type, fragment) {
+        (content_type::CHANGE_CIPHER_SPEC, _) => RecordBody::ChangeCipherSpec,
+        (content_type::ALERT, [level, description]) if !encrypted => RecordBody::Alert(Alert { level: *level, description: *description }),
+        (content_type::APPLICATION_DATA, _) => RecordBody::ApplicationData(fragment.to_vec()),
+        (content_type::HEARTBEAT, _) => RecordBody::Heartbeat(fragment.to_vec()),
+        (content_type::ALERT | content_type::HANDSHAKE, _) => RecordBody::Opaque(fragment.to_vec()),
+        _ => RecordBody::Unknown(fragment.to_vec()),
+    }
+}
+
+// the length of the record a stream starts with including its header, None while the header is incomplete
+pub fn record_length(stream: &[u8]) -> Result<Option<usize>> {
+    let Some(header) = stream.get(..RECORD_HEADER_LENGTH) else {
+        return Ok(None);
+    };
+    if !is_tls(header) {
+        return Err(anyhow!("TLS record header {header:02x?} is invalid"));
+    }
+
+    Ok(Some(RECORD_HEADER_LENGTH + header[3..5].to_u16() as usize))
+}
+
+// whether the bytes start with a record header
+pub fn is_tls(data: &[u8]) -> bool {
+    match data.get(..RECORD_HEADER_LENGTH) {
+        Some([content_type, 3, minor, ..]) => {
+            (content_type::CHANGE_CIPHER_SPEC..=content_type::HEARTBEAT).contains(content_type)
+                && *minor <= 4
+                && data[3..5].to_u16() as usize <= MAX_RECORD_LENGTH
+        },
+        _ => false,
+    }
+}
+
+
+
+// what the handshake of a connection told
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TlsSession {
+    pub client_hello: Option<ClientHello>,
+    pub server_hello: Option<ServerHello>,
+    pub certificates: Vec<x509::Certificate>,
+    pub alerts: Vec<Alert>,
+}
+
+impl TlsSession {
+    pub fn ja3(&self) -> Option<String> {
+        self.client_hello.as_ref().map(fingerprint::ja3)
+    }
+
+    pub fn ja3s(&self) -> Option<String> {
+        self.server_hello.as_ref().map(fingerprint::ja3s)
+    }
+
+    pub fn ja4(&self) -> Option<String> {
+        self.client_hello.as_ref().map(fingerprint::ja4)
+    }
+
+    // what the fingerprint database knows the client as, by its JA4 before its JA3
+    pub fn client_application(&self) -> Option<String> {
+        [self.ja4(), self.ja3()].into_iter().flatten().find_map(|fingerprint| fingerprint::label(&fingerprint))
+    }
+
+    pub fn server_application(&self) -> Option<String> {
+        self.ja3s().and_then(|fingerprint| fingerprint::label(&fingerprint))
+    }
+
+    // the value of one of FIELDS, None when the handshake did not tell
+    pub fn field(&self, name: &str) -> Option<String> {
+        let client_hello = self.client_hello.as_ref();
+        let server_hello = self.server_hello.as_ref();
+
+        match name {
+            "tls.sni" => client_hello.and_then(ClientHello::server_name).map(str::to_owned),
+            "tls.alpn" => server_hello.and_then(ServerHello::alpn).map(str::to_owned),
+            "tls.version" => server_hello.map(|hello| version_name(hello.selected_version()).to_owned()),
+            "tls.cipher" => server_hello.map(|hello| format!("{:#06x}", hello.cipher_suite)),
+            "tls.ja3" => self.ja3(),
+            "tls.ja3_string" => client_hello.map(fingerprint::ja3_string),
+            "tls.ja3s" => self.ja3s(),
+            "tls.ja3s_string" => server_hello.map(fingerprint::ja3s_string),
+            "tls.ja4" => self.ja4(),
+            "tls.client_application" => self.client_application(),
+            "tls.server_application" => self.server_application(),
+            _ => None,
+        }
+    }
+}
+
+// the fields sessions can be filtered by
+pub const FIELDS: [&str; 11] = [
+    "tls.sni", "tls.alpn", "tls.version", "tls.cipher", "tls.ja3", "tls.ja3_string", "tls.ja3s", "tls.ja3s_string", "tls.ja4",
+    "tls.client_application", "tls.server_application",
+];
+
+// keeps the sessions whose field has the value, like tls.ja4==t13d1516h2_8daaf6152771_e5627efa2ab1, values are
+// compared without case
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    pub field: String,
+    pub value: String,
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (field, value) = s.split_once("==").ok_or(anyhow!("TLS filter {s} has to compare a field to a value"))?;
+        let field = field.trim().to_lowercase();
+
+        match FIELDS.contains(&field.as_str()) {
+            true => Ok(Self { field, value: value.trim().to_owned() }),
+            false => Err(anyhow!("Unknown TLS field {field}, the fields are {}", FIELDS.join(", "))),
+        }
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, session: &TlsSession) -> bool {
+        session.field(&self.field).is_some_and(|value| value.eq_ignore_ascii_case(&self.value))
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct Direction {
+    // handshake messages that continue in the next record
+    handshake: Vec<u8>,
+    // after a ChangeCipherSpec
+    encrypted: bool,
+}
+
+// follows the tls connections of a capture, sessions are keyed by the flow from the client to the server
+#[derive(Debug, Default, Clone)]
+pub struct TlsSessions {
+    reassembler: TcpReassembler,
+    directions: HashMap<Flow, Direction>,
+    sessions: HashMap<Flow, TlsSession>,
+}
+
+impl TlsSessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the records the packet completed, connections on other ports are recognized by their first record. A
+    // stream that fails to frame ends with the error after the records framed before it.
+    pub fn observe(&mut self, packet: &Packet) -> Vec<(Flow, Result<TlsRecord>)> {
+        let Some((flow, tcp)) = Flow::of_segment(packet) else {
+            return Vec::new();
+        };
+        let known = self.directions.contains_key(&flow) || PORTS.contains(&flow.port_src) || PORTS.contains(&flow.port_dst);
+        if !known && !is_tls(&tcp.payload[..]) {
+            return Vec::new();
+        }
+        self.directions.entry(flow).or_default();
+        self.directions.entry(flow.reversed()).or_default();
+        self.reassembler.observe(packet);
+
+        let (framed, error) = self.reassembler.messages(&flow, record_length);
+        let mut records: Vec<_> = framed.iter().map(|record| (flow, Ok(self.record(flow, record)))).collect();
+        records.extend(error.map(|error| (flow, Err(error))));
+
+        if self.reassembler.is_closed(&flow) && self.reassembler.is_closed(&flow.reversed()) {
+            for flow in [flow, flow.reversed()] {
+                self.reassembler.remove(&flow);
+                self.directions.remove(&flow);
+            }
+        }
+
+        records
+    }
+
+    // handshake messages that fail to decode leave the record opaque rather than failing the connection
+    fn record(&mut self, flow: Flow, record: &[u8]) -> TlsRecord {
+        let direction = self.directions.entry(flow).or_default();
+        let (content_type, fragment) = (record[0], &record[RECORD_HEADER_LENGTH..]);
+
+        let body = match content_type {
+            content_type::HANDSHAKE if !direction.encrypted => {
+                direction.handshake.extend_from_slice(fragment);
+                let (messages, length, error) = handshake_messages(&direction.handshake);
+                // there is no telling where the message after a broken one starts, so nothing buffered is kept
+                match error {
+                    Some(_) => direction.handshake.clear(),
+                    None => {
+                        direction.handshake.drain(..length);
+                    },
+                }
+                match (messages.is_empty(), error) {
+                    (true, Some(_)) => RecordBody::Opaque(fragment.to_vec()),
+                    _ => RecordBody::Handshake(messages),
+                }
+            },
+            content_type => body(content_type, fragment, direction.encrypted),
+        };
+        direction.encrypted |= body == RecordBody::ChangeCipherSpec;
+
+        match &body {
+            RecordBody::Handshake(messages) => for message in messages {
+                match message {
+                    Handshake::ClientHello(hello) => self.sessions.entry(flow).or_default().client_hello = Some(hello.clone()),
+                    Handshake::ServerHello(hello) => self.sessions.entry(flow.reversed()).or_default().server_hello = Some(hello.clone()),
+                    Handshake::Certificate(certificates) => self.sessions.entry(flow.reversed()).or_default().certificates = certificates.clone(),
+                    Handshake::Other { .. } => {},
+                }
+            },
+            RecordBody::Alert(alert) => {
+                let client = match self.sessions.contains_key(&flow) {
+                    true => flow,
+                    false => flow.reversed(),
+                };
+                self.sessions.entry(client).or_default().alerts.push(*alert);
+            },
+            _ => {},
+        }
+
+        TlsRecord {
+            content_type,
+            version: record[1..3].to_u16(),
+            body,
+        }
+    }
+
+    // the session of the connection, whichever direction the flow is
+    pub fn session(&self, flow: &Flow) -> Option<&TlsSession> {
+        self.sessions.get(flow).or_else(|| self.sessions.get(&flow.reversed()))
+    }
+
+    pub fn sessions(&self) -> impl Iterator<Item = (&Flow, &TlsSession)> {
+        self.sessions.iter()
+    }
+
+    // keeps the sessions every filter matches
+    pub fn retain(&mut self, filters: &[Filter]) {
+        self.sessions.retain(|_, session| filters.iter().all(|filter| filter.matches(session)));
+    }
+}
+
+impl Display for TlsSessions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let endpoint = |address, port| format!("{address}:{port}");
+        let mut sessions: Vec<_> = self.sessions
+            .iter()
+            .map(|(flow, session)| (endpoint(flow.address_src, flow.port_src), endpoint(flow.address_dst, flow.port_dst), session))
+            .collect();
+        sessions.sort_by(|(client, server, _), (other_client, other_server, _)| (server, client).cmp(&(other_server, other_client)));
+
+        writeln!(
+            f,
+            "{:<22}  {:<22}  {:<30}  {:<7}  {:<32}  {:<36}  {:<32}  Application",
+            "Client", "Server", "Server name", "Version", "JA3", "JA4", "JA3S",
+        )?;
+        for (client, server, session) in sessions {
+            let field = |name| session.field(name).unwrap_or_else(|| "-".to_owned());
+            // the client's application, then the server's
+            let application = [session.client_application(), session.server_application()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" / ");
+            writeln!(
+                f,
+                "{client:<22}  {server:<22}  {:<30}  {:<7}  {:<32}  {:<36}  {:<32}  {application}",
+                field("tls.sni"),
+                field("tls.version"),
+                field("tls.ja3"),
+                field("tls.ja4"),
+                field("tls.ja3s"),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+
+// the sessions of a capture
+pub fn from_file(path: impl AsRef<Path>) -> Result<TlsSessions> {
+    let mut sessions = TlsSessions::new();
+
+    for record in pcap::read_file(path)? {
+        if let Ok(packet) = Packet::from_link_type(record.link_type, &mut Bytes::from_slice(&record.data)) {
+            // a connection that fails to parse only loses what was buffered for it
+            sessions.observe(&packet);
+        }
+    }
+
+    Ok(sessions)
+}
+
+
+
+impl Reader<'_> {
+    // a list of u16 preceded by its length in bytes
+    fn u16_list(&mut self) -> Result<Vec<u16>> {
+        let length = self.u16()? as usize;
+        self.sub_reader(length)?.until_empty(Self::u16)
+    }
+}
+
+
+
+pub struct TlsDissector;
+
+impl Dissector for TlsDissector {
+    fn name(&self) -> &str {
+        "tls"
+    }
+
+    fn probe(&self, bytes: &Bytes, _: &Context) -> bool {
+        is_tls(&bytes[..])
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::ApplicationLayer(ApplicationLayer::TLS(TlsRecord::from_tcp_bytes(bytes)?)))
+    }
+}
@@ -0,0 +1,225 @@
+use std::{collections::HashMap, path::Path, sync::{LazyLock, RwLock}};
+
+use anyhow::{anyhow, Result};
+use md5::{Digest, Md5};
+use sha2::Sha256;
+
+use super::{extension_type, is_grease, version, ClientHello, ExtensionValue, ServerHello};
+
+
+// Fingerprints of the hellos that tell tls clients and servers apart whatever they connect to. JA3 is the md5 of
+// the client's version, cipher suites, extensions, groups and point formats in the order sent, JA3S the md5 of
+// the server's version, cipher suite and extensions. JA4 is readable up front and sorts the lists before hashing,
+// so clients that shuffle their extensions keep their fingerprint:
+//
+// t13d1516h2_8daaf6152771_e5627efa2ab1
+//
+// transport (t for tcp), version, d with a server name or i without, the number of cipher suites and
+// extensions, the first and last character of the first alpn protocol, then the truncated sha-256 of the
+// sorted cipher suites and of the sorted extensions followed by the signature algorithms. GREASE values are
+// left out of all of them.
+//
+// The fingerprint database labels known applications, one fingerprint per line followed by the application
+// with # starting a comment:
+//
+// t13d1516h2_8daaf6152771_e5627efa2ab1  Chrome
+// e7d705a3286e19ea42f587b344ee6865      Tor
+
+
+
+// the values of a list without GREASE, joined like JA3 wants them
+fn ja3_list<T: Copy + Into<u16>>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|value| (*value).into())
+        .filter(|value| !is_grease(*value))
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn md5_hex(text: &str) -> String {
+    Md5::digest(text.as_bytes()).iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub fn ja3_string(hello: &ClientHello) -> String {
+    let extensions: Vec<u16> = hello.extensions.iter().map(|extension| extension.extension_type).collect();
+    let groups = match hello.extension(extension_type::SUPPORTED_GROUPS) {
+        Some(ExtensionValue::SupportedGroups(groups)) => ja3_list(groups),
+        _ => String::new(),
+    };
+    let point_formats = match hello.extension(extension_type::EC_POINT_FORMATS) {
+        Some(ExtensionValue::EcPointFormats(formats)) => ja3_list(formats),
+        _ => String::new(),
+    };
+
+    format!("{},{},{},{groups},{point_formats}", hello.version, ja3_list(&hello.cipher_suites), ja3_list(&extensions))
+}
+
+pub fn ja3(hello: &ClientHello) -> String {
+    md5_hex(&ja3_string(hello))
+}
+
+pub fn ja3s_string(hello: &ServerHello) -> String {
+    let extensions: Vec<u16> = hello.extensions.iter().map(|extension| extension.extension_type).collect();
+
+    format!("{},{},{}", hello.version, hello.cipher_suite, ja3_list(&extensions))
+}
+
+pub fn ja3s(hello: &ServerHello) -> String {
+    md5_hex(&ja3s_string(hello))
+}
+
+// the first 12 hex digits of the sha-256, zeros for nothing at all
+fn ja4_hash(text: &str) -> String {
+    match text.is_empty() {
+        true => "0".repeat(12),
+        false => Sha256::digest(text.as_bytes()).iter().take(6).map(|byte| format!("{byte:02x}")).collect(),
+    }
+}
+
+fn ja4_list(values: impl Iterator<Item = u16>, sort: bool) -> Vec<String> {
+    let mut values: Vec<_> = values.filter(|value| !is_grease(*value)).collect();
+    if sort {
+        values.sort_unstable();
+    }
+    values.iter().map(|value| format!("{value:04x}")).collect()
+}
+
+// the fingerprint of a hello sent over tcp, quic would start with q instead of t
+pub fn ja4(hello: &ClientHello) -> String {
+    let version = match hello.max_version() {
+        version::TLS_1_3 => "13",
+        version::TLS_1_2 => "12",
+        version::TLS_1_1 => "11",
+        version::TLS_1_0 => "10",
+        version::SSL_3_0 => "s3",
+        _ => "00",
+    };
+    let server_name = match hello.server_name() {
+        Some(_) => 'd',
+        None => 'i',
+    };
+    let cipher_suites = ja4_list(hello.cipher_suites.iter().copied(), true);
+    let extensions: Vec<u16> = hello.extensions
+        .iter()
+        .map(|extension| extension.extension_type)
+        .filter(|extension| !is_grease(*extension))
+        .collect();
+    let protocol = hello.alpn().first().and_then(|protocol| Some((*protocol.as_bytes().first()?, *protocol.as_bytes().last()?)));
+    // protocols that do not start and end with a letter or digit are given by the first and last hex digit
+    let alpn = match protocol {
+        Some((first, last)) if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() => format!("{}{}", first as char, last as char),
+        Some((first, last)) => format!("{:x}{:x}", first >> 4, last & 0x0f),
+        None => "00".to_owned(),
+    };
+
+    // the server name and alpn are in the readable part already
+    let hashed_extensions = ja4_list(
+        extensions.iter().copied().filter(|extension| !matches!(*extension, extension_type::SERVER_NAME | extension_type::ALPN)),
+        true,
+    );
+    let signature_algorithms = match hello.extension(extension_type::SIGNATURE_ALGORITHMS) {
+        Some(ExtensionValue::SignatureAlgorithms(algorithms)) => ja4_list(algorithms.iter().copied(), false),
+        _ => Vec::new(),
+    };
+    let extension_text = match (hashed_extensions.is_empty(), signature_algorithms.is_empty()) {
+        (true, _) => String::new(),
+        (false, true) => hashed_extensions.join(","),
+        (false, false) => format!("{}_{}", hashed_extensions.join(","), signature_algorithms.join(",")),
+    };
+
+    format!(
+        "t{version}{server_name}{:02}{:02}{alpn}_{}_{}",
+        cipher_suites.len().min(99),
+        extensions.len().min(99),
+        ja4_hash(&cipher_suites.join(",")),
+        ja4_hash(&extension_text),
+    )
+}
+
+
+
+// known fingerprints and the applications they belong to
+#[derive(Debug, Default, Clone)]
+pub struct FingerprintDatabase {
+    applications: HashMap<String, String>,
+}
+
+impl FingerprintDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, fingerprint: &str, application: &str) {
+        self.applications.insert(fingerprint.to_lowercase(), application.to_owned());
+    }
+
+    pub fn remove(&mut self, fingerprint: &str) -> Option<String> {
+        self.applications.remove(&fingerprint.to_lowercase())
+    }
+
+    pub fn label(&self, fingerprint: &str) -> Option<&str> {
+        self.applications.get(&fingerprint.to_lowercase()).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.applications.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.applications.is_empty()
+    }
+}
+
+// a JA3 or JA3S md5, or a JA4
+fn is_fingerprint(fingerprint: &str) -> bool {
+    let hex = |part: &str, length: usize| part.len() == length && part.bytes().all(|digit| digit.is_ascii_hexdigit());
+
+    match fingerprint.split('_').collect::<Vec<_>>()[..] {
+        [md5] => hex(md5, 32),
+        [readable, cipher_suites, extensions] => readable.len() == 10 && readable.is_ascii() && hex(cipher_suites, 12) && hex(extensions, 12),
+        _ => false,
+    }
+}
+
+pub fn parse_database(text: &str) -> Result<Vec<(String, String)>> {
+    text.lines()
+        .enumerate()
+        .map(|(line, entry)| (line, entry.split('#').next().unwrap_or_default().trim()))
+        .filter(|(_, entry)| !entry.is_empty())
+        .map(|(line, entry)| {
+            let (fingerprint, application) = entry.split_once(char::is_whitespace).unwrap_or((entry, ""));
+            match (is_fingerprint(fingerprint), application.trim()) {
+                (false, _) => Err(anyhow!("Line {}: {fingerprint} is not a JA3, JA3S or JA4 fingerprint", line + 1)),
+                (true, "") => Err(anyhow!("Line {}: {fingerprint} is missing an application", line + 1)),
+                (true, application) => Ok((fingerprint.to_owned(), application.to_owned())),
+            }
+        })
+        .collect()
+}
+
+// adds the fingerprints of the file to the database sessions are labeled with, returns how many there were
+pub fn load_file(path: impl AsRef<Path>) -> Result<usize> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path).map_err(|error| anyhow!("Failed to read {}: {error}", path.display()))?;
+    let entries = parse_database(&text)?;
+    let count = entries.len();
+
+    with_database(|database| entries.iter().for_each(|(fingerprint, application)| database.insert(fingerprint, application)));
+
+    Ok(count)
+}
+
+
+static DATABASE: LazyLock<RwLock<FingerprintDatabase>> = LazyLock::new(|| RwLock::new(FingerprintDatabase::new()));
+
+// changes the fingerprints sessions are labeled with
+pub fn with_database<T>(change: impl FnOnce(&mut FingerprintDatabase) -> T) -> T {
+    change(&mut DATABASE.write().unwrap_or_else(|poisoned| poisoned.into_inner()))
+}
+
+// the application of a fingerprint in the database
+pub fn label(fingerprint: &str) -> Option<String> {
+    DATABASE.read().unwrap_or_else(|poisoned| poisoned.into_inner()).label(fingerprint).map(str::to_owned)
+}
@@ -0,0 +1,248 @@
+use anyhow::{anyhow, Result};
+use byte_slice::{Ipv4addr, Ipv6addr, SliceToUnsigned};
+
+
+// X.509 certificates (RFC 5280) as far as they tell who a server is: the names, the validity and the subject
+// alternative names of the to be signed part. Everything is DER, tag length value with constructed types
+// nesting further elements.
+
+mod tag {
+    pub const INTEGER: u8 = 0x02;
+    pub const OCTET_STRING: u8 = 0x04;
+    pub const OID: u8 = 0x06;
+    pub const UTC_TIME: u8 = 0x17;
+    pub const GENERALIZED_TIME: u8 = 0x18;
+    pub const BMP_STRING: u8 = 0x1e;
+    pub const SEQUENCE: u8 = 0x30;
+    // context specific
+    pub const VERSION: u8 = 0xa0;
+    pub const EXTENSIONS: u8 = 0xa3;
+    pub const DNS_NAME: u8 = 0x82;
+    pub const IP_ADDRESS: u8 = 0x87;
+}
+
+const SUBJECT_ALT_NAME: &str = "2.5.29.17";
+
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Certificate {
+    // 1 to 3
+    pub version: u8,
+    // hex
+    pub serial_number: String,
+    // distinguished names like "C=US, O=Example, CN=example.com"
+    pub issuer: String,
+    pub subject: String,
+    // "2024-01-01 00:00:00 UTC"
+    pub not_before: String,
+    pub not_after: String,
+    // dns names and ip addresses
+    pub subject_alt_names: Vec<String>,
+}
+
+impl Certificate {
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        let mut certificate = Der::new(der).expect(tag::SEQUENCE)?;
+        let mut tbs = certificate.expect(tag::SEQUENCE)?;
+
+        let version = match tbs.peek() {
+            Some(tag::VERSION) => tbs.expect(tag::VERSION)?.expect(tag::INTEGER)?.data.last().copied().unwrap_or_default()
+                .checked_add(1)
+                .ok_or(anyhow!("X.509 version is invalid"))?,
+            _ => 1,
+        };
+        let serial_number = tbs.expect(tag::INTEGER)?.data.iter().map(|byte| format!("{byte:02x}")).collect();
+        // the signature algorithm
+        tbs.expect(tag::SEQUENCE)?;
+        let issuer = name(tbs.expect(tag::SEQUENCE)?)?;
+        let mut validity = tbs.expect(tag::SEQUENCE)?;
+        let not_before = time(&mut validity)?;
+        let not_after = time(&mut validity)?;
+        let subject = name(tbs.expect(tag::SEQUENCE)?)?;
+        // the public key
+        tbs.expect(tag::SEQUENCE)?;
+
+        let mut subject_alt_names = Vec::new();
+        while let Some((element_tag, mut element)) = tbs.next()? {
+            if element_tag != tag::EXTENSIONS {
+                continue;
+            }
+            let mut extensions = element.expect(tag::SEQUENCE)?;
+            while let Some((_, mut extension)) = extensions.next()? {
+                let id = oid(extension.expect(tag::OID)?.data);
+                // critical is optional, the value is the last element
+                let mut value = None;
+                while let Some((value_tag, element)) = extension.next()? {
+                    if value_tag == tag::OCTET_STRING {
+                        value = Some(element);
+                    }
+                }
+                if let (SUBJECT_ALT_NAME, Some(mut value)) = (id.as_str(), value) {
+                    subject_alt_names = alt_names(value.expect(tag::SEQUENCE)?)?;
+                }
+            }
+        }
+
+        Ok(
+            Self {
+                version,
+                serial_number,
+                issuer,
+                subject,
+                not_before,
+                not_after,
+                subject_alt_names,
+            }
+        )
+    }
+
+    // the common name of the subject
+    pub fn common_name(&self) -> Option<&str> {
+        self.subject
+            .split(", ")
+            .find_map(|attribute| attribute.strip_prefix("CN="))
+    }
+}
+
+
+
+struct Der<'d> {
+    data: &'d [u8],
+    offset: usize,
+}
+
+impl<'d> Der<'d> {
+    fn new(data: &'d [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.offset).copied()
+    }
+
+    // the tag and the content of the next element, None at the end
+    fn next(&mut self) -> Result<Option<(u8, Der<'d>)>> {
+        let Some(element_tag) = self.peek() else {
+            return Ok(None);
+        };
+        let truncated = || anyhow!("DER element at offset {} is truncated", self.offset);
+
+        let first = *self.data.get(self.offset + 1).ok_or_else(truncated)?;
+        let (length, header) = match first {
+            0..=0x7f => (first as usize, 2),
+            // the long form gives the number of length bytes that follow
+            0x81..=0x84 => {
+                let count = (first & 0x7f) as usize;
+                let bytes = self.data.get(self.offset + 2..self.offset + 2 + count).ok_or_else(truncated)?;
+                (bytes.iter().fold(0, |length, byte| length << 8 | *byte as usize), 2 + count)
+            },
+            _ => return Err(anyhow!("DER length {first:#x} is not supported")),
+        };
+
+        let start = self.offset + header;
+        let content = self.data.get(start..start + length).ok_or_else(truncated)?;
+        self.offset = start + length;
+
+        Ok(Some((element_tag, Der::new(content))))
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<Der<'d>> {
+        match self.next()? {
+            Some((element_tag, element)) if element_tag == expected => Ok(element),
+            Some((element_tag, _)) => Err(anyhow!("DER tag {element_tag:#04x} where {expected:#04x} was expected")),
+            None => Err(anyhow!("DER element {expected:#04x} is missing")),
+        }
+    }
+}
+
+fn oid(data: &[u8]) -> String {
+    let Some((first, rest)) = data.split_first() else {
+        return String::new();
+    };
+
+    let mut arcs = vec![(*first / 40) as u64, (*first % 40) as u64];
+    let mut arc = 0u64;
+    // base 128, the top bit marks that more bytes follow
+    for byte in rest {
+        arc = arc << 7 | (*byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+        }
+    }
+
+    arcs.iter().map(ToString::to_string).collect::<Vec<_>>().join(".")
+}
+
+fn string(element_tag: u8, data: &[u8]) -> String {
+    match element_tag {
+        tag::BMP_STRING => String::from_utf16_lossy(&data.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect::<Vec<_>>()),
+        _ => String::from_utf8_lossy(data).into_owned(),
+    }
+}
+
+// a sequence of sets of attribute type and value pairs
+fn name(mut rdns: Der) -> Result<String> {
+    let mut attributes = Vec::new();
+
+    while let Some((_, mut rdn)) = rdns.next()? {
+        while let Some((_, mut attribute)) = rdn.next()? {
+            let id = oid(attribute.expect(tag::OID)?.data);
+            let (value_tag, value) = attribute.next()?.ok_or(anyhow!("X.509 attribute {id} has no value"))?;
+            let short_name = match id.as_str() {
+                "2.5.4.3" => "CN",
+                "2.5.4.5" => "serialNumber",
+                "2.5.4.6" => "C",
+                "2.5.4.7" => "L",
+                "2.5.4.8" => "ST",
+                "2.5.4.9" => "street",
+                "2.5.4.10" => "O",
+                "2.5.4.11" => "OU",
+                "0.9.2342.19200300.100.1.25" => "DC",
+                "1.2.840.113549.1.9.1" => "emailAddress",
+                id => id,
+            };
+            attributes.push(format!("{short_name}={}", string(value_tag, value.data)));
+        }
+    }
+
+    Ok(attributes.join(", "))
+}
+
+fn time(validity: &mut Der) -> Result<String> {
+    let (time_tag, time) = validity.next()?.ok_or(anyhow!("X.509 validity is missing a time"))?;
+    let time = String::from_utf8_lossy(time.data);
+    let digits = time.trim_end_matches('Z');
+
+    if !digits.bytes().all(|digit| digit.is_ascii_digit()) {
+        return Err(anyhow!("X.509 time {time:?} is invalid"));
+    }
+    let full = match (time_tag, digits.len()) {
+        // two digit years from 50 on are in the 1900s
+        (tag::UTC_TIME, 10 | 12) => match digits[..2].parse::<u8>() {
+            Ok(50..) => format!("19{digits}"),
+            _ => format!("20{digits}"),
+        },
+        (tag::GENERALIZED_TIME, 12 | 14) => digits.to_owned(),
+        _ => return Err(anyhow!("X.509 time {time:?} is invalid")),
+    };
+    let seconds = full.get(12..14).unwrap_or("00");
+
+    Ok(format!("{}-{}-{} {}:{}:{seconds} UTC", &full[0..4], &full[4..6], &full[6..8], &full[8..10], &full[10..12]))
+}
+
+fn alt_names(mut names: Der) -> Result<Vec<String>> {
+    let mut alt_names = Vec::new();
+
+    while let Some((name_tag, name)) = names.next()? {
+        match (name_tag, name.data.len()) {
+            (tag::DNS_NAME, _) => alt_names.push(String::from_utf8_lossy(name.data).into_owned()),
+            (tag::IP_ADDRESS, 4) => alt_names.push(Ipv4addr(name.data.to_u32()).to_string()),
+            (tag::IP_ADDRESS, 16) => alt_names.push(Ipv6addr(name.data.to_u128()).to_string()),
+            _ => {},
+        }
+    }
+
+    Ok(alt_names)
+}
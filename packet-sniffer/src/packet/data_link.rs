@@ -1,21 +1,35 @@
 use std::fmt::Debug;
 
 use byte_slice::{Bytes, MacAddress, SliceToUnsigned};
-use crate::packet::network::NetworkLayer;
+use crate::packet::dissector::{self, Context, Dissector, Key};
 use anyhow::Result;
 
 use super::{Layer, LayerTrait};
 
+pub mod cdp;
+pub mod hdlc;
+pub mod lldp;
+pub mod ppp;
+pub mod stp;
 
-#[derive(Debug, Default)]
+
+#[derive(Debug, Default, Clone)]
 pub enum DataLinkLayer<'a> {
     #[default]
     NULL,
 
-    UndefinedData(&'a Bytes<'a>),
+    UndefinedData(Bytes<'a>),
     ETHII(ETHII<'a>),
-    PPP,
-    HDLC,
+    IEEE802_3(IEEE802_3<'a>),
+    STP(stp::BPDU),
+    LLDP(lldp::LLDP),
+    CDP(cdp::CDP),
+    PPP(ppp::PPP<'a>),
+    PPPoE(ppp::PPPoE<'a>),
+    PppControl(ppp::PppControl),
+    HDLC(hdlc::HDLC<'a>),
+    CiscoHDLC(hdlc::CiscoHDLC<'a>),
+    SLARP(hdlc::SLARP),
 }
 
 impl<'a> DataLinkLayer<'a> {
@@ -24,10 +38,30 @@ impl<'a> DataLinkLayer<'a> {
             DataLinkLayer::NULL => "NULL",
             DataLinkLayer::UndefinedData(_) => "UndefinedData",
             DataLinkLayer::ETHII(_) => "ETHII",
-            DataLinkLayer::PPP => "PPP",
-            DataLinkLayer::HDLC => "HDLC",
+            DataLinkLayer::IEEE802_3(_) => "IEEE802_3",
+            DataLinkLayer::STP(_) => "STP",
+            DataLinkLayer::LLDP(_) => "LLDP",
+            DataLinkLayer::CDP(_) => "CDP",
+            DataLinkLayer::PPP(_) => "PPP",
+            DataLinkLayer::PPPoE(_) => "PPPoE",
+            DataLinkLayer::PppControl(_) => "PppControl",
+            DataLinkLayer::HDLC(_) => "HDLC",
+            DataLinkLayer::CiscoHDLC(_) => "CiscoHDLC",
+            DataLinkLayer::SLARP(_) => "SLARP",
         }
     }
+
+    // ethernet frames carry an ethertype or, below 0x0600, the length of an 802.3 frame
+    pub fn from_ethernet(bytes: &mut Bytes<'a>) -> Result<Self> {
+        let mac_header = MacHeader::from_bytes(bytes)?;
+
+        Ok(
+            match mac_header.ethertype < MIN_ETHERTYPE {
+                true => DataLinkLayer::IEEE802_3(IEEE802_3::from_header(mac_header, bytes)?),
+                false => DataLinkLayer::ETHII(ETHII::from_header(mac_header, bytes)?),
+            }
+        )
+    }
 }
 
 
@@ -35,50 +69,127 @@ impl<'a> DataLinkLayer<'a> {
 
 
 
-#[derive(Default)]
-pub struct Tag802_1Q(u16); // PCP 3 bits // DEI 1 bit // VID 12 bits
+#[derive(Default, Clone, Copy, PartialEq)]
+pub struct Tag802_1Q(pub u16); // PCP 3 bits // DEI 1 bit // VID 12 bits
+
+impl Tag802_1Q {
+    pub fn pcp(&self) -> u8 {
+        (self.0 >> 13) as u8
+    }
+
+    pub fn dei(&self) -> u8 {
+        ((self.0 >> 12) & 1) as u8
+    }
+
+    pub fn vid(&self) -> u16 {
+        self.0 & 0x0fff
+    }
+}
 
 impl Debug for Tag802_1Q {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Tag802_1Q")
-            .field("PCP", &(self.0 >> 13))
-            .field("DEI", &((self.0 >> 12) & 1))
-            .field("VID", &(self.0 & 0x0fff))
+            .field("PCP", &self.pcp())
+            .field("DEI", &self.dei())
+            .field("VID", &self.vid())
             .finish()
     }
 }
 
 
-#[derive(Debug, Default)]
+// tag protocol identifiers, 802.1ad names the outer tag of a QinQ frame the S-tag and the inner one the C-tag
+pub const TPID_802_1Q: u16 = 0x8100;
+pub const TPID_802_1AD: u16 = 0x88a8;
+// used for QinQ before 802.1ad was published
+pub const TPID_QINQ_LEGACY: u16 = 0x9100;
+
+pub fn is_vlan_tpid(ethertype: u16) -> bool {
+    matches!(ethertype, TPID_802_1Q | TPID_802_1AD | TPID_QINQ_LEGACY | 0x9200)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VlanTag {
+    pub tpid: u16,
+    pub tci: Tag802_1Q,
+}
+
+impl VlanTag {
+    pub fn new(tpid: u16, tci: Tag802_1Q) -> Self {
+        Self { tpid, tci }
+    }
+}
+
+impl Default for VlanTag {
+    fn default() -> Self {
+        Self::new(TPID_802_1Q, Tag802_1Q::default())
+    }
+}
+
+
+#[derive(Debug, Default, Clone)]
 pub struct MacHeader {
     pub address_dst: MacAddress,
     pub address_src: MacAddress,
-    pub tag_802_1q:  Option<Tag802_1Q>,
+    // outermost tag first
+    pub vlan_tags:   Vec<VlanTag>,
+    // the type after the last tag
     pub ethertype:   u16,
 }
 
 impl MacHeader {
     pub fn from_bytes(bytes: &mut Bytes) -> Result<Self> {
+        bytes.require(14)?;
+
         let address_dst = MacAddress::from(bytes[0..6].to_u64());
         let address_src = MacAddress::from(bytes[6..12].to_u64());
-        let tag_802_1q = bytes[12..14]
-            .eq(&[0x81, 0x00])
-            .then(|| { Tag802_1Q(bytes[14..16].to_u16()) });
-        let offset = if tag_802_1q.is_some() { 4 } else { 0 };
-        let ethertype = bytes[12+offset..14+offset].to_u16();
+        let mut vlan_tags = Vec::new();
+        let mut offset = 12;
+        let mut ethertype = bytes[offset..offset+2].to_u16();
+
+        while is_vlan_tpid(ethertype) {
+            bytes.require(offset+6)?;
+            vlan_tags.push(VlanTag::new(ethertype, Tag802_1Q(bytes[offset+2..offset+4].to_u16())));
+            offset += 4;
+            ethertype = bytes[offset..offset+2].to_u16();
+        }
 
-        bytes.shift_first(14+offset)?;
+        bytes.shift_first(offset+2)?;
 
         Ok(
             Self {
                 address_dst,
                 address_src,
-                tag_802_1q,
+                vlan_tags,
                 ethertype,
-                ..Default::default()
             }
         )
     }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(14 + 4 * self.vlan_tags.len());
+        bytes.extend(self.address_dst.octets());
+        bytes.extend(self.address_src.octets());
+        for tag in &self.vlan_tags {
+            bytes.extend(tag.tpid.to_be_bytes());
+            bytes.extend(tag.tci.0.to_be_bytes());
+        }
+        bytes.extend(self.ethertype.to_be_bytes());
+
+        bytes
+    }
+
+    // the service tag of a QinQ frame, None unless there are at least two tags
+    pub fn s_tag(&self) -> Option<&VlanTag> {
+        match self.vlan_tags.len() >= 2 {
+            true => self.vlan_tags.first(),
+            false => None,
+        }
+    }
+
+    // the customer tag, the innermost one
+    pub fn c_tag(&self) -> Option<&VlanTag> {
+        self.vlan_tags.last()
+    }
 }
 
 
@@ -87,7 +198,7 @@ impl MacHeader {
 
 
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct ETHII<'a> {
     pub mac_header: MacHeader,
     pub next_layer: Box<Layer<'a>>,
@@ -95,10 +206,13 @@ pub struct ETHII<'a> {
 
 impl<'a> ETHII<'a> {
     // TODO this does not need to be pub if DataLinkLayer has a from_bytes function
-    pub fn from_bytes(bytes: &'a mut Bytes) -> Result<Self> {
-        let mac_header = MacHeader::from_bytes(bytes)?;
+    pub fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        Self::from_header(MacHeader::from_bytes(bytes)?, bytes)
+    }
+
+    fn from_header(mac_header: MacHeader, bytes: &mut Bytes<'a>) -> Result<Self> {
         let next_layer = Box::new(
-            Layer::NetworkLayer(NetworkLayer::from_data(mac_header.ethertype, bytes)?)
+            dissector::dispatch(Key::Ethertype(mac_header.ethertype), bytes)?
         );
 
         Ok(
@@ -111,7 +225,184 @@ impl<'a> ETHII<'a> {
 }
 
 impl<'a> LayerTrait for ETHII<'a> {
-    fn next_layer(&self) -> &Layer {
+    fn next_layer(&self) -> &Layer<'_> {
         &self.next_layer
     }
-}
\ No newline at end of file
+}
+
+
+// service access points of the llc header
+pub mod sap {
+    pub const STP: u8 = 0x42;
+    pub const SNAP: u8 = 0xaa;
+    pub const IPX: u8 = 0xe0;
+    pub const NETBIOS: u8 = 0xf0;
+    pub const GLOBAL: u8 = 0xff;
+}
+
+// the smallest value of the type field that is an ethertype and not a length
+pub const MIN_ETHERTYPE: u16 = 0x0600;
+
+
+// IEEE 802.2 logical link control
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LLC {
+    pub dsap: u8,
+    pub ssap: u8,
+    pub control: u8,
+    // information and supervisory frames have a second control byte with N(R) and the poll/final bit
+    pub control_ext: Option<u8>,
+}
+
+impl LLC {
+    pub fn from_bytes(bytes: &Bytes) -> Result<Self> {
+        bytes.require(3)?;
+
+        let control = bytes[2];
+        let control_ext = match control & 0b11 == 0b11 {
+            true => None,
+            false => {
+                bytes.require(4)?;
+                Some(bytes[3])
+            },
+        };
+
+        Ok(
+            Self {
+                dsap: bytes[0],
+                ssap: bytes[1],
+                control,
+                control_ext,
+            }
+        )
+    }
+
+    // the lowest bit of the ssap tells commands and responses apart
+    pub fn is_response(&self) -> bool {
+        self.ssap & 1 == 1
+    }
+
+    pub fn header_length(&self) -> usize {
+        match self.control_ext {
+            Some(_) => 4,
+            None => 3,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.dsap, self.ssap, self.control];
+        bytes.extend(self.control_ext);
+
+        bytes
+    }
+}
+
+
+// subnetwork access protocol, follows an llc header with both saps set to 0xaa
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SNAP {
+    // 24 bits, 0 means the protocol id is an ethertype
+    pub oui: u32,
+    pub protocol_id: u16,
+}
+
+impl SNAP {
+    pub fn from_bytes(bytes: &Bytes) -> Result<Self> {
+        bytes.require(5)?;
+
+        Ok(
+            Self {
+                oui: bytes[0..3].to_u32(),
+                protocol_id: bytes[3..5].to_u16(),
+            }
+        )
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.oui.to_be_bytes()[1..].to_vec();
+        bytes.extend(self.protocol_id.to_be_bytes());
+
+        bytes
+    }
+}
+
+
+// an 802.3 frame, mac_header.ethertype holds the length of everything after the mac header
+#[derive(Debug, Default, Clone)]
+pub struct IEEE802_3<'a> {
+    pub mac_header: MacHeader,
+    // None for novell's raw 802.3 frames, which carry ipx right after the length
+    pub llc: Option<LLC>,
+    pub snap: Option<SNAP>,
+    pub next_layer: Box<Layer<'a>>,
+}
+
+impl<'a> IEEE802_3<'a> {
+    pub fn length(&self) -> u16 {
+        self.mac_header.ethertype
+    }
+
+    fn from_header(mac_header: MacHeader, bytes: &mut Bytes<'a>) -> Result<Self> {
+        // frames shorter than 64 bytes are padded, frames cut short by the capture keep what is there
+        let length = mac_header.ethertype as usize;
+        if length < bytes.len() {
+            bytes.shift_last(bytes.len() - length)?;
+        }
+
+        if bytes.len() >= 2 && bytes[0..2] == [0xff, 0xff] {
+            let next_layer = Box::new(dissector::dispatch(Key::LlcSap(sap::IPX), bytes)?);
+            return Ok(Self { mac_header, llc: None, snap: None, next_layer });
+        }
+
+        let llc = LLC::from_bytes(bytes)?;
+        bytes.shift_first(llc.header_length())?;
+
+        let (snap, key) = match (llc.dsap, llc.ssap) {
+            (sap::SNAP, sap::SNAP) => {
+                let snap = SNAP::from_bytes(bytes)?;
+                bytes.shift_first(5)?;
+                let key = match snap.oui {
+                    0 => Key::Ethertype(snap.protocol_id),
+                    oui => Key::Snap(oui, snap.protocol_id),
+                };
+                (Some(snap), key)
+            },
+            (dsap, _) => (None, Key::LlcSap(dsap)),
+        };
+
+        let next_layer = Box::new(
+            match bytes.is_empty() {
+                true => Layer::NoLayer,
+                false => dissector::dispatch(key, bytes)?,
+            }
+        );
+
+        Ok(
+            Self {
+                mac_header,
+                llc: Some(llc),
+                snap,
+                next_layer,
+            }
+        )
+    }
+}
+
+impl<'a> LayerTrait for IEEE802_3<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+
+pub struct EthernetDissector;
+
+impl Dissector for EthernetDissector {
+    fn name(&self) -> &str {
+        "eth"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::DataLinkLayer(DataLinkLayer::from_ethernet(bytes)?))
+    }
+}
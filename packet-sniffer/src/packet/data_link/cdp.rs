@@ -0,0 +1,180 @@
+use anyhow::{anyhow, Result};
+use byte_slice::{Bytes, Ipv4addr, Ipv6addr, SliceToUnsigned};
+
+use crate::packet::{dissector::{Context, Dissector}, Layer};
+
+use super::{lldp::NetworkAddress, DataLinkLayer};
+
+
+// cisco discovery protocol, snap with cisco's oui and protocol id 0x2000 sent to 01:00:0c:cc:cc:cc
+// tlvs have a 16 bit type and a 16 bit length that counts the 4 byte header
+
+
+
+// the protocol of a cdp address, nlpid 0xcc is ipv4 and the 802.2 snap header of ipv6 is ipv6
+const NLPID_IPV4: &[u8] = &[0xcc];
+const SNAP_IPV6: &[u8] = &[0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x86, 0xdd];
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CdpCapabilities(pub u32);
+
+impl CdpCapabilities {
+    const NAMES: [&str; 10] = ["router", "transparent-bridge", "source-route-bridge", "switch", "host", "igmp", "repeater", "phone", "remote", "cvta"];
+
+    pub fn names(&self) -> Vec<&'static str> {
+        Self::NAMES
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| (self.0 >> bit) & 1 == 1)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CdpTlv {
+    DeviceId(String),
+    Addresses(Vec<NetworkAddress>),
+    PortId(String),
+    Capabilities(CdpCapabilities),
+    SoftwareVersion(String),
+    Platform(String),
+    NativeVlan(u16),
+    FullDuplex(bool),
+    ManagementAddresses(Vec<NetworkAddress>),
+    Unknown { tlv_type: u16, value: Vec<u8> },
+}
+
+impl CdpTlv {
+    fn from_value(tlv_type: u16, value: &[u8]) -> Result<Self> {
+        let text = || String::from_utf8_lossy(value).into_owned();
+        let require = |length: usize| match value.len() >= length {
+            true => Ok(()),
+            false => Err(anyhow!("CDP TLV {tlv_type} is shorter than {length} bytes")),
+        };
+
+        Ok(
+            match tlv_type {
+                0x0001 => CdpTlv::DeviceId(text()),
+                0x0002 => CdpTlv::Addresses(Self::addresses(value)?),
+                0x0003 => CdpTlv::PortId(text()),
+                0x0004 => {
+                    require(4)?;
+                    CdpTlv::Capabilities(CdpCapabilities(value[0..4].to_u32()))
+                },
+                0x0005 => CdpTlv::SoftwareVersion(text()),
+                0x0006 => CdpTlv::Platform(text()),
+                0x000a => {
+                    require(2)?;
+                    CdpTlv::NativeVlan(value[0..2].to_u16())
+                },
+                0x000b => {
+                    require(1)?;
+                    CdpTlv::FullDuplex(value[0] == 1)
+                },
+                0x0016 => CdpTlv::ManagementAddresses(Self::addresses(value)?),
+                tlv_type => CdpTlv::Unknown { tlv_type, value: value.to_vec() },
+            }
+        )
+    }
+
+    // a count followed by protocol type, protocol length, protocol, address length and address
+    fn addresses(value: &[u8]) -> Result<Vec<NetworkAddress>> {
+        let truncated = || anyhow!("CDP address list is truncated");
+        let count = value.get(0..4).ok_or_else(truncated)?.to_u32();
+        let mut offset = 4;
+        let mut addresses = Vec::new();
+
+        for _ in 0..count {
+            let protocol_length = *value.get(offset + 1).ok_or_else(truncated)? as usize;
+            let protocol = value.get(offset + 2..offset + 2 + protocol_length).ok_or_else(truncated)?;
+            offset += 2 + protocol_length;
+            let address_length = value.get(offset..offset + 2).ok_or_else(truncated)?.to_u16() as usize;
+            let address = value.get(offset + 2..offset + 2 + address_length).ok_or_else(truncated)?;
+            offset += 2 + address_length;
+
+            addresses.push(
+                match (protocol, address.len()) {
+                    (NLPID_IPV4, 4) => NetworkAddress::Ipv4(Ipv4addr(address.to_u32())),
+                    (SNAP_IPV6, 16) => NetworkAddress::Ipv6(Ipv6addr(address.to_u128())),
+                    _ => NetworkAddress::Other(protocol.last().copied().unwrap_or_default(), address.to_vec()),
+                }
+            );
+        }
+
+        Ok(addresses)
+    }
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CDP {
+    pub version: u8,
+    // seconds the information stays valid
+    pub ttl: u8,
+    pub checksum: u16,
+    pub tlvs: Vec<CdpTlv>,
+}
+
+impl CDP {
+    pub fn from_bytes(bytes: &mut Bytes) -> Result<Self> {
+        bytes.require(4)?;
+
+        let version = bytes[0];
+        let ttl = bytes[1];
+        let checksum = bytes[2..4].to_u16();
+        bytes.shift_first(4)?;
+
+        let mut tlvs = Vec::new();
+        while !bytes.is_empty() {
+            bytes.require(4)?;
+            let tlv_type = bytes[0..2].to_u16();
+            let length = bytes[2..4].to_u16() as usize;
+            if length < 4 {
+                return Err(anyhow!("CDP TLV {tlv_type} has a length of {length}"));
+            }
+            bytes.require(length)?;
+
+            tlvs.push(CdpTlv::from_value(tlv_type, &bytes[4..length])?);
+            bytes.shift_first(length)?;
+        }
+
+        Ok(
+            Self {
+                version,
+                ttl,
+                checksum,
+                tlvs,
+            }
+        )
+    }
+
+    pub fn device_id(&self) -> Option<&str> {
+        self.tlvs.iter().find_map(|tlv| match tlv {
+            CdpTlv::DeviceId(device_id) => Some(device_id.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn port_id(&self) -> Option<&str> {
+        self.tlvs.iter().find_map(|tlv| match tlv {
+            CdpTlv::PortId(port_id) => Some(port_id.as_str()),
+            _ => None,
+        })
+    }
+}
+
+
+pub struct CdpDissector;
+
+impl Dissector for CdpDissector {
+    fn name(&self) -> &str {
+        "cdp"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::DataLinkLayer(DataLinkLayer::CDP(CDP::from_bytes(bytes)?)))
+    }
+}
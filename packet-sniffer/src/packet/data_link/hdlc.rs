@@ -0,0 +1,237 @@
+use anyhow::{anyhow, Result};
+use byte_slice::{Bytes, Ipv4addr, SliceToUnsigned};
+
+use crate::packet::{dissector::{self, Context, Dissector, Key}, Layer, LayerTrait};
+
+use super::DataLinkLayer;
+
+
+// ISO 13239 hdlc frames with a one byte address and a modulo 8 control field, and cisco's hdlc which
+// puts an ethertype where the information field starts. Captures do not include the flags or the fcs.
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SupervisoryFunction {
+    ReceiveReady,
+    ReceiveNotReady,
+    Reject,
+    SelectiveReject,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    // numbered information transfer
+    Information { send_sequence: u8, receive_sequence: u8, poll_final: bool },
+    Supervisory { function: SupervisoryFunction, receive_sequence: u8, poll_final: bool },
+    // link management, the function is the control byte without the poll/final bit
+    Unnumbered { function: u8, poll_final: bool },
+}
+
+impl Control {
+    pub fn from_byte(control: u8) -> Self {
+        let poll_final = control & 0x10 != 0;
+        let receive_sequence = control >> 5;
+
+        match control & 0b11 {
+            0b01 => Control::Supervisory {
+                function: match (control >> 2) & 0b11 {
+                    0 => SupervisoryFunction::ReceiveReady,
+                    1 => SupervisoryFunction::ReceiveNotReady,
+                    2 => SupervisoryFunction::Reject,
+                    _ => SupervisoryFunction::SelectiveReject,
+                },
+                receive_sequence,
+                poll_final,
+            },
+            0b11 => Control::Unnumbered { function: control & !0x10, poll_final },
+            _ => Control::Information { send_sequence: (control >> 1) & 0b111, receive_sequence, poll_final },
+        }
+    }
+
+    // the commands and responses of unnumbered frames
+    pub fn unnumbered_name(&self) -> Option<&'static str> {
+        let Control::Unnumbered { function, .. } = self else {
+            return None;
+        };
+
+        Some(
+            match function {
+                0x03 => "UI",
+                0x07 => "SIM/RIM",
+                0x0f => "DM",
+                0x13 => "UP",
+                0x2f => "SABM",
+                0x43 => "DISC",
+                0x63 => "UA",
+                0x6f => "SABME",
+                0x83 => "SNRM",
+                0x87 => "FRMR",
+                0xaf => "XID",
+                0xcf => "SNRME",
+                0xe3 => "TEST",
+                _ => "unknown",
+            }
+        )
+    }
+
+    // information and unnumbered information frames carry data
+    pub fn has_information(&self) -> bool {
+        matches!(self, Control::Information { .. } | Control::Unnumbered { function: 0x03, .. })
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct HDLC<'a> {
+    pub address: u8,
+    pub control: Control,
+
+    // the information field, undecoded
+    pub next_layer: Box<Layer<'a>>,
+}
+
+impl<'a> HDLC<'a> {
+    pub fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(2)?;
+
+        let address = bytes[0];
+        let control = Control::from_byte(bytes[1]);
+        bytes.shift_first(2)?;
+
+        let next_layer = Box::new(
+            match bytes.is_empty() {
+                true => Layer::NoLayer,
+                false => Layer::Data(*bytes),
+            }
+        );
+
+        Ok(
+            Self {
+                address,
+                control,
+                next_layer,
+            }
+        )
+    }
+}
+
+impl<'a> LayerTrait for HDLC<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+
+
+pub const CISCO_UNICAST: u8 = 0x0f;
+pub const CISCO_BROADCAST: u8 = 0x8f;
+pub const PROTOCOL_SLARP: u16 = 0x8035;
+pub const PROTOCOL_CDP: u16 = 0x2000;
+
+#[derive(Debug, Default, Clone)]
+pub struct CiscoHDLC<'a> {
+    pub address: u8,
+    pub control: u8,
+    // an ethertype, apart from slarp and cdp
+    pub protocol: u16,
+
+    pub next_layer: Box<Layer<'a>>,
+}
+
+impl<'a> CiscoHDLC<'a> {
+    pub fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(4)?;
+
+        let address = bytes[0];
+        let control = bytes[1];
+        let protocol = bytes[2..4].to_u16();
+        bytes.shift_first(4)?;
+
+        // 0x8035 is rarp on ethernet, so slarp is decoded here instead of through the ethertype table
+        let next_layer = Box::new(
+            match protocol {
+                _ if bytes.is_empty() => Layer::NoLayer,
+                PROTOCOL_SLARP => Layer::DataLinkLayer(DataLinkLayer::SLARP(SLARP::from_bytes(bytes)?)),
+                PROTOCOL_CDP => dissector::dispatch(Key::Snap(0x00000c, PROTOCOL_CDP), bytes)?,
+                protocol => dissector::dispatch(Key::Ethertype(protocol), bytes)?,
+            }
+        );
+
+        Ok(
+            Self {
+                address,
+                control,
+                protocol,
+                next_layer,
+            }
+        )
+    }
+
+    // cisco frames always use these addresses and a control of 0
+    pub fn is_cisco(bytes: &Bytes) -> bool {
+        bytes.len() >= 4 && matches!(bytes[0], CISCO_UNICAST | CISCO_BROADCAST) && bytes[1] == 0
+    }
+}
+
+impl<'a> LayerTrait for CiscoHDLC<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+
+// serial line address resolution, cisco's keepalives and address discovery on hdlc links
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SLARP {
+    Request { address: Ipv4addr, mask: Ipv4addr },
+    Reply { address: Ipv4addr, mask: Ipv4addr },
+    Keepalive { my_sequence: u32, your_sequence: u32, reliability: u16 },
+}
+
+impl SLARP {
+    pub fn from_bytes(bytes: &mut Bytes) -> Result<Self> {
+        bytes.require(14)?;
+
+        let slarp = match bytes[0..4].to_u32() {
+            0 => SLARP::Request { address: Ipv4addr(bytes[4..8].to_u32()), mask: Ipv4addr(bytes[8..12].to_u32()) },
+            1 => SLARP::Reply { address: Ipv4addr(bytes[4..8].to_u32()), mask: Ipv4addr(bytes[8..12].to_u32()) },
+            2 => SLARP::Keepalive { my_sequence: bytes[4..8].to_u32(), your_sequence: bytes[8..12].to_u32(), reliability: bytes[12..14].to_u16() },
+            opcode => return Err(anyhow!("Unknown SLARP opcode {opcode}")),
+        };
+        // keepalives may carry a timestamp after the 14 bytes every opcode has
+        bytes.shift_first(bytes.len())?;
+
+        Ok(slarp)
+    }
+}
+
+
+
+pub struct HdlcDissector;
+
+impl Dissector for HdlcDissector {
+    fn name(&self) -> &str {
+        "hdlc"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::DataLinkLayer(DataLinkLayer::HDLC(HDLC::from_bytes(bytes)?)))
+    }
+}
+
+// captures of LINKTYPE_C_HDLC, frames that do not look like cisco's are decoded as plain hdlc
+pub struct CiscoHdlcDissector;
+
+impl Dissector for CiscoHdlcDissector {
+    fn name(&self) -> &str {
+        "chdlc"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, context: &Context) -> Result<Layer<'a>> {
+        match CiscoHDLC::is_cisco(bytes) {
+            true => Ok(Layer::DataLinkLayer(DataLinkLayer::CiscoHDLC(CiscoHDLC::from_bytes(bytes)?))),
+            false => HdlcDissector.dissect(bytes, context),
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use std::fmt::Display;
+
+use anyhow::{anyhow, Result};
+use byte_slice::{Bytes, Ipv4addr, Ipv6addr, MacAddress, SliceToUnsigned};
+
+use crate::packet::{dissector::{Context, Dissector}, Layer};
+
+use super::DataLinkLayer;
+
+
+// IEEE 802.1AB link layer discovery protocol, ethertype 0x88cc
+// every tlv starts with 7 bits of type and 9 bits of length
+
+
+
+// ieee's organizationally unique identifiers of the 802.1 and 802.3 extensions
+pub const OUI_802_1: u32 = 0x0080c2;
+pub const OUI_802_3: u32 = 0x00120f;
+
+
+// addresses as lldp and cdp advertise them
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetworkAddress {
+    Ipv4(Ipv4addr),
+    Ipv6(Ipv6addr),
+    Mac(MacAddress),
+    // address family number and the address
+    Other(u8, Vec<u8>),
+}
+
+impl NetworkAddress {
+    // https://www.iana.org/assignments/address-family-numbers/address-family-numbers.xhtml
+    pub fn from_family(family: u8, address: &[u8]) -> Self {
+        match (family, address.len()) {
+            (1, 4) => NetworkAddress::Ipv4(Ipv4addr(address.to_u32())),
+            (2, 16) => NetworkAddress::Ipv6(Ipv6addr(address.to_u128())),
+            (6, 6) => NetworkAddress::Mac(MacAddress::from(address.to_u64())),
+            _ => NetworkAddress::Other(family, address.to_vec()),
+        }
+    }
+}
+
+impl Display for NetworkAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkAddress::Ipv4(address) => write!(f, "{address}"),
+            NetworkAddress::Ipv6(address) => write!(f, "{address}"),
+            NetworkAddress::Mac(address) => write!(f, "{address}"),
+            NetworkAddress::Other(family, address) => write!(f, "{family}:{}", address.iter().map(|byte| format!("{byte:02x}")).collect::<String>()),
+        }
+    }
+}
+
+
+// chassis and port ids, which subtypes hold what is the only difference between the two
+#[derive(Debug, Clone, PartialEq)]
+pub enum Identifier {
+    Mac(MacAddress),
+    Address(NetworkAddress),
+    Text(String),
+}
+
+impl Identifier {
+    fn from_bytes(bytes: &[u8], mac_subtype: u8, address_subtype: u8) -> Result<(u8, Self)> {
+        let (&subtype, id) = bytes.split_first().ok_or(anyhow!("LLDP id is missing its subtype"))?;
+
+        let id = match subtype {
+            _ if subtype == mac_subtype && id.len() == 6 => Identifier::Mac(MacAddress::from(id.to_u64())),
+            _ if subtype == address_subtype && !id.is_empty() => Identifier::Address(NetworkAddress::from_family(id[0], &id[1..])),
+            _ => Identifier::Text(String::from_utf8_lossy(id).into_owned()),
+        };
+
+        Ok((subtype, id))
+    }
+}
+
+impl Display for Identifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Identifier::Mac(address) => write!(f, "{address}"),
+            Identifier::Address(address) => write!(f, "{address}"),
+            Identifier::Text(text) => write!(f, "{text}"),
+        }
+    }
+}
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Capabilities(pub u16);
+
+impl Capabilities {
+    const NAMES: [&str; 11] = ["other", "repeater", "bridge", "wlan-ap", "router", "telephone", "docsis", "station", "c-vlan", "s-vlan", "tpmr"];
+
+    pub fn names(&self) -> Vec<&'static str> {
+        Self::NAMES
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| (self.0 >> bit) & 1 == 1)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dot1Tlv {
+    PortVlanId(u16),
+    ProtocolVlanId { flags: u8, vlan_id: u16 },
+    VlanName { vlan_id: u16, name: String },
+    ProtocolIdentity(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dot3Tlv {
+    MacPhy { autonegotiation: u8, advertised_capabilities: u16, mau_type: u16 },
+    PowerViaMdi { support: u8, power_pair: u8, power_class: u8 },
+    LinkAggregation { status: u8, port_id: u32 },
+    MaxFrameSize(u16),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tlv {
+    End,
+    ChassisId { subtype: u8, id: Identifier },
+    PortId { subtype: u8, id: Identifier },
+    // seconds the information stays valid, 0 withdraws it
+    Ttl(u16),
+    PortDescription(String),
+    SystemName(String),
+    SystemDescription(String),
+    Capabilities { system: Capabilities, enabled: Capabilities },
+    ManagementAddress { address: NetworkAddress, interface_subtype: u8, interface: u32, oid: Vec<u8> },
+    Ieee802_1(Dot1Tlv),
+    Ieee802_3(Dot3Tlv),
+    // organization specific tlvs this crate does not decode
+    Organization { oui: u32, subtype: u8, info: Vec<u8> },
+    Unknown { tlv_type: u8, value: Vec<u8> },
+}
+
+impl Tlv {
+    fn from_value(tlv_type: u8, value: &[u8]) -> Result<Self> {
+        let text = || String::from_utf8_lossy(value).into_owned();
+        let require = |length: usize| match value.len() >= length {
+            true => Ok(()),
+            false => Err(anyhow!("LLDP TLV {tlv_type} is shorter than {length} bytes")),
+        };
+
+        Ok(
+            match tlv_type {
+                0 => Tlv::End,
+                1 => {
+                    let (subtype, id) = Identifier::from_bytes(value, 4, 5)?;
+                    Tlv::ChassisId { subtype, id }
+                },
+                2 => {
+                    let (subtype, id) = Identifier::from_bytes(value, 3, 4)?;
+                    Tlv::PortId { subtype, id }
+                },
+                3 => {
+                    require(2)?;
+                    Tlv::Ttl(value[0..2].to_u16())
+                },
+                4 => Tlv::PortDescription(text()),
+                5 => Tlv::SystemName(text()),
+                6 => Tlv::SystemDescription(text()),
+                7 => {
+                    require(4)?;
+                    Tlv::Capabilities { system: Capabilities(value[0..2].to_u16()), enabled: Capabilities(value[2..4].to_u16()) }
+                },
+                8 => {
+                    // the address length counts the family byte
+                    require(1)?;
+                    let address_length = value[0] as usize;
+                    require(1 + address_length + 6)?;
+                    let address = match address_length {
+                        0 => return Err(anyhow!("LLDP management address is missing its family")),
+                        _ => NetworkAddress::from_family(value[1], &value[2..1 + address_length]),
+                    };
+                    let interface = &value[1 + address_length..];
+                    let oid_length = interface[5] as usize;
+                    require(1 + address_length + 6 + oid_length)?;
+
+                    Tlv::ManagementAddress {
+                        address,
+                        interface_subtype: interface[0],
+                        interface: interface[1..5].to_u32(),
+                        oid: interface[6..6 + oid_length].to_vec(),
+                    }
+                },
+                127 => {
+                    require(4)?;
+                    Self::organization(value[0..3].to_u32(), value[3], &value[4..])
+                },
+                tlv_type => Tlv::Unknown { tlv_type, value: value.to_vec() },
+            }
+        )
+    }
+
+    // extensions that are too short for their subtype are kept undecoded
+    fn organization(oui: u32, subtype: u8, info: &[u8]) -> Self {
+        match (oui, subtype, info.len()) {
+            (OUI_802_1, 1, 2..) => Tlv::Ieee802_1(Dot1Tlv::PortVlanId(info[0..2].to_u16())),
+            (OUI_802_1, 2, 3..) => Tlv::Ieee802_1(Dot1Tlv::ProtocolVlanId { flags: info[0], vlan_id: info[1..3].to_u16() }),
+            (OUI_802_1, 3, 3..) if info.len() >= 3 + info[2] as usize => Tlv::Ieee802_1(Dot1Tlv::VlanName {
+                vlan_id: info[0..2].to_u16(),
+                name: String::from_utf8_lossy(&info[3..3 + info[2] as usize]).into_owned(),
+            }),
+            (OUI_802_1, 4, 1..) if info.len() > info[0] as usize => Tlv::Ieee802_1(Dot1Tlv::ProtocolIdentity(info[1..1 + info[0] as usize].to_vec())),
+            (OUI_802_3, 1, 5..) => Tlv::Ieee802_3(Dot3Tlv::MacPhy {
+                autonegotiation: info[0],
+                advertised_capabilities: info[1..3].to_u16(),
+                mau_type: info[3..5].to_u16(),
+            }),
+            (OUI_802_3, 2, 3..) => Tlv::Ieee802_3(Dot3Tlv::PowerViaMdi { support: info[0], power_pair: info[1], power_class: info[2] }),
+            (OUI_802_3, 3, 5..) => Tlv::Ieee802_3(Dot3Tlv::LinkAggregation { status: info[0], port_id: info[1..5].to_u32() }),
+            (OUI_802_3, 4, 2..) => Tlv::Ieee802_3(Dot3Tlv::MaxFrameSize(info[0..2].to_u16())),
+            _ => Tlv::Organization { oui, subtype, info: info.to_vec() },
+        }
+    }
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LLDP {
+    // in the order they were sent, without the end tlv
+    pub tlvs: Vec<Tlv>,
+}
+
+impl LLDP {
+    pub fn from_bytes(bytes: &mut Bytes) -> Result<Self> {
+        let mut tlvs = Vec::new();
+
+        // the end tlv is optional when the frame ends with the last tlv
+        while !bytes.is_empty() {
+            bytes.require(2)?;
+            let header = bytes[0..2].to_u16();
+            let (tlv_type, length) = ((header >> 9) as u8, (header & 0x01ff) as usize);
+            bytes.require(2 + length)?;
+
+            let tlv = Tlv::from_value(tlv_type, &bytes[2..2 + length])?;
+            bytes.shift_first(2 + length)?;
+
+            match tlv {
+                Tlv::End => break,
+                tlv => tlvs.push(tlv),
+            }
+        }
+
+        match tlvs.as_slice() {
+            [Tlv::ChassisId { .. }, Tlv::PortId { .. }, Tlv::Ttl(_), ..] => Ok(Self { tlvs }),
+            _ => Err(anyhow!("LLDP has to start with the chassis id, port id and ttl")),
+        }
+    }
+
+    pub fn chassis_id(&self) -> Option<&Identifier> {
+        self.tlvs.iter().find_map(|tlv| match tlv {
+            Tlv::ChassisId { id, .. } => Some(id),
+            _ => None,
+        })
+    }
+
+    pub fn port_id(&self) -> Option<&Identifier> {
+        self.tlvs.iter().find_map(|tlv| match tlv {
+            Tlv::PortId { id, .. } => Some(id),
+            _ => None,
+        })
+    }
+
+    pub fn ttl(&self) -> Option<u16> {
+        self.tlvs.iter().find_map(|tlv| match tlv {
+            Tlv::Ttl(ttl) => Some(*ttl),
+            _ => None,
+        })
+    }
+
+    pub fn system_name(&self) -> Option<&str> {
+        self.tlvs.iter().find_map(|tlv| match tlv {
+            Tlv::SystemName(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+}
+
+
+pub struct LldpDissector;
+
+impl Dissector for LldpDissector {
+    fn name(&self) -> &str {
+        "lldp"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::DataLinkLayer(DataLinkLayer::LLDP(LLDP::from_bytes(bytes)?)))
+    }
+}
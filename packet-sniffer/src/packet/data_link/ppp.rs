@@ -0,0 +1,465 @@
+use anyhow::{anyhow, Result};
+use byte_slice::{Bytes, Ipv4addr, SliceToUnsigned};
+
+use crate::packet::{dissector::{self, Context, Dissector, Key}, Layer, LayerTrait};
+
+use super::DataLinkLayer;
+
+
+// RFC 1661 point to point protocol, RFC 2516 ppp over ethernet
+// the protocol field selects the next layer through Key::PppProtocol
+
+
+
+// https://www.iana.org/assignments/ppp-numbers/ppp-numbers.xhtml
+pub mod protocol {
+    pub const IPV4: u16 = 0x0021;
+    pub const IPV6: u16 = 0x0057;
+    pub const MPLS: u16 = 0x0281;
+    pub const IPCP: u16 = 0x8021;
+    pub const IPV6CP: u16 = 0x8057;
+    pub const LCP: u16 = 0xc021;
+    pub const PAP: u16 = 0xc023;
+    pub const CHAP: u16 = 0xc223;
+}
+
+
+#[derive(Debug, Default, Clone)]
+pub struct PPP<'a> {
+    // the 0xff 0x03 of hdlc-like framing, absent with address and control field compression and on pppoe
+    pub address: Option<u8>,
+    pub control: Option<u8>,
+    pub protocol: u16,
+
+    pub next_layer: Box<Layer<'a>>,
+}
+
+impl<'a> PPP<'a> {
+    pub fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(1)?;
+
+        let (address, control) = match bytes.len() >= 2 && bytes[0..2] == [0xff, 0x03] {
+            true => {
+                bytes.shift_first(2)?;
+                (Some(0xff), Some(0x03))
+            },
+            false => (None, None),
+        };
+
+        // protocol field compression sends protocols below 0x0100 as a single odd byte
+        bytes.require(1)?;
+        let protocol_length = match bytes[0] & 1 {
+            1 => 1,
+            _ => 2,
+        };
+        bytes.require(protocol_length)?;
+        let protocol = bytes[0..protocol_length].to_u16();
+        bytes.shift_first(protocol_length)?;
+
+        let next_layer = Box::new(
+            match bytes.is_empty() {
+                true => Layer::NoLayer,
+                false => dissector::dispatch(Key::PppProtocol(protocol), bytes)?,
+            }
+        );
+
+        Ok(
+            Self {
+                address,
+                control,
+                protocol,
+                next_layer,
+            }
+        )
+    }
+}
+
+impl<'a> LayerTrait for PPP<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+
+
+pub mod code {
+    pub const CONFIGURE_REQUEST: u8 = 1;
+    pub const CONFIGURE_ACK: u8 = 2;
+    pub const CONFIGURE_NAK: u8 = 3;
+    pub const CONFIGURE_REJECT: u8 = 4;
+    pub const TERMINATE_REQUEST: u8 = 5;
+    pub const TERMINATE_ACK: u8 = 6;
+    pub const CODE_REJECT: u8 = 7;
+    pub const PROTOCOL_REJECT: u8 = 8;
+    pub const ECHO_REQUEST: u8 = 9;
+    pub const ECHO_REPLY: u8 = 10;
+    pub const DISCARD_REQUEST: u8 = 11;
+}
+
+
+// configuration options of lcp, ipcp and ipv6cp, the same type means something else in each of them
+#[derive(Debug, Clone, PartialEq)]
+pub enum PppOption {
+    MaximumReceiveUnit(u16),
+    AuthenticationProtocol { protocol: u16, data: Vec<u8> },
+    MagicNumber(u32),
+    ProtocolFieldCompression,
+    AddressControlFieldCompression,
+    IpAddress(Ipv4addr),
+    PrimaryDns(Ipv4addr),
+    SecondaryDns(Ipv4addr),
+    InterfaceIdentifier(u64),
+    Unknown { option_type: u8, data: Vec<u8> },
+}
+
+impl PppOption {
+    fn from_value(protocol: u16, option_type: u8, data: &[u8]) -> Self {
+        match (protocol, option_type, data.len()) {
+            (protocol::LCP, 1, 2) => PppOption::MaximumReceiveUnit(data.to_u16()),
+            (protocol::LCP, 3, 2..) => PppOption::AuthenticationProtocol { protocol: data[0..2].to_u16(), data: data[2..].to_vec() },
+            (protocol::LCP, 5, 4) => PppOption::MagicNumber(data.to_u32()),
+            (protocol::LCP, 7, 0) => PppOption::ProtocolFieldCompression,
+            (protocol::LCP, 8, 0) => PppOption::AddressControlFieldCompression,
+            (protocol::IPCP, 3, 4) => PppOption::IpAddress(Ipv4addr(data.to_u32())),
+            (protocol::IPCP, 129, 4) => PppOption::PrimaryDns(Ipv4addr(data.to_u32())),
+            (protocol::IPCP, 131, 4) => PppOption::SecondaryDns(Ipv4addr(data.to_u32())),
+            (protocol::IPV6CP, 1, 8) => PppOption::InterfaceIdentifier(data.to_u64()),
+            _ => PppOption::Unknown { option_type, data: data.to_vec() },
+        }
+    }
+
+    fn list(protocol: u16, mut data: &[u8]) -> Result<Vec<Self>> {
+        let mut options = Vec::new();
+
+        while !data.is_empty() {
+            let length = *data.get(1).ok_or(anyhow!("PPP option is missing its length"))? as usize;
+            if !(2..=data.len()).contains(&length) {
+                return Err(anyhow!("PPP option {} has a length of {length}", data[0]));
+            }
+            options.push(Self::from_value(protocol, data[0], &data[2..length]));
+            data = &data[length..];
+        }
+
+        Ok(options)
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlData {
+    // configure request, ack, nak and reject
+    Options(Vec<PppOption>),
+    // echo request, echo reply and discard request
+    Echo { magic_number: u32, data: Vec<u8> },
+    ProtocolReject { protocol: u16, packet: Vec<u8> },
+    // terminate request and ack, code reject
+    Data(Vec<u8>),
+}
+
+// a packet of lcp or one of the network control protocols
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlPacket {
+    pub code: u8,
+    pub identifier: u8,
+    pub length: u16,
+    pub data: ControlData,
+}
+
+impl ControlPacket {
+    fn from_bytes(protocol: u16, bytes: &mut Bytes) -> Result<Self> {
+        let (code, identifier, length, data) = packet_header(bytes)?;
+
+        let data = match code {
+            code::CONFIGURE_REQUEST..=code::CONFIGURE_REJECT => ControlData::Options(PppOption::list(protocol, data)?),
+            code::ECHO_REQUEST..=code::DISCARD_REQUEST if protocol == protocol::LCP && data.len() >= 4 => {
+                ControlData::Echo { magic_number: data[0..4].to_u32(), data: data[4..].to_vec() }
+            },
+            code::PROTOCOL_REJECT if protocol == protocol::LCP && data.len() >= 2 => {
+                ControlData::ProtocolReject { protocol: data[0..2].to_u16(), packet: data[2..].to_vec() }
+            },
+            _ => ControlData::Data(data.to_vec()),
+        };
+        bytes.shift_first(length as usize)?;
+
+        Ok(
+            Self {
+                code,
+                identifier,
+                length,
+                data,
+            }
+        )
+    }
+}
+
+
+// RFC 1334 password authentication, the password is sent in the clear
+#[derive(Debug, Clone, PartialEq)]
+pub enum PAP {
+    Request { identifier: u8, peer_id: String, password: String },
+    Ack { identifier: u8, message: String },
+    Nak { identifier: u8, message: String },
+}
+
+impl PAP {
+    fn from_bytes(bytes: &mut Bytes) -> Result<Self> {
+        let (code, identifier, length, data) = packet_header(bytes)?;
+
+        let pap = match code {
+            1 => {
+                let (peer_id, rest) = length_prefixed(data)?;
+                let (password, _) = length_prefixed(rest)?;
+                PAP::Request { identifier, peer_id: text(peer_id), password: text(password) }
+            },
+            2 => PAP::Ack { identifier, message: text(length_prefixed(data)?.0) },
+            3 => PAP::Nak { identifier, message: text(length_prefixed(data)?.0) },
+            code => return Err(anyhow!("Unknown PAP code {code}")),
+        };
+        bytes.shift_first(length as usize)?;
+
+        Ok(pap)
+    }
+}
+
+
+// RFC 1994 challenge handshake authentication
+#[derive(Debug, Clone, PartialEq)]
+pub enum CHAP {
+    Challenge { identifier: u8, value: Vec<u8>, name: String },
+    Response { identifier: u8, value: Vec<u8>, name: String },
+    Success { identifier: u8, message: String },
+    Failure { identifier: u8, message: String },
+}
+
+impl CHAP {
+    fn from_bytes(bytes: &mut Bytes) -> Result<Self> {
+        let (code, identifier, length, data) = packet_header(bytes)?;
+
+        let chap = match code {
+            1 | 2 => {
+                let (value, name) = length_prefixed(data)?;
+                let (value, name) = (value.to_vec(), text(name));
+                match code {
+                    1 => CHAP::Challenge { identifier, value, name },
+                    _ => CHAP::Response { identifier, value, name },
+                }
+            },
+            3 => CHAP::Success { identifier, message: text(data) },
+            4 => CHAP::Failure { identifier, message: text(data) },
+            code => return Err(anyhow!("Unknown CHAP code {code}")),
+        };
+        bytes.shift_first(length as usize)?;
+
+        Ok(chap)
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PppControl {
+    LCP(ControlPacket),
+    IPCP(ControlPacket),
+    IPV6CP(ControlPacket),
+    PAP(PAP),
+    CHAP(CHAP),
+}
+
+
+// code, identifier and length shared by every control and authentication protocol, the data is cut to the length
+fn packet_header<'b>(bytes: &'b Bytes) -> Result<(u8, u8, u16, &'b [u8])> {
+    bytes.require(4)?;
+
+    let length = bytes[2..4].to_u16();
+    if length < 4 {
+        return Err(anyhow!("PPP packet length {length} is shorter than its header"));
+    }
+    bytes.require(length as usize)?;
+
+    Ok((bytes[0], bytes[1], length, &bytes[4..length as usize]))
+}
+
+// a byte of length followed by that many bytes, and what comes after them
+fn length_prefixed(data: &[u8]) -> Result<(&[u8], &[u8])> {
+    let (&length, rest) = data.split_first().ok_or(anyhow!("PPP field is missing its length"))?;
+    match rest.len() >= length as usize {
+        true => Ok(rest.split_at(length as usize)),
+        false => Err(anyhow!("PPP field of {length} bytes is truncated")),
+    }
+}
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+
+
+pub mod pppoe_code {
+    pub const SESSION: u8 = 0x00;
+    pub const PADO: u8 = 0x07;
+    pub const PADI: u8 = 0x09;
+    pub const PADR: u8 = 0x19;
+    pub const PADS: u8 = 0x65;
+    pub const PADT: u8 = 0xa7;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PppoeTag {
+    EndOfList,
+    // empty means any service
+    ServiceName(String),
+    AcName(String),
+    HostUniq(Vec<u8>),
+    AcCookie(Vec<u8>),
+    VendorSpecific { vendor_id: u32, data: Vec<u8> },
+    RelaySessionId(Vec<u8>),
+    ServiceNameError(String),
+    AcSystemError(String),
+    GenericError(String),
+    Unknown { tag_type: u16, value: Vec<u8> },
+}
+
+impl PppoeTag {
+    fn from_value(tag_type: u16, value: &[u8]) -> Self {
+        match tag_type {
+            0x0000 => PppoeTag::EndOfList,
+            0x0101 => PppoeTag::ServiceName(text(value)),
+            0x0102 => PppoeTag::AcName(text(value)),
+            0x0103 => PppoeTag::HostUniq(value.to_vec()),
+            0x0104 => PppoeTag::AcCookie(value.to_vec()),
+            0x0105 if value.len() >= 4 => PppoeTag::VendorSpecific { vendor_id: value[0..4].to_u32(), data: value[4..].to_vec() },
+            0x0110 => PppoeTag::RelaySessionId(value.to_vec()),
+            0x0201 => PppoeTag::ServiceNameError(text(value)),
+            0x0202 => PppoeTag::AcSystemError(text(value)),
+            0x0203 => PppoeTag::GenericError(text(value)),
+            tag_type => PppoeTag::Unknown { tag_type, value: value.to_vec() },
+        }
+    }
+}
+
+
+// discovery on ethertype 0x8863 carries tags, the session stage on 0x8864 carries ppp
+#[derive(Debug, Default, Clone)]
+pub struct PPPoE<'a> {
+    pub version: u8,
+    pub pppoe_type: u8,
+    pub code: u8,
+    pub session_id: u16,
+    pub length: u16,
+    pub tags: Vec<PppoeTag>,
+
+    pub next_layer: Box<Layer<'a>>,
+}
+
+impl<'a> PPPoE<'a> {
+    pub fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(6)?;
+
+        let version = bytes[0] >> 4;
+        let pppoe_type = bytes[0] & 0x0f;
+        let code = bytes[1];
+        let session_id = bytes[2..4].to_u16();
+        let length = bytes[4..6].to_u16();
+        bytes.shift_first(6)?;
+
+        // ethernet pads short frames, the length says where the payload ends
+        bytes.require(length as usize)?;
+        if (length as usize) < bytes.len() {
+            bytes.shift_last(bytes.len() - length as usize)?;
+        }
+
+        let (tags, next_layer) = match code {
+            pppoe_code::SESSION => (Vec::new(), PPP::from_bytes(bytes).map(|ppp| Layer::DataLinkLayer(DataLinkLayer::PPP(ppp)))?),
+            _ => (Self::tags(bytes)?, Layer::NoLayer),
+        };
+
+        Ok(
+            Self {
+                version,
+                pppoe_type,
+                code,
+                session_id,
+                length,
+                tags,
+                next_layer: Box::new(next_layer),
+            }
+        )
+    }
+
+    fn tags(bytes: &mut Bytes) -> Result<Vec<PppoeTag>> {
+        let mut tags = Vec::new();
+
+        while !bytes.is_empty() {
+            bytes.require(4)?;
+            let tag_type = bytes[0..2].to_u16();
+            let length = bytes[2..4].to_u16() as usize;
+            bytes.require(4 + length)?;
+
+            let tag = PppoeTag::from_value(tag_type, &bytes[4..4 + length]);
+            bytes.shift_first(4 + length)?;
+            match tag {
+                PppoeTag::EndOfList => break,
+                tag => tags.push(tag),
+            }
+        }
+
+        Ok(tags)
+    }
+
+    pub fn is_discovery(&self) -> bool {
+        self.code != pppoe_code::SESSION
+    }
+}
+
+impl<'a> LayerTrait for PPPoE<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+
+
+pub struct PppDissector;
+
+impl Dissector for PppDissector {
+    fn name(&self) -> &str {
+        "ppp"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::DataLinkLayer(DataLinkLayer::PPP(PPP::from_bytes(bytes)?)))
+    }
+}
+
+pub struct PppoeDissector;
+
+impl Dissector for PppoeDissector {
+    fn name(&self) -> &str {
+        "pppoe"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::DataLinkLayer(DataLinkLayer::PPPoE(PPPoE::from_bytes(bytes)?)))
+    }
+}
+
+// lcp, ipcp, ipv6cp, pap and chap, told apart by the key they were dispatched with
+pub struct PppControlDissector;
+
+impl Dissector for PppControlDissector {
+    fn name(&self) -> &str {
+        "ppp-control"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, context: &Context) -> Result<Layer<'a>> {
+        let control = match context.key {
+            Some(Key::PppProtocol(protocol::LCP)) => PppControl::LCP(ControlPacket::from_bytes(protocol::LCP, bytes)?),
+            Some(Key::PppProtocol(protocol::IPCP)) => PppControl::IPCP(ControlPacket::from_bytes(protocol::IPCP, bytes)?),
+            Some(Key::PppProtocol(protocol::IPV6CP)) => PppControl::IPV6CP(ControlPacket::from_bytes(protocol::IPV6CP, bytes)?),
+            Some(Key::PppProtocol(protocol::PAP)) => PppControl::PAP(PAP::from_bytes(bytes)?),
+            Some(Key::PppProtocol(protocol::CHAP)) => PppControl::CHAP(CHAP::from_bytes(bytes)?),
+            key => return Err(anyhow!("PPP control dissector does not handle {key:?}")),
+        };
+
+        Ok(Layer::DataLinkLayer(DataLinkLayer::PppControl(control)))
+    }
+}
@@ -0,0 +1,309 @@
+use std::fmt::Debug;
+
+use anyhow::{anyhow, Result};
+use byte_slice::{Bytes, MacAddress, SliceToUnsigned};
+
+use crate::packet::{dissector::{Context, Dissector}, Layer};
+
+use super::DataLinkLayer;
+
+
+// Bridge protocol data units of 802.1D spanning tree, 802.1w rapid spanning tree and 802.1s multiple
+// spanning tree. They are sent to 01:80:c2:00:00:00 on llc sap 0x42, cisco's PVST+ uses snap instead.
+
+pub mod bpdu_type {
+    pub const CONFIGURATION: u8 = 0x00;
+    pub const RAPID: u8 = 0x02;
+    pub const TOPOLOGY_CHANGE_NOTIFICATION: u8 = 0x80;
+}
+
+pub mod version {
+    pub const STP: u8 = 0;
+    pub const RSTP: u8 = 2;
+    pub const MSTP: u8 = 3;
+}
+
+
+
+// priority in steps of 4096 and the vlan or msti it was sent for
+#[derive(Default, Clone, Copy, PartialEq)]
+pub struct BridgeId {
+    pub priority: u16,
+    pub system_id_extension: u16,
+    pub address: MacAddress,
+}
+
+impl BridgeId {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            priority: bytes[0..2].to_u16() & 0xf000,
+            system_id_extension: bytes[0..2].to_u16() & 0x0fff,
+            address: MacAddress::from(bytes[2..8].to_u64()),
+        }
+    }
+}
+
+impl Debug for BridgeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.priority, self.system_id_extension, self.address)
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PortRole {
+    // the role of plain stp bpdus, rstp never sends it
+    Unknown,
+    // alternate or backup
+    Alternate,
+    Root,
+    Designated,
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub struct BpduFlags(pub u8);
+
+impl BpduFlags {
+    pub fn topology_change(&self) -> bool {
+        self.0 & 0x01 != 0
+    }
+
+    pub fn proposal(&self) -> bool {
+        self.0 & 0x02 != 0
+    }
+
+    pub fn port_role(&self) -> PortRole {
+        match (self.0 >> 2) & 0b11 {
+            1 => PortRole::Alternate,
+            2 => PortRole::Root,
+            3 => PortRole::Designated,
+            _ => PortRole::Unknown,
+        }
+    }
+
+    pub fn learning(&self) -> bool {
+        self.0 & 0x10 != 0
+    }
+
+    pub fn forwarding(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+
+    pub fn agreement(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+
+    pub fn topology_change_ack(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+}
+
+impl Debug for BpduFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BpduFlags")
+            .field("TC", &self.topology_change())
+            .field("proposal", &self.proposal())
+            .field("role", &self.port_role())
+            .field("learning", &self.learning())
+            .field("forwarding", &self.forwarding())
+            .field("agreement", &self.agreement())
+            .field("TCA", &self.topology_change_ack())
+            .finish()
+    }
+}
+
+
+// the body of configuration and rapid bpdus, times are in 1/256 seconds
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ConfigBpdu {
+    pub flags: BpduFlags,
+    pub root_id: BridgeId,
+    pub root_path_cost: u32,
+    pub bridge_id: BridgeId,
+    pub port_id: u16,
+    pub message_age: u16,
+    pub max_age: u16,
+    pub hello_time: u16,
+    pub forward_delay: u16,
+}
+
+impl ConfigBpdu {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            flags: BpduFlags(bytes[0]),
+            root_id: BridgeId::from_bytes(&bytes[1..9]),
+            root_path_cost: bytes[9..13].to_u32(),
+            bridge_id: BridgeId::from_bytes(&bytes[13..21]),
+            port_id: bytes[21..23].to_u16(),
+            message_age: bytes[23..25].to_u16(),
+            max_age: bytes[25..27].to_u16(),
+            hello_time: bytes[27..29].to_u16(),
+            forward_delay: bytes[29..31].to_u16(),
+        }
+    }
+
+    pub fn port_priority(&self) -> u8 {
+        (self.port_id >> 8) as u8 & 0xf0
+    }
+
+    pub fn port_number(&self) -> u16 {
+        self.port_id & 0x0fff
+    }
+}
+
+
+// bridges only share an mst region when all of this matches
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MstConfigId {
+    pub format_selector: u8,
+    pub name: String,
+    pub revision: u16,
+    // hmac-md5 of the vlan to msti table
+    pub digest: [u8; 16],
+}
+
+// one spanning tree instance of an mst region
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Msti {
+    pub flags: BpduFlags,
+    // the system id extension is the msti id
+    pub regional_root_id: BridgeId,
+    pub internal_root_path_cost: u32,
+    pub bridge_priority: u8,
+    pub port_priority: u8,
+    pub remaining_hops: u8,
+}
+
+impl Msti {
+    const LENGTH: usize = 16;
+
+    pub fn id(&self) -> u16 {
+        self.regional_root_id.system_id_extension
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            flags: BpduFlags(bytes[0]),
+            regional_root_id: BridgeId::from_bytes(&bytes[1..9]),
+            internal_root_path_cost: bytes[9..13].to_u32(),
+            bridge_priority: bytes[13] & 0xf0,
+            port_priority: bytes[14] & 0xf0,
+            remaining_hops: bytes[15],
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MstExtension {
+    pub config_id: MstConfigId,
+    pub cist_internal_root_path_cost: u32,
+    pub cist_bridge_id: BridgeId,
+    pub cist_remaining_hops: u8,
+    pub mstis: Vec<Msti>,
+}
+
+impl MstExtension {
+    // everything before the msti records
+    const LENGTH: usize = 64;
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < Self::LENGTH || !(bytes.len() - Self::LENGTH).is_multiple_of(Msti::LENGTH) {
+            return Err(anyhow!("MST extension of {} bytes does not end on an MSTI record", bytes.len()));
+        }
+
+        let mut digest = [0; 16];
+        digest.copy_from_slice(&bytes[35..51]);
+
+        Ok(
+            Self {
+                config_id: MstConfigId {
+                    format_selector: bytes[0],
+                    name: String::from_utf8_lossy(&bytes[1..33]).trim_end_matches('\0').to_owned(),
+                    revision: bytes[33..35].to_u16(),
+                    digest,
+                },
+                cist_internal_root_path_cost: bytes[51..55].to_u32(),
+                cist_bridge_id: BridgeId::from_bytes(&bytes[55..63]),
+                cist_remaining_hops: bytes[63],
+                mstis: bytes[Self::LENGTH..].chunks_exact(Msti::LENGTH).map(Msti::from_bytes).collect(),
+            }
+        )
+    }
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BPDU {
+    pub protocol_id: u16,
+    pub version: u8,
+    pub bpdu_type: u8,
+    // None for topology change notifications
+    pub config: Option<ConfigBpdu>,
+    pub mst: Option<MstExtension>,
+}
+
+impl BPDU {
+    pub fn from_bytes(bytes: &mut Bytes) -> Result<Self> {
+        bytes.require(4)?;
+
+        let protocol_id = bytes[0..2].to_u16();
+        let version = bytes[2];
+        let bpdu_type = bytes[3];
+        if protocol_id != 0 {
+            return Err(anyhow!("BPDU protocol id {protocol_id} is not spanning tree"));
+        }
+
+        let (config, mst, length) = match bpdu_type {
+            bpdu_type::TOPOLOGY_CHANGE_NOTIFICATION => (None, None, 4),
+            bpdu_type::CONFIGURATION => {
+                bytes.require(35)?;
+                (Some(ConfigBpdu::from_bytes(&bytes[4..35])), None, 35)
+            },
+            // rapid bpdus carry the length of the empty version 1 part, mstp adds a version 3 part
+            bpdu_type::RAPID => {
+                bytes.require(36)?;
+                let config = Some(ConfigBpdu::from_bytes(&bytes[4..35]));
+
+                match version >= version::MSTP {
+                    true => {
+                        bytes.require(38)?;
+                        let length = 38 + bytes[36..38].to_u16() as usize;
+                        bytes.require(length)?;
+                        (config, Some(MstExtension::from_bytes(&bytes[38..length])?), length)
+                    },
+                    false => (config, None, 36),
+                }
+            },
+            _ => return Err(anyhow!("Unknown BPDU type 0x{bpdu_type:02x}")),
+        };
+
+        bytes.shift_first(length)?;
+
+        Ok(
+            Self {
+                protocol_id,
+                version,
+                bpdu_type,
+                config,
+                mst,
+            }
+        )
+    }
+
+    pub fn is_topology_change_notification(&self) -> bool {
+        self.bpdu_type == bpdu_type::TOPOLOGY_CHANGE_NOTIFICATION
+    }
+}
+
+
+pub struct StpDissector;
+
+impl Dissector for StpDissector {
+    fn name(&self) -> &str {
+        "stp"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::DataLinkLayer(DataLinkLayer::STP(BPDU::from_bytes(bytes)?)))
+    }
+}
@@ -0,0 +1,94 @@
+use std::{path::Path, str::FromStr};
+
+use anyhow::{anyhow, Result};
+
+use super::dissector::{self, Key};
+
+
+// Decode as rules force the dissector used for a key, one rule per line with # starting a comment:
+//
+// tcp.port==8081 -> http
+// udp.port==4789 -> vxlan
+// ethertype 0x9000 -> custom
+//
+// the fields are tcp.port, udp.port, ethertype (or eth.type), llc.dsap, ppp.protocol, ip.proto and linktype,
+// the value can be given with == or after a space, in decimal or 0x prefixed hex
+
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub key: Key,
+    // name of the dissector, see Dissector::name
+    pub dissector: String,
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (selector, dissector) = s.split_once("->").ok_or(anyhow!("Decode as rule {s} is missing -> <dissector>"))?;
+        let (field, value) = selector
+            .split_once("==")
+            .or_else(|| selector.trim().split_once(char::is_whitespace))
+            .ok_or(anyhow!("Decode as rule {s} has to compare a field to a value"))?;
+
+        let value = value.trim();
+        let number = match value.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => value.parse(),
+        }
+        .map_err(|_| anyhow!("{value} is not a number"))?;
+        let fits = |bits: u32| match number >> bits {
+            0 => Ok(number),
+            _ => Err(anyhow!("{value} is out of range for {}", field.trim())),
+        };
+
+        let key = match field.trim().to_lowercase().as_str() {
+            "tcp.port" => Key::TcpPort(fits(16)? as u16),
+            "udp.port" => Key::UdpPort(fits(16)? as u16),
+            "ethertype" | "eth.type" => Key::Ethertype(fits(16)? as u16),
+            "llc.dsap" => Key::LlcSap(fits(8)? as u8),
+            "ppp.protocol" => Key::PppProtocol(fits(16)? as u16),
+            "ip.proto" | "ip.protocol" => Key::IpProtocol(fits(8)? as u8),
+            "linktype" => Key::LinkType(fits(16)? as u16),
+            field => return Err(anyhow!("Unknown decode as field {field}")),
+        };
+
+        let dissector = dissector.trim().to_lowercase();
+        match dissector.is_empty() {
+            true => Err(anyhow!("Decode as rule {s} is missing a dissector")),
+            false => Ok(Self { key, dissector }),
+        }
+    }
+}
+
+
+pub fn parse_rules(text: &str) -> Result<Vec<Rule>> {
+    text.lines()
+        .enumerate()
+        .map(|(line, rule)| (line, rule.split('#').next().unwrap_or_default().trim()))
+        .filter(|(_, rule)| !rule.is_empty())
+        .map(|(line, rule)| rule.parse().map_err(|error| anyhow!("Line {}: {error}", line + 1)))
+        .collect()
+}
+
+pub fn load_file(path: impl AsRef<Path>) -> Result<Vec<Rule>> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path).map_err(|error| anyhow!("Failed to read {}: {error}", path.display()))?;
+
+    parse_rules(&text)
+}
+
+// adds the rules to the registry packets are dissected with, later rules for the same key win
+// returns the rules whose dissector is not registered (yet), they take effect once it is
+pub fn apply(rules: &[Rule]) -> Vec<&Rule> {
+    for rule in rules {
+        dissector::with_registry(|registry| registry.decode_as(rule.key, &rule.dissector));
+    }
+
+    rules
+        .iter()
+        .filter(|rule| dissector::by_name(&rule.dissector).is_none())
+        .collect()
+}
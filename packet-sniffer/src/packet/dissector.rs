@@ -0,0 +1,332 @@
+use std::{collections::HashMap, sync::{Arc, LazyLock, RwLock}};
+
+use byte_slice::Bytes;
+use anyhow::Result;
+
+use super::{application::{self, ApplicationLayer}, data_link::{self, ppp, DataLinkLayer}, network::{self, ipsec, ospf, tunnel, NetworkLayer}, transport::{self, TransportLayer}, Layer};
+
+
+// Dissectors turn the bytes of one protocol into a Layer and dispatch whatever they carry back through the
+// registry. Every protocol in this crate is registered at startup, other crates can add or replace entries:
+//
+// struct MyProtocol;
+// impl Dissector for MyProtocol {
+//     fn name(&self) -> &str { "myproto" }
+//     fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> { ... }
+// }
+// dissector::register(Key::UdpPort(9999), MyProtocol);
+
+
+
+// the tables dissectors are keyed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Table {
+    // https://www.tcpdump.org/linktypes.html
+    LinkType,
+    Ethertype,
+    // dsap of 802.2 llc headers
+    LlcSap,
+    // oui and protocol id of snap headers, snap with an oui of 0 is dispatched as an ethertype
+    Snap,
+    PppProtocol,
+    IpProtocol,
+    TcpPort,
+    UdpPort,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    LinkType(u16),
+    Ethertype(u16),
+    LlcSap(u8),
+    Snap(u32, u16),
+    PppProtocol(u16),
+    IpProtocol(u8),
+    TcpPort(u16),
+    UdpPort(u16),
+}
+
+impl Key {
+    pub fn table(&self) -> Table {
+        match self {
+            Key::LinkType(_) => Table::LinkType,
+            Key::Ethertype(_) => Table::Ethertype,
+            Key::LlcSap(_) => Table::LlcSap,
+            Key::Snap(..) => Table::Snap,
+            Key::PppProtocol(_) => Table::PppProtocol,
+            Key::IpProtocol(_) => Table::IpProtocol,
+            Key::TcpPort(_) => Table::TcpPort,
+            Key::UdpPort(_) => Table::UdpPort,
+        }
+    }
+}
+
+
+// what a dissector knows about how it was reached
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Context {
+    // the key that selected the dissector, None when a heuristic picked it
+    pub key: Option<Key>,
+    // set for payloads of tcp and udp
+    pub port_src: u16,
+    pub port_dst: u16,
+}
+
+
+pub trait Dissector: Send + Sync {
+    // the name rules and users refer to the dissector by, looked up without regard to case
+    fn name(&self) -> &str;
+
+    // heuristic dissectors are only handed payloads they accept here
+    fn probe(&self, _bytes: &Bytes, _context: &Context) -> bool {
+        true
+    }
+
+    // shifts `bytes` past everything the layer consumed
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, context: &Context) -> Result<Layer<'a>>;
+}
+
+
+
+#[derive(Default)]
+pub struct Registry {
+    keyed: HashMap<Key, Arc<dyn Dissector>>,
+    heuristics: Vec<(Table, Arc<dyn Dissector>)>,
+    named: HashMap<String, Arc<dyn Dissector>>,
+    // decode as rules, dissector names by key that win over the keyed table
+    decode_as: HashMap<Key, String>,
+}
+
+impl Registry {
+    // a registry with every dissector of this crate
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::default();
+
+        registry.register(Key::LinkType(crate::pcap::LINKTYPE_ETHERNET), data_link::EthernetDissector);
+        registry.register(Key::LinkType(crate::pcap::LINKTYPE_PPP), ppp::PppDissector);
+        registry.register(Key::LinkType(crate::pcap::LINKTYPE_PPP_HDLC), ppp::PppDissector);
+        registry.register(Key::LinkType(crate::pcap::LINKTYPE_PPP_ETHER), ppp::PppoeDissector);
+        registry.register(Key::LinkType(crate::pcap::LINKTYPE_C_HDLC), data_link::hdlc::CiscoHdlcDissector);
+        // plain hdlc has no link type of its own
+        registry.register_named(data_link::hdlc::HdlcDissector);
+
+        registry.register(Key::Ethertype(0x0800), network::Ipv4Dissector);
+        registry.register(Key::Ethertype(0x0806), network::ArpDissector);
+        registry.register(Key::Ethertype(0x86dd), network::Ipv6Dissector);
+        registry.register(Key::Ethertype(0x8863), ppp::PppoeDissector);
+        registry.register(Key::Ethertype(0x8864), ppp::PppoeDissector);
+        registry.register(Key::Ethertype(0x8847), network::MplsDissector);
+        registry.register(Key::Ethertype(0x8848), network::MplsDissector);
+        registry.register(Key::Ethertype(0x88cc), data_link::lldp::LldpDissector);
+        registry.register(Key::Ethertype(tunnel::ETHERTYPE_TEB), data_link::EthernetDissector);
+        registry.register(Key::Ethertype(tunnel::ETHERTYPE_ERSPAN_II), tunnel::ErspanDissector);
+        registry.register(Key::Ethertype(tunnel::ETHERTYPE_ERSPAN_III), tunnel::ErspanDissector);
+        // ppp over the enhanced gre of pptp
+        registry.register(Key::Ethertype(0x880b), ppp::PppDissector);
+
+        registry.register(Key::LlcSap(data_link::sap::STP), data_link::stp::StpDissector);
+        // cisco's per vlan spanning tree
+        registry.register(Key::Snap(0x00000c, 0x010b), data_link::stp::StpDissector);
+        registry.register(Key::Snap(0x00000c, 0x2000), data_link::cdp::CdpDissector);
+        registry.register(Key::LlcSap(data_link::sap::IPX), network::IpxDissector);
+        registry.register(Key::LlcSap(data_link::sap::NETBIOS), network::NetbiosDissector);
+
+        registry.register(Key::PppProtocol(ppp::protocol::IPV4), network::Ipv4Dissector);
+        registry.register(Key::PppProtocol(ppp::protocol::IPV6), network::Ipv6Dissector);
+        registry.register(Key::PppProtocol(ppp::protocol::MPLS), network::MplsDissector);
+        for protocol in [ppp::protocol::LCP, ppp::protocol::IPCP, ppp::protocol::IPV6CP, ppp::protocol::PAP, ppp::protocol::CHAP] {
+            registry.register(Key::PppProtocol(protocol), ppp::PppControlDissector);
+        }
+
+        registry.register(Key::IpProtocol(0x01), transport::IcmpDissector);
+        registry.register(Key::IpProtocol(0x02), transport::igmp::IgmpDissector);
+        registry.register(Key::IpProtocol(0x06), transport::TcpDissector);
+        registry.register(Key::IpProtocol(0x11), transport::UdpDissector);
+        registry.register(Key::IpProtocol(0x84), transport::sctp::SctpDissector);
+        // ip in ip, what esp and gre carry in tunnel mode
+        registry.register(Key::IpProtocol(0x04), network::Ipv4Dissector);
+        registry.register(Key::IpProtocol(0x29), network::Ipv6Dissector);
+        registry.register(Key::IpProtocol(0x2f), tunnel::GreDissector);
+        registry.register(Key::IpProtocol(0x32), ipsec::EspDissector);
+        registry.register(Key::IpProtocol(0x33), ipsec::AhDissector);
+        registry.register(Key::IpProtocol(0x59), ospf::OspfDissector);
+        // mpls in ip
+        registry.register(Key::IpProtocol(0x89), network::MplsDissector);
+
+        registry.register(Key::UdpPort(4789), tunnel::VxlanDissector);
+        registry.register(Key::UdpPort(6081), tunnel::GeneveDissector);
+
+        registry.register(Key::TcpPort(application::bgp::PORT), application::bgp::BgpDissector);
+        registry.register(Key::UdpPort(application::rip::PORT), application::rip::RipDissector);
+        registry.register(Key::UdpPort(application::rip::PORT_RIPNG), application::rip::RipngDissector);
+        for port in application::http::PORTS {
+            registry.register(Key::TcpPort(port), application::http::HttpDissector);
+        }
+        for port in application::tls::PORTS {
+            registry.register(Key::TcpPort(port), application::tls::TlsDissector);
+        }
+        // http and tls on any other port are recognized by their request and status lines and record headers
+        registry.register_heuristic(Table::TcpPort, application::http::HttpDissector);
+        registry.register_heuristic(Table::TcpPort, application::tls::TlsDissector);
+
+        // 5353 is mDNS, 5355 LLMNR which uses the same message format
+        for key in [Key::UdpPort(53), Key::UdpPort(5353), Key::UdpPort(5355), Key::TcpPort(53)] {
+            registry.register(key, application::dns::DnsDissector);
+        }
+
+        registry
+    }
+
+    pub fn register(&mut self, key: Key, dissector: impl Dissector + 'static) {
+        self.register_arc(key, Arc::new(dissector));
+    }
+
+    pub fn register_arc(&mut self, key: Key, dissector: Arc<dyn Dissector>) {
+        self.named.insert(dissector.name().to_lowercase(), dissector.clone());
+        self.keyed.insert(key, dissector);
+    }
+
+    // heuristics run in the order they were added when no key of the table matched
+    pub fn register_heuristic(&mut self, table: Table, dissector: impl Dissector + 'static) {
+        let dissector: Arc<dyn Dissector> = Arc::new(dissector);
+        self.named.insert(dissector.name().to_lowercase(), dissector.clone());
+        self.heuristics.push((table, dissector));
+    }
+
+    // dissectors without a key are only reachable through decode as rules and by_name
+    pub fn register_named(&mut self, dissector: impl Dissector + 'static) {
+        self.named.insert(dissector.name().to_lowercase(), Arc::new(dissector));
+    }
+
+    pub fn unregister(&mut self, key: Key) -> Option<Arc<dyn Dissector>> {
+        self.keyed.remove(&key)
+    }
+
+    pub fn unregister_heuristic(&mut self, table: Table, name: &str) {
+        self.heuristics.retain(|(heuristic_table, dissector)| *heuristic_table != table || !dissector.name().eq_ignore_ascii_case(name));
+    }
+
+    // the dissector stays in the tables it was registered in, only rules and by_name stop finding it
+    pub fn unregister_named(&mut self, name: &str) -> Option<Arc<dyn Dissector>> {
+        self.named.remove(&name.to_lowercase())
+    }
+
+    // rules naming a dissector that is not registered fall back to the keyed table
+    pub fn get(&self, key: Key) -> Option<Arc<dyn Dissector>> {
+        self.decode_as
+            .get(&key)
+            .and_then(|name| self.by_name(name))
+            .or_else(|| self.keyed.get(&key).cloned())
+    }
+
+    pub fn decode_as(&mut self, key: Key, name: &str) {
+        self.decode_as.insert(key, name.to_lowercase());
+    }
+
+    pub fn clear_decode_as(&mut self, key: Key) {
+        self.decode_as.remove(&key);
+    }
+
+    // any dissector that was ever registered, by name
+    pub fn by_name(&self, name: &str) -> Option<Arc<dyn Dissector>> {
+        self.named.get(&name.to_lowercase()).cloned()
+    }
+
+    // the first heuristic of the table from `start` on that accepts the payload, and where it is in the list
+    fn heuristic(&self, table: Table, start: usize, bytes: &Bytes, context: &Context) -> Option<(usize, Arc<dyn Dissector>)> {
+        self.heuristics
+            .iter()
+            .enumerate()
+            .skip(start)
+            .find(|(_, (heuristic_table, dissector))| *heuristic_table == table && dissector.probe(bytes, context))
+            .map(|(index, (_, dissector))| (index, dissector.clone()))
+    }
+}
+
+
+static REGISTRY: LazyLock<RwLock<Registry>> = LazyLock::new(|| RwLock::new(Registry::with_builtins()));
+
+// changes the registry every packet is dissected with
+pub fn with_registry<T>(change: impl FnOnce(&mut Registry) -> T) -> T {
+    change(&mut REGISTRY.write().unwrap_or_else(|poisoned| poisoned.into_inner()))
+}
+
+pub fn register(key: Key, dissector: impl Dissector + 'static) {
+    with_registry(|registry| registry.register(key, dissector));
+}
+
+pub fn register_heuristic(table: Table, dissector: impl Dissector + 'static) {
+    with_registry(|registry| registry.register_heuristic(table, dissector));
+}
+
+pub fn by_name(name: &str) -> Option<Arc<dyn Dissector>> {
+    read(|registry| registry.by_name(name))
+}
+
+// the lock is only held to look dissectors up, never while they run since they dispatch again
+fn read<T>(look_up: impl FnOnce(&Registry) -> T) -> T {
+    look_up(&REGISTRY.read().unwrap_or_else(|poisoned| poisoned.into_inner()))
+}
+
+
+
+// what is left of a payload nothing claimed
+fn undefined<'a>(table: Table, bytes: Bytes<'a>) -> Layer<'a> {
+    match table {
+        Table::LinkType => Layer::DataLinkLayer(DataLinkLayer::UndefinedData(bytes)),
+        Table::Ethertype | Table::LlcSap | Table::Snap | Table::PppProtocol => Layer::NetworkLayer(NetworkLayer::UndefinedData(bytes)),
+        Table::IpProtocol => Layer::TransportLayer(TransportLayer::UndefinedData(bytes)),
+        Table::TcpPort | Table::UdpPort => Layer::ApplicationLayer(ApplicationLayer::UndefinedData(bytes)),
+    }
+}
+
+fn heuristic<'a>(table: Table, bytes: &mut Bytes<'a>, context: &Context) -> Option<Layer<'a>> {
+    let context = Context { key: None, ..*context };
+
+    // probes only look at the bytes so they run under the lock, the dissector they pick runs after it
+    let mut start = 0;
+    while let Some((index, dissector)) = read(|registry| registry.heuristic(table, start, bytes, &context)) {
+        let mut attempt = *bytes;
+        if let Ok(layer) = dissector.dissect(&mut attempt, &context) {
+            *bytes = attempt;
+            return Some(layer);
+        }
+        start = index + 1;
+    }
+
+    None
+}
+
+// link types, ethertypes and ip protocols, errors of the selected dissector are passed on
+pub fn dispatch<'a>(key: Key, bytes: &mut Bytes<'a>) -> Result<Layer<'a>> {
+    let context = Context { key: Some(key), ..Default::default() };
+
+    if let Some(dissector) = read(|registry| registry.get(key)) {
+        return dissector.dissect(bytes, &context);
+    }
+
+    Ok(heuristic(key.table(), bytes, &context).unwrap_or(undefined(key.table(), *bytes)))
+}
+
+// payloads of tcp and udp, the destination port is tried before the source port
+// since ports are only a hint, payloads that fail to dissect are kept as UndefinedData
+pub fn dispatch_ports<'a>(table: Table, port_src: u16, port_dst: u16, bytes: &mut Bytes<'a>) -> Layer<'a> {
+    let key = |port| match table {
+        Table::TcpPort => Key::TcpPort(port),
+        _ => Key::UdpPort(port),
+    };
+
+    for port in [port_dst, port_src] {
+        let context = Context { key: Some(key(port)), port_src, port_dst };
+        let mut attempt = *bytes;
+
+        if let Some(dissector) = read(|registry| registry.get(key(port)))
+            && let Ok(layer) = dissector.dissect(&mut attempt, &context) {
+            *bytes = attempt;
+            return layer;
+        }
+    }
+
+    let context = Context { key: None, port_src, port_dst };
+    heuristic(table, bytes, &context).unwrap_or(undefined(table, *bytes))
+}
@@ -1,41 +1,70 @@
+use std::fmt::Display;
+
 use byte_slice::{Bytes, Ipv4addr, Ipv6addr, MacAddress, SliceToUnsigned};
-use crate::packet::transport::*;
-use anyhow::Result;
+use crate::packet::dissector::{self, Context, Dissector, Key};
+use anyhow::{anyhow, Result};
 
 use super::{Layer, LayerTrait};
 
-#[derive(Debug, Default)]
+pub mod ipsec;
+pub mod ospf;
+pub mod tunnel;
+
+#[derive(Debug, Default, Clone)]
 pub enum NetworkLayer<'a> {
     #[default]
     NULL,
 
-    UndefinedData(&'a Bytes<'a>),
+    UndefinedData(Bytes<'a>),
     Ipv4(Ipv4<'a>),
     Ipv6(Ipv6<'a>),
     ARP(ARP),
+    IPX(IPX<'a>),
+    NetBIOS(NetBIOS<'a>),
     RARP,
     NAT,
-    RIP,
-    OSPF,
-    BGP,
-    IPSec,
-    MPLS
+    OSPF(ospf::OSPF),
+    AH(ipsec::AH<'a>),
+    ESP(ipsec::ESP<'a>),
+    MPLS(MPLS<'a>),
+    GRE(tunnel::GRE<'a>),
+    ERSPAN(tunnel::ERSPAN<'a>),
+    VXLAN(tunnel::VXLAN<'a>),
+    Geneve(tunnel::Geneve<'a>),
 }
 
 impl<'a> NetworkLayer<'a> {
-    pub fn from_data(layer_type: u16, bytes: &'a mut Bytes) -> Result<Self> {
-        Ok(
-            match layer_type {
-                0x0800 => NetworkLayer::Ipv4(Ipv4::from_bytes(bytes)?),
-                0x0806 => NetworkLayer::ARP(ARP::from_bytes(bytes)?),
-                0x86dd => NetworkLayer::Ipv6(Ipv6::from_bytes(bytes)?),
-                _ => NetworkLayer::UndefinedData(bytes)
-            }
-        )
+    // dissects with whatever is registered for the ethertype, payloads that are not a network layer stay data
+    pub fn from_data(layer_type: u16, bytes: &mut Bytes<'a>) -> Result<Self> {
+        let data = *bytes;
+
+        match dissector::dispatch(Key::Ethertype(layer_type), bytes)? {
+            Layer::NetworkLayer(layer) => Ok(layer),
+            _ => Ok(NetworkLayer::UndefinedData(data)),
+        }
+    }
+}
+
+
+
+// an address of either version, for what carries both
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IpAddress {
+    V4(Ipv4addr),
+    V6(Ipv6addr),
+}
+
+impl Display for IpAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpAddress::V4(address) => Display::fmt(address, f),
+            IpAddress::V6(address) => Display::fmt(address, f),
+        }
     }
 }
 
 
+#[allow(dead_code)]
 enum IpHeader<'a> {
     Ipv4(Ipv4<'a>),
     Ipv6(Ipv6<'a>)
@@ -47,7 +76,7 @@ enum IpHeader<'a> {
 
 
 #[allow(non_snake_case)]
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Ipv4<'a> {
     pub version: u8,
     pub IHL: u8, // length of the header in 32 bit words
@@ -69,26 +98,31 @@ pub struct Ipv4<'a> {
 
 impl<'a> Ipv4<'a> {
     #[allow(non_snake_case)]
-    fn from_bytes(bytes: &'a mut Bytes) -> Result<Self> {
+    fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(20)?;
+
         let version = bytes[0] >> 4; // WHAT
         let IHL = bytes[0] & 0x0f;
         let DSPC = bytes[1];
         let total_length = bytes[2..4].to_u16();
         let identification = bytes[4..6].to_u16();
-        let flags = bytes[6] >> 1;
-        let fragment_offset = bytes[6..8].to_u16() & 0x1ff;
+        let flags = bytes[6] >> 5;
+        let fragment_offset = bytes[6..8].to_u16() & 0x1fff;
         let ttl = bytes[8];
         let protocol = bytes[9];
         let header_checksum = bytes[10..12].to_u16();
         let address_src = Ipv4addr(bytes[12..16].to_u32());
         let address_dst = Ipv4addr(bytes[16..20].to_u32());
 
-        // TODO
-        println!("TODO IMPLEMENT OPTIONS");
-
-        bytes.shift_first(20)?;
+        // TODO parse options, for now they are skipped over
+        let header_length = (IHL as usize * 4).max(20);
+        bytes.shift_first(header_length)?;
+        // anything past the total length is padding of the frame
+        if (header_length..bytes.len() + header_length).contains(&(total_length as usize)) {
+            bytes.shift_last(bytes.len() + header_length - total_length as usize)?;
+        }
         let next_layer = Box::new(
-            Layer::TransportLayer(TransportLayer::from_data(protocol, bytes)?)
+            dissector::dispatch(Key::IpProtocol(protocol), bytes)?
         );
 
         Ok(
@@ -109,10 +143,28 @@ impl<'a> Ipv4<'a> {
             }
         )
     }
+
+    // header only, options are written as zeroed padding up to IHL
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(20);
+        bytes.push((self.version << 4) | (self.IHL & 0x0f));
+        bytes.push(self.DSPC);
+        bytes.extend(self.total_length.to_be_bytes());
+        bytes.extend(self.identification.to_be_bytes());
+        bytes.extend((((self.flags as u16) << 13) | (self.fragment_offset & 0x1fff)).to_be_bytes());
+        bytes.push(self.ttl);
+        bytes.push(self.protocol);
+        bytes.extend(self.header_checksum.to_be_bytes());
+        bytes.extend(self.address_src.0.to_be_bytes());
+        bytes.extend(self.address_dst.0.to_be_bytes());
+        bytes.resize((self.IHL as usize * 4).max(20), 0);
+
+        bytes
+    }
 }
 
 impl<'a> LayerTrait for Ipv4<'a> {
-    fn next_layer(&self) -> &Layer {
+    fn next_layer(&self) -> &Layer<'_> {
         &self.next_layer
     }
 }
@@ -121,7 +173,7 @@ impl<'a> LayerTrait for Ipv4<'a> {
 
 
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Ipv6<'a> {
     pub version: u8, // 4 bits
     pub traffic_class: u8,
@@ -136,7 +188,8 @@ pub struct Ipv6<'a> {
 }
 
 impl<'a> Ipv6<'a> {
-    fn from_bytes(bytes: &'a mut Bytes) -> Result<Self> {
+    fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(40)?;
 
         let version = bytes[0] >> 4;
         let traffic_class = (bytes[0..2].to_u16() >> 4) as u8;
@@ -151,7 +204,7 @@ impl<'a> Ipv6<'a> {
 
 
         let next_layer = Box::new(
-            Layer::TransportLayer(TransportLayer::from_data(next_header, bytes)?)
+            dissector::dispatch(Key::IpProtocol(next_header), bytes)?
         );
 
         Ok(
@@ -165,11 +218,30 @@ impl<'a> Ipv6<'a> {
                 address_src,
                 address_dst,
                 next_layer,
-
-                ..Default::default()
             }
         )
     }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(40);
+        let first_word = ((self.version as u32) << 28)
+            | ((self.traffic_class as u32) << 20)
+            | (self.flow_label & 0x0fffff);
+        bytes.extend(first_word.to_be_bytes());
+        bytes.extend(self.payload_length.to_be_bytes());
+        bytes.push(self.next_header);
+        bytes.push(self.hop_limit);
+        bytes.extend(self.address_src.0.to_be_bytes());
+        bytes.extend(self.address_dst.0.to_be_bytes());
+
+        bytes
+    }
+}
+
+impl<'a> LayerTrait for Ipv6<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
 }
 
 
@@ -177,7 +249,7 @@ impl<'a> Ipv6<'a> {
 
 
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct ARP {
     pub hardware_type: u16,
     pub protocol_type: u16,
@@ -192,6 +264,7 @@ pub struct ARP {
 
 impl ARP {
     fn from_bytes(bytes: &Bytes) -> Result<Self> {
+        bytes.require(28)?;
 
         let hardware_type = bytes[0..2].to_u16();
         let protocol_type = bytes[2..4].to_u16();
@@ -217,4 +290,387 @@ impl ARP {
             }
         )
     }
-}
\ No newline at end of file
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(28);
+        bytes.extend(self.hardware_type.to_be_bytes());
+        bytes.extend(self.protocol_type.to_be_bytes());
+        bytes.push(self.hardware_len);
+        bytes.push(self.protocol_len);
+        bytes.extend(self.operation.to_be_bytes());
+        bytes.extend(self.sender_hardware_address.octets());
+        bytes.extend(self.sender_protocol_address.0.to_be_bytes());
+        bytes.extend(self.target_hardware_address.octets());
+        bytes.extend(self.target_protocol_address.0.to_be_bytes());
+
+        bytes
+    }
+}
+
+
+
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct IpxAddress {
+    pub network: u32,
+    pub node: MacAddress,
+    pub socket: u16,
+}
+
+impl IpxAddress {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            network: bytes[0..4].to_u32(),
+            node: MacAddress::from(bytes[4..10].to_u64()),
+            socket: bytes[10..12].to_u16(),
+        }
+    }
+}
+
+
+// novell's internetwork packet exchange
+#[derive(Debug, Default, Clone)]
+pub struct IPX<'a> {
+    pub checksum: u16, // always 0xffff
+    pub length: u16,
+    pub transport_control: u8, // hops travelled
+    pub packet_type: u8,
+    pub address_dst: IpxAddress,
+    pub address_src: IpxAddress,
+
+    pub next_layer: Box<Layer<'a>>,
+}
+
+impl<'a> IPX<'a> {
+    fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(30)?;
+
+        let checksum = bytes[0..2].to_u16();
+        let length = bytes[2..4].to_u16();
+        let transport_control = bytes[4];
+        let packet_type = bytes[5];
+        let address_dst = IpxAddress::from_bytes(&bytes[6..18]);
+        let address_src = IpxAddress::from_bytes(&bytes[18..30]);
+
+        if (30..bytes.len()).contains(&(length as usize)) {
+            bytes.shift_last(bytes.len() - length as usize)?;
+        }
+        bytes.shift_first(30)?;
+
+        Ok(
+            Self {
+                checksum,
+                length,
+                transport_control,
+                packet_type,
+                address_dst,
+                address_src,
+                next_layer: Box::new(data_layer(*bytes)),
+            }
+        )
+    }
+}
+
+impl<'a> LayerTrait for IPX<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+
+// 15 characters padded with spaces and a suffix that tells the kind of service
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct NetbiosName {
+    pub name: String,
+    pub suffix: u8,
+}
+
+impl NetbiosName {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            name: String::from_utf8_lossy(&bytes[..15]).trim_end_matches([' ', '\0']).to_owned(),
+            suffix: bytes[15],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetbiosPeers {
+    // datagrams and name management
+    Names { destination: NetbiosName, source: NetbiosName },
+    // frames of an established session
+    Sessions { destination: u8, source: u8 },
+}
+
+// netbios frames protocol, carried directly on llc, all numbers are little endian
+#[derive(Debug, Clone)]
+pub struct NetBIOS<'a> {
+    pub header_length: u16,
+    pub command: u8,
+    pub data1: u8,
+    pub data2: u16,
+    pub transmit_correlator: u16,
+    pub response_correlator: u16,
+    pub peers: NetbiosPeers,
+
+    pub next_layer: Box<Layer<'a>>,
+}
+
+impl<'a> NetBIOS<'a> {
+    const DELIMITER: u16 = 0xefff;
+
+    fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(14)?;
+
+        let le_u16 = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let header_length = le_u16(0);
+        if le_u16(2) != Self::DELIMITER {
+            return Err(anyhow!("NetBIOS header is missing its delimiter"));
+        }
+
+        let peers = match header_length {
+            44 => {
+                bytes.require(44)?;
+                NetbiosPeers::Names {
+                    destination: NetbiosName::from_bytes(&bytes[12..28]),
+                    source: NetbiosName::from_bytes(&bytes[28..44]),
+                }
+            },
+            14 => NetbiosPeers::Sessions { destination: bytes[12], source: bytes[13] },
+            _ => return Err(anyhow!("NetBIOS header length {header_length} is neither 14 nor 44")),
+        };
+
+        let netbios = Self {
+            header_length,
+            command: bytes[4],
+            data1: bytes[5],
+            data2: le_u16(6),
+            transmit_correlator: le_u16(8),
+            response_correlator: le_u16(10),
+            peers,
+            next_layer: Box::default(),
+        };
+        bytes.shift_first(header_length as usize)?;
+
+        Ok(
+            Self {
+                next_layer: Box::new(data_layer(*bytes)),
+                ..netbios
+            }
+        )
+    }
+}
+
+impl<'a> LayerTrait for NetBIOS<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LabelStackEntry {
+    pub label: u32, // 20 bits
+    pub traffic_class: u8, // 3 bits
+    pub bottom_of_stack: bool,
+    pub ttl: u8,
+}
+
+impl LabelStackEntry {
+    // labels 0 to 15 are reserved
+    pub const IPV4_EXPLICIT_NULL: u32 = 0;
+    pub const IPV6_EXPLICIT_NULL: u32 = 2;
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let entry = bytes[0..4].to_u32();
+
+        Self {
+            label: entry >> 12,
+            traffic_class: ((entry >> 9) & 0b111) as u8,
+            bottom_of_stack: (entry >> 8) & 1 == 1,
+            ttl: entry as u8,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 4] {
+        ((self.label & 0xfffff) << 12 | (self.traffic_class as u32 & 0b111) << 9 | (self.bottom_of_stack as u32) << 8 | self.ttl as u32).to_be_bytes()
+    }
+}
+
+
+// ethertype 0x8847 for unicast, 0x8848 for multicast
+#[derive(Debug, Default, Clone)]
+pub struct MPLS<'a> {
+    // top of the stack first, the last entry has bottom_of_stack set
+    pub labels: Vec<LabelStackEntry>,
+    // the 4 bytes in front of the ethernet frame of a pseudowire
+    pub control_word: Option<u32>,
+
+    pub next_layer: Box<Layer<'a>>,
+}
+
+impl<'a> MPLS<'a> {
+    fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        let mut labels = Vec::new();
+
+        loop {
+            bytes.require(4)?;
+            let entry = LabelStackEntry::from_bytes(&bytes[0..4]);
+            bytes.shift_first(4)?;
+            labels.push(entry);
+
+            if entry.bottom_of_stack {
+                break;
+            }
+        }
+
+        let mut control_word = None;
+        let next_layer = Box::new(
+            match Self::payload(labels[labels.len() - 1].label, bytes) {
+                _ if bytes.is_empty() => Layer::NoLayer,
+                MplsPayload::Ipv4 => dissector::dispatch(Key::Ethertype(0x0800), bytes)?,
+                MplsPayload::Ipv6 => dissector::dispatch(Key::Ethertype(0x86dd), bytes)?,
+                MplsPayload::ControlWord => {
+                    control_word = Some(bytes[0..4].to_u32());
+                    bytes.shift_first(4)?;
+                    dissector::dispatch(Key::LinkType(crate::pcap::LINKTYPE_ETHERNET), bytes)?
+                },
+                MplsPayload::Ethernet => dissector::dispatch(Key::LinkType(crate::pcap::LINKTYPE_ETHERNET), bytes)?,
+                MplsPayload::Unknown => Layer::Data(*bytes),
+            }
+        );
+
+        Ok(
+            Self {
+                labels,
+                control_word,
+                next_layer,
+            }
+        )
+    }
+
+    // the label stack does not say what it carries, the first nibble of the payload is the best guess there is
+    fn payload(bottom_label: u32, bytes: &Bytes) -> MplsPayload {
+        let length = bytes.len();
+        let fits = |header: usize, total: usize| (header..=length).contains(&total);
+        let ethertype_at = |offset: usize| length >= offset + 2 && bytes[offset..offset + 2].to_u16() >= super::data_link::MIN_ETHERTYPE;
+
+        match bottom_label {
+            LabelStackEntry::IPV4_EXPLICIT_NULL => return MplsPayload::Ipv4,
+            LabelStackEntry::IPV6_EXPLICIT_NULL => return MplsPayload::Ipv6,
+            _ => {},
+        }
+
+        match bytes[..].first().map(|byte| byte >> 4) {
+            Some(4) if length >= 20 && bytes[0] & 0x0f >= 5 && fits(20, bytes[2..4].to_u16() as usize) => MplsPayload::Ipv4,
+            Some(6) if length >= 40 && fits(40, 40 + bytes[4..6].to_u16() as usize) => MplsPayload::Ipv6,
+            // a control word starts with a 0 nibble just like many mac addresses do, it wins when the frame
+            // only makes sense after it or when its flags and length are 0, which they usually are
+            Some(0) if ethertype_at(16) && (!ethertype_at(12) || bytes[0..2] == [0, 0]) => MplsPayload::ControlWord,
+            _ if ethertype_at(12) => MplsPayload::Ethernet,
+            _ => MplsPayload::Unknown,
+        }
+    }
+}
+
+impl<'a> LayerTrait for MPLS<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+enum MplsPayload {
+    Ipv4,
+    Ipv6,
+    // pseudowire ethernet with and without a control word
+    ControlWord,
+    Ethernet,
+    Unknown,
+}
+
+
+// payloads this crate has no dissector table for
+fn data_layer<'a>(bytes: Bytes<'a>) -> Layer<'a> {
+    match bytes.is_empty() {
+        true => Layer::NoLayer,
+        false => Layer::Data(bytes),
+    }
+}
+
+
+
+pub struct Ipv4Dissector;
+
+impl Dissector for Ipv4Dissector {
+    fn name(&self) -> &str {
+        "ipv4"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::NetworkLayer(NetworkLayer::Ipv4(Ipv4::from_bytes(bytes)?)))
+    }
+}
+
+pub struct Ipv6Dissector;
+
+impl Dissector for Ipv6Dissector {
+    fn name(&self) -> &str {
+        "ipv6"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::NetworkLayer(NetworkLayer::Ipv6(Ipv6::from_bytes(bytes)?)))
+    }
+}
+
+pub struct ArpDissector;
+
+impl Dissector for ArpDissector {
+    fn name(&self) -> &str {
+        "arp"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        let arp = ARP::from_bytes(bytes)?;
+        bytes.shift_first(28)?;
+
+        Ok(Layer::NetworkLayer(NetworkLayer::ARP(arp)))
+    }
+}
+
+pub struct IpxDissector;
+
+impl Dissector for IpxDissector {
+    fn name(&self) -> &str {
+        "ipx"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::NetworkLayer(NetworkLayer::IPX(IPX::from_bytes(bytes)?)))
+    }
+}
+
+pub struct NetbiosDissector;
+
+impl Dissector for NetbiosDissector {
+    fn name(&self) -> &str {
+        "netbios"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::NetworkLayer(NetworkLayer::NetBIOS(NetBIOS::from_bytes(bytes)?)))
+    }
+}
+
+pub struct MplsDissector;
+
+impl Dissector for MplsDissector {
+    fn name(&self) -> &str {
+        "mpls"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::NetworkLayer(NetworkLayer::MPLS(MPLS::from_bytes(bytes)?)))
+    }
+}
@@ -0,0 +1,490 @@
+use std::{collections::HashMap, path::Path, str::FromStr, sync::{LazyLock, RwLock}};
+
+use aes_gcm::{aead::{consts::U12, Aead, KeyInit, Payload}, AesGcm, Nonce};
+use anyhow::{anyhow, Result};
+use byte_slice::{Bytes, SliceToUnsigned};
+use cbc::cipher::{block_padding::NoPadding, BlockCipher, BlockDecrypt, BlockDecryptMut, KeyIvInit};
+use hmac::{Hmac, Mac};
+use self_cell::self_cell;
+
+use crate::packet::{dissector::{self, Context, Dissector, Key}, Layer, LayerTrait};
+
+use super::NetworkLayer;
+
+
+// RFC 4302 authentication headers and RFC 4303 encapsulating security payloads. ESP payloads are decrypted
+// when the security association of their SPI is known, one association per line with # starting a comment:
+//
+// # spi       algorithm            encryption key (gcm: key and 4 byte salt)   authentication key
+// 0x00001001  aes-gcm              0x00112233445566778899aabbccddeeff01020304
+// 0x00001002  aes-cbc-hmac-sha256  0x00112233445566778899aabbccddeeff          0x0102...1f20
+//
+// the aes key size follows from the length of the key, the icv is 16 bytes for gcm and half the hash for hmac
+//
+// the packet inside a decrypted payload follows the esp layer in Packet::layers like any other layer would
+
+
+
+#[derive(Debug, Default, Clone)]
+pub struct AH<'a> {
+    pub next_header: u8,
+    // the length of the header in 32 bit words, minus 2
+    pub payload_length: u8,
+    pub spi: u32,
+    pub sequence: u32,
+    pub icv: Bytes<'a>,
+
+    pub next_layer: Box<Layer<'a>>,
+}
+
+impl<'a> AH<'a> {
+    fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(12)?;
+
+        let next_header = bytes[0];
+        let payload_length = bytes[1];
+        let spi = bytes[4..8].to_u32();
+        let sequence = bytes[8..12].to_u32();
+
+        let length = (payload_length as usize + 2) * 4;
+        if length < 12 {
+            return Err(anyhow!("AH payload length {payload_length} is shorter than the header"));
+        }
+        bytes.require(length)?;
+        let mut icv = *bytes;
+        icv.shift_first(12)?;
+        icv.shift_last(icv.len() - (length - 12))?;
+        bytes.shift_first(length)?;
+
+        let next_layer = Box::new(
+            dissector::dispatch(Key::IpProtocol(next_header), bytes)?
+        );
+
+        Ok(
+            Self {
+                next_header,
+                payload_length,
+                spi,
+                sequence,
+                icv,
+                next_layer,
+            }
+        )
+    }
+}
+
+impl<'a> LayerTrait for AH<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+
+
+#[derive(Debug, Default, Clone)]
+pub struct ESP<'a> {
+    pub spi: u32,
+    pub sequence: u32,
+    // iv, ciphertext, trailer and icv, which part is which is only known from the security association
+    pub payload: Bytes<'a>,
+
+    pub decrypted: Option<Decrypted>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Decrypted {
+    pub iv: Vec<u8>,
+    pub icv: Vec<u8>,
+    pub padding_length: u8,
+    pub next_header: u8,
+    // the plaintext without the padding and the trailer, and the packet inside it
+    pub inner: InnerPacket,
+}
+
+// the layers borrow the plaintext they were dissected from, a copy dissects its own copy again
+impl Clone for Decrypted {
+    fn clone(&self) -> Self {
+        Self {
+            iv: self.iv.clone(),
+            icv: self.icv.clone(),
+            padding_length: self.padding_length,
+            next_header: self.next_header,
+            inner: InnerPacket::dissect(self.next_header, self.plaintext().to_vec()),
+        }
+    }
+}
+
+impl Decrypted {
+    pub fn plaintext(&self) -> &[u8] {
+        self.inner.borrow_owner()
+    }
+
+    // an ip packet in tunnel mode and a transport header in transport mode, what fails to dissect stays data
+    pub fn layer(&self) -> &Layer<'_> {
+        self.inner.borrow_dependent()
+    }
+}
+
+
+self_cell!(
+    // the plaintext outlives the captured bytes, so the layers dissected from it are kept with it
+    pub struct InnerPacket {
+        owner: Vec<u8>,
+
+        #[covariant]
+        dependent: Layer,
+    }
+);
+
+impl InnerPacket {
+    fn dissect(next_header: u8, plaintext: Vec<u8>) -> Self {
+        Self::new(plaintext, |plaintext| {
+            let mut bytes = Bytes::from_slice(plaintext);
+            match next_header {
+                // a dummy packet, RFC 4303 traffic flow confidentiality
+                59 => Layer::NoLayer,
+                next_header => dissector::dispatch(Key::IpProtocol(next_header), &mut bytes).unwrap_or(Layer::Data(Bytes::from_slice(plaintext))),
+            }
+        })
+    }
+}
+
+impl std::fmt::Debug for InnerPacket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.borrow_dependent().fmt(f)
+    }
+}
+
+impl PartialEq for InnerPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.borrow_owner() == other.borrow_owner()
+    }
+}
+
+impl<'a> ESP<'a> {
+    fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(8)?;
+
+        let spi = bytes[0..4].to_u32();
+        let sequence = bytes[4..8].to_u32();
+        let payload = {
+            let mut payload = *bytes;
+            payload.shift_first(8)?;
+            payload
+        };
+
+        // a payload that fails to decrypt or authenticate is kept encrypted
+        let decrypted = read(|table| table.get(spi).cloned())
+            .and_then(|association| association.decrypt(&bytes[0..8], &payload[..]).ok());
+        bytes.shift_first(bytes.len())?;
+
+        Ok(
+            Self {
+                spi,
+                sequence,
+                payload,
+                decrypted,
+            }
+        )
+    }
+}
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Integrity {
+    HmacSha1,
+    HmacSha256,
+    HmacSha384,
+    HmacSha512,
+}
+
+impl Integrity {
+    // RFC 2404 and RFC 4868 truncate the hmac to half of it, sha1 to 96 bits
+    pub fn icv_length(&self) -> usize {
+        match self {
+            Integrity::HmacSha1 => 12,
+            Integrity::HmacSha256 => 16,
+            Integrity::HmacSha384 => 24,
+            Integrity::HmacSha512 => 32,
+        }
+    }
+
+    fn verify(&self, key: &[u8], data: &[u8], icv: &[u8]) -> Result<()> {
+        fn verify<M: Mac + KeyInit>(key: &[u8], data: &[u8], icv: &[u8]) -> Result<()> {
+            let mut mac = <M as Mac>::new_from_slice(key).map_err(|_| anyhow!("Invalid HMAC key length {}", key.len()))?;
+            mac.update(data);
+            mac.verify_truncated_left(icv).map_err(|_| anyhow!("ESP ICV does not match"))
+        }
+
+        match self {
+            Integrity::HmacSha1 => verify::<Hmac<sha1::Sha1>>(key, data, icv),
+            Integrity::HmacSha256 => verify::<Hmac<sha2::Sha256>>(key, data, icv),
+            Integrity::HmacSha384 => verify::<Hmac<sha2::Sha384>>(key, data, icv),
+            Integrity::HmacSha512 => verify::<Hmac<sha2::Sha512>>(key, data, icv),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    // RFC 4106, combined mode
+    AesGcm,
+    // RFC 3602
+    AesCbc(Integrity),
+}
+
+impl FromStr for Algorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(
+            match s.to_lowercase().as_str() {
+                "aes-gcm" | "aes-gcm-16" => Algorithm::AesGcm,
+                "aes-cbc-hmac-sha1" => Algorithm::AesCbc(Integrity::HmacSha1),
+                "aes-cbc-hmac-sha256" => Algorithm::AesCbc(Integrity::HmacSha256),
+                "aes-cbc-hmac-sha384" => Algorithm::AesCbc(Integrity::HmacSha384),
+                "aes-cbc-hmac-sha512" => Algorithm::AesCbc(Integrity::HmacSha512),
+                algorithm => return Err(anyhow!("Unknown ESP algorithm {algorithm}")),
+            }
+        )
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecurityAssociation {
+    pub spi: u32,
+    pub algorithm: Algorithm,
+    // the key followed by the salt for gcm
+    pub encryption_key: Vec<u8>,
+    pub authentication_key: Vec<u8>,
+}
+
+impl SecurityAssociation {
+    const GCM_SALT: usize = 4;
+    const GCM_IV: usize = 8;
+    const GCM_ICV: usize = 16;
+    const CBC_IV: usize = 16;
+
+    // `header` is the spi and the sequence number, `payload` what follows them
+    pub fn decrypt(&self, header: &[u8], payload: &[u8]) -> Result<Decrypted> {
+        let (iv, icv, plaintext) = match self.algorithm {
+            Algorithm::AesGcm => {
+                let (iv, rest) = split(payload, Self::GCM_IV)?;
+                let icv = rest.get(rest.len().saturating_sub(Self::GCM_ICV)..).unwrap_or_default();
+                let (key, salt) = self.encryption_key.split_at(self.encryption_key.len().saturating_sub(Self::GCM_SALT));
+                let nonce = [salt, iv].concat();
+                let payload = Payload { msg: rest, aad: header };
+
+                let plaintext = match key.len() {
+                    16 => gcm::<aes::Aes128>(key, &nonce, payload),
+                    24 => gcm::<aes::Aes192>(key, &nonce, payload),
+                    32 => gcm::<aes::Aes256>(key, &nonce, payload),
+                    length => Err(anyhow!("Invalid AES-GCM key length {length}")),
+                }?;
+
+                (iv, icv, plaintext)
+            },
+            Algorithm::AesCbc(integrity) => {
+                let authenticated = payload.len().checked_sub(integrity.icv_length()).ok_or(anyhow!("ESP payload is shorter than its ICV"))?;
+                let (authenticated, icv) = payload.split_at(authenticated);
+                integrity.verify(&self.authentication_key, &[header, authenticated].concat(), icv)?;
+
+                let (iv, ciphertext) = split(authenticated, Self::CBC_IV)?;
+                let key = &self.encryption_key;
+                let plaintext = match key.len() {
+                    16 => cbc::<aes::Aes128>(key, iv, ciphertext),
+                    24 => cbc::<aes::Aes192>(key, iv, ciphertext),
+                    32 => cbc::<aes::Aes256>(key, iv, ciphertext),
+                    length => Err(anyhow!("Invalid AES-CBC key length {length}")),
+                }?;
+
+                (iv, icv, plaintext)
+            },
+        };
+
+        // the trailer is the padding, its length and the next header
+        let [.., padding_length, next_header] = plaintext[..] else {
+            return Err(anyhow!("ESP plaintext is missing its trailer"));
+        };
+        let length = plaintext.len()
+            .checked_sub(padding_length as usize + 2)
+            .ok_or(anyhow!("ESP padding of {padding_length} bytes is longer than the plaintext"))?;
+
+        Ok(
+            Decrypted {
+                iv: iv.to_vec(),
+                icv: icv.to_vec(),
+                padding_length,
+                next_header,
+                inner: InnerPacket::dissect(next_header, plaintext[..length].to_vec()),
+            }
+        )
+    }
+}
+
+fn split(bytes: &[u8], at: usize) -> Result<(&[u8], &[u8])> {
+    match bytes.len() >= at {
+        true => Ok(bytes.split_at(at)),
+        false => Err(anyhow!("ESP payload is shorter than its {at} byte IV")),
+    }
+}
+
+fn gcm<C>(key: &[u8], nonce: &[u8], payload: Payload) -> Result<Vec<u8>>
+where
+    AesGcm<C, U12>: KeyInit + Aead,
+{
+    AesGcm::<C, U12>::new_from_slice(key)
+        .map_err(|_| anyhow!("Invalid AES-GCM key length {}", key.len()))?
+        .decrypt(Nonce::from_slice(nonce), payload)
+        .map_err(|_| anyhow!("ESP payload failed to authenticate"))
+}
+
+fn cbc<C>(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>>
+where
+    C: BlockCipher + BlockDecrypt + cbc::cipher::KeyInit,
+{
+    let mut plaintext = ciphertext.to_vec();
+    let length = cbc::Decryptor::<C>::new_from_slices(key, iv)
+        .map_err(|_| anyhow!("Invalid AES-CBC key length {}", key.len()))?
+        .decrypt_padded_mut::<NoPadding>(&mut plaintext)
+        .map_err(|_| anyhow!("ESP ciphertext is not a multiple of the block size"))?
+        .len();
+    plaintext.truncate(length);
+
+    Ok(plaintext)
+}
+
+
+
+#[derive(Debug, Default, Clone)]
+pub struct SaTable {
+    associations: HashMap<u32, SecurityAssociation>,
+}
+
+impl SaTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // replaces the association of the same spi
+    pub fn insert(&mut self, association: SecurityAssociation) {
+        self.associations.insert(association.spi, association);
+    }
+
+    pub fn remove(&mut self, spi: u32) -> Option<SecurityAssociation> {
+        self.associations.remove(&spi)
+    }
+
+    pub fn get(&self, spi: u32) -> Option<&SecurityAssociation> {
+        self.associations.get(&spi)
+    }
+
+    pub fn len(&self) -> usize {
+        self.associations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.associations.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.associations.clear();
+    }
+}
+
+
+pub fn parse_associations(text: &str) -> Result<Vec<SecurityAssociation>> {
+    let number = |value: &str| match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|_| anyhow!("{value} is not a number"));
+    let key = |value: &str| -> Result<Vec<u8>> {
+        let hex = value.strip_prefix("0x").unwrap_or(value);
+        if !hex.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+            return Err(anyhow!("{value} is not a hex key"));
+        }
+        match hex.len() % 2 {
+            0 => (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| anyhow!("{value} is not a hex key")))
+                .collect(),
+            _ => Err(anyhow!("{value} has an odd number of hex digits")),
+        }
+    };
+
+    text.lines()
+        .enumerate()
+        .map(|(line, association)| (line, association.split('#').next().unwrap_or_default().trim()))
+        .filter(|(_, association)| !association.is_empty())
+        .map(|(line, association)| {
+            let fields: Vec<&str> = association.split_whitespace().collect();
+            let association = match fields[..] {
+                [spi, algorithm, encryption_key, ref authentication_key @ ..] if authentication_key.len() <= 1 => {
+                    let algorithm: Algorithm = algorithm.parse()?;
+                    let authentication_key = authentication_key.first().map(|value| key(value)).transpose()?.unwrap_or_default();
+                    if matches!(algorithm, Algorithm::AesCbc(_)) && authentication_key.is_empty() {
+                        return Err(anyhow!("Line {}: {association} is missing the authentication key", line + 1));
+                    }
+
+                    SecurityAssociation { spi: number(spi)?, algorithm, encryption_key: key(encryption_key)?, authentication_key }
+                },
+                _ => return Err(anyhow!("Line {}: {association} is not <spi> <algorithm> <key> [<authentication key>]", line + 1)),
+            };
+
+            Ok(association)
+        })
+        .collect()
+}
+
+// adds the associations of a file to the table esp payloads are decrypted with, returns how many there were
+pub fn load_file(path: impl AsRef<Path>) -> Result<usize> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path).map_err(|error| anyhow!("Failed to read {}: {error}", path.display()))?;
+    let associations = parse_associations(&text)?;
+    let count = associations.len();
+
+    with_sa_table(|table| associations.into_iter().for_each(|association| table.insert(association)));
+
+    Ok(count)
+}
+
+
+static SA_TABLE: LazyLock<RwLock<SaTable>> = LazyLock::new(|| RwLock::new(SaTable::new()));
+
+// changes the associations every esp payload is decrypted with
+pub fn with_sa_table<T>(change: impl FnOnce(&mut SaTable) -> T) -> T {
+    change(&mut SA_TABLE.write().unwrap_or_else(|poisoned| poisoned.into_inner()))
+}
+
+fn read<T>(look_up: impl FnOnce(&SaTable) -> T) -> T {
+    look_up(&SA_TABLE.read().unwrap_or_else(|poisoned| poisoned.into_inner()))
+}
+
+
+
+pub struct AhDissector;
+
+impl Dissector for AhDissector {
+    fn name(&self) -> &str {
+        "ah"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::NetworkLayer(NetworkLayer::AH(AH::from_bytes(bytes)?)))
+    }
+}
+
+pub struct EspDissector;
+
+impl Dissector for EspDissector {
+    fn name(&self) -> &str {
+        "esp"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::NetworkLayer(NetworkLayer::ESP(ESP::from_bytes(bytes)?)))
+    }
+}
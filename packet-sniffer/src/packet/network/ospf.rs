@@ -0,0 +1,641 @@
+use anyhow::{anyhow, Result};
+use byte_slice::{Bytes, Ipv4addr, Ipv6addr, SliceToUnsigned};
+
+use crate::packet::{dissector::{Context, Dissector}, reader::Reader, Layer};
+
+use super::NetworkLayer;
+
+
+// OSPFv2 (RFC 2328) and OSPFv3 (RFC 5340) on ip protocol 89. Both share the five packet types and most of
+// the lsa header, v3 drops authentication and addresses from the packets and moves prefixes into their own lsas.
+// Opaque lsas (RFC 5250) of the traffic engineering type (RFC 3630) are decoded, other opaque types are kept raw.
+
+
+
+pub mod packet_type {
+    pub const HELLO: u8 = 1;
+    pub const DATABASE_DESCRIPTION: u8 = 2;
+    pub const LINK_STATE_REQUEST: u8 = 3;
+    pub const LINK_STATE_UPDATE: u8 = 4;
+    pub const LINK_STATE_ACK: u8 = 5;
+}
+
+// v2 lsa types, v3 types are 16 bits with the flooding scope in the top bits
+pub mod lsa_type {
+    pub const ROUTER: u16 = 1;
+    pub const NETWORK: u16 = 2;
+    pub const SUMMARY_NETWORK: u16 = 3;
+    pub const SUMMARY_ASBR: u16 = 4;
+    pub const AS_EXTERNAL: u16 = 5;
+    pub const NSSA: u16 = 7;
+    pub const OPAQUE_LINK: u16 = 9;
+    pub const OPAQUE_AREA: u16 = 10;
+    pub const OPAQUE_AS: u16 = 11;
+
+    pub const V3_ROUTER: u16 = 0x2001;
+    pub const V3_NETWORK: u16 = 0x2002;
+    pub const V3_INTER_AREA_PREFIX: u16 = 0x2003;
+    pub const V3_INTER_AREA_ROUTER: u16 = 0x2004;
+    pub const V3_AS_EXTERNAL: u16 = 0x4005;
+    pub const V3_NSSA: u16 = 0x2007;
+    pub const V3_LINK: u16 = 0x0008;
+    pub const V3_INTRA_AREA_PREFIX: u16 = 0x2009;
+}
+
+pub const OPAQUE_TRAFFIC_ENGINEERING: u8 = 1;
+
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Authentication {
+    Null,
+    // a clear text password padded with zeros
+    Simple([u8; 8]),
+    // the digest follows the packet, it is not part of its length
+    Cryptographic { key_id: u8, data_length: u8, sequence: u32 },
+    Unknown { auth_type: u16, data: [u8; 8] },
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OSPF {
+    pub version: u8,
+    pub packet_type: u8,
+    pub length: u16,
+    pub router_id: Ipv4addr,
+    pub area_id: Ipv4addr,
+    pub checksum: u16,
+    // v2 only
+    pub authentication: Option<Authentication>,
+    // v3 only
+    pub instance_id: Option<u8>,
+
+    pub body: OspfBody,
+}
+
+impl OSPF {
+    pub fn from_bytes(bytes: &mut Bytes) -> Result<Self> {
+        bytes.require(16)?;
+
+        let version = bytes[0];
+        let packet_type = bytes[1];
+        let length = bytes[2..4].to_u16();
+        let router_id = Ipv4addr(bytes[4..8].to_u32());
+        let area_id = Ipv4addr(bytes[8..12].to_u32());
+        let checksum = bytes[12..14].to_u16();
+
+        let (header_length, authentication, instance_id) = match version {
+            2 => {
+                bytes.require(24)?;
+                let data: [u8; 8] = bytes[16..24].try_into()?;
+                let authentication = match bytes[14..16].to_u16() {
+                    0 => Authentication::Null,
+                    1 => Authentication::Simple(data),
+                    2 => Authentication::Cryptographic { key_id: data[2], data_length: data[3], sequence: data[4..8].to_u32() },
+                    auth_type => Authentication::Unknown { auth_type, data },
+                };
+                (24, Some(authentication), None)
+            },
+            3 => (16, None, Some(bytes[14])),
+            version => return Err(anyhow!("Unknown OSPF version {version}")),
+        };
+
+        if (length as usize) < header_length {
+            return Err(anyhow!("OSPF packet length {length} is shorter than its header"));
+        }
+        bytes.require(length as usize)?;
+
+        let mut reader = Reader::with_context("OSPF", &bytes[header_length..length as usize], version);
+        let body = match packet_type {
+            packet_type::HELLO => OspfBody::Hello(reader.hello()?),
+            packet_type::DATABASE_DESCRIPTION => OspfBody::DatabaseDescription(reader.database_description()?),
+            packet_type::LINK_STATE_REQUEST => OspfBody::LinkStateRequest(reader.until_empty(Reader::lsa_request)?),
+            packet_type::LINK_STATE_UPDATE => {
+                let count = reader.u32()?;
+                OspfBody::LinkStateUpdate((0..count).map(|_| reader.lsa()).collect::<Result<_>>()?)
+            },
+            packet_type::LINK_STATE_ACK => OspfBody::LinkStateAck(reader.until_empty(Reader::lsa_header)?),
+            packet_type => return Err(anyhow!("Unknown OSPF packet type {packet_type}")),
+        };
+        // the cryptographic digest or link local signaling data
+        bytes.shift_first(bytes.len())?;
+
+        Ok(
+            Self {
+                version,
+                packet_type,
+                length,
+                router_id,
+                area_id,
+                checksum,
+                authentication,
+                instance_id,
+                body,
+            }
+        )
+    }
+
+    pub fn packet_type_name(&self) -> &'static str {
+        match self.packet_type {
+            packet_type::HELLO => "Hello",
+            packet_type::DATABASE_DESCRIPTION => "Database Description",
+            packet_type::LINK_STATE_REQUEST => "Link State Request",
+            packet_type::LINK_STATE_UPDATE => "Link State Update",
+            _ => "Link State Acknowledgment",
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OspfBody {
+    Hello(Hello),
+    DatabaseDescription(DatabaseDescription),
+    LinkStateRequest(Vec<LsaRequest>),
+    LinkStateUpdate(Vec<Lsa>),
+    LinkStateAck(Vec<LsaHeader>),
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Hello {
+    // v2 only
+    pub network_mask: Option<Ipv4addr>,
+    // v3 only
+    pub interface_id: Option<u32>,
+    pub hello_interval: u16,
+    // 8 bits in v2, 24 bits in v3
+    pub options: u32,
+    pub priority: u8,
+    pub dead_interval: u32,
+    pub designated_router: Ipv4addr,
+    pub backup_designated_router: Ipv4addr,
+    pub neighbors: Vec<Ipv4addr>,
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DatabaseDescription {
+    pub interface_mtu: u16,
+    pub options: u32,
+    pub flags: u8,
+    pub sequence: u32,
+    pub lsa_headers: Vec<LsaHeader>,
+}
+
+impl DatabaseDescription {
+    const INIT: u8 = 0x04;
+    const MORE: u8 = 0x02;
+    const MASTER: u8 = 0x01;
+
+    pub fn is_init(&self) -> bool {
+        self.flags & Self::INIT != 0
+    }
+
+    pub fn is_more(&self) -> bool {
+        self.flags & Self::MORE != 0
+    }
+
+    pub fn is_master(&self) -> bool {
+        self.flags & Self::MASTER != 0
+    }
+}
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LsaRequest {
+    pub ls_type: u16,
+    pub link_state_id: Ipv4addr,
+    pub advertising_router: Ipv4addr,
+}
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LsaHeader {
+    pub age: u16,
+    // v2 only, v3 lsas carry their options in the body
+    pub options: Option<u8>,
+    pub ls_type: u16,
+    pub link_state_id: Ipv4addr,
+    pub advertising_router: Ipv4addr,
+    pub sequence: u32,
+    pub checksum: u16,
+    // including the header
+    pub length: u16,
+}
+
+impl LsaHeader {
+    pub const LENGTH: usize = 20;
+
+    // an opaque lsa's link state id is its opaque type followed by a 24 bit opaque id
+    pub fn opaque_type(&self) -> u8 {
+        (self.link_state_id.0 >> 24) as u8
+    }
+
+    pub fn opaque_id(&self) -> u32 {
+        self.link_state_id.0 & 0x00ff_ffff
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lsa {
+    pub header: LsaHeader,
+    pub body: LsaBody,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RouterLink {
+    // 1 point to point, 2 transit network, 3 stub network, 4 virtual link
+    pub link_type: u8,
+    pub link_id: Ipv4addr,
+    pub link_data: Ipv4addr,
+    pub metric: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RouterInterface {
+    pub interface_type: u8,
+    pub metric: u16,
+    pub interface_id: u32,
+    pub neighbor_interface_id: u32,
+    pub neighbor_router_id: Ipv4addr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ipv6Prefix {
+    pub length: u8,
+    pub options: u8,
+    // the metric in intra area prefix lsas, the referenced ls type in external lsas, 0 otherwise
+    pub metric: u16,
+    pub address: Ipv6addr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TeLinkTlv {
+    // 1 point to point, 2 multi access
+    LinkType(u8),
+    LinkId(Ipv4addr),
+    LocalAddresses(Vec<Ipv4addr>),
+    RemoteAddresses(Vec<Ipv4addr>),
+    TeMetric(u32),
+    // bytes per second
+    MaximumBandwidth(f32),
+    MaximumReservableBandwidth(f32),
+    // per priority
+    UnreservedBandwidth([f32; 8]),
+    AdministrativeGroup(u32),
+    Unknown { tlv_type: u16, value: Vec<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TeTlv {
+    RouterAddress(Ipv4addr),
+    Link(Vec<TeLinkTlv>),
+    Unknown { tlv_type: u16, value: Vec<u8> },
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LsaBody {
+    // v2
+    Router { flags: u8, links: Vec<RouterLink> },
+    Network { mask: Ipv4addr, attached_routers: Vec<Ipv4addr> },
+    // networks (type 3) and as boundary routers (type 4) of other areas
+    Summary { mask: Ipv4addr, metric: u32 },
+    // as external (type 5) and nssa (type 7)
+    External { mask: Ipv4addr, type_2_metric: bool, metric: u32, forwarding_address: Ipv4addr, route_tag: u32 },
+    TrafficEngineering(Vec<TeTlv>),
+    Opaque(Vec<u8>),
+
+    // v3
+    RouterV3 { flags: u8, options: u32, interfaces: Vec<RouterInterface> },
+    NetworkV3 { options: u32, attached_routers: Vec<Ipv4addr> },
+    InterAreaPrefix { metric: u32, prefix: Ipv6Prefix },
+    InterAreaRouter { options: u32, metric: u32, destination_router: Ipv4addr },
+    // as external and nssa
+    ExternalV3 {
+        flags: u8,
+        metric: u32,
+        prefix: Ipv6Prefix,
+        forwarding_address: Option<Ipv6addr>,
+        route_tag: Option<u32>,
+        referenced_link_state_id: Option<u32>,
+    },
+    Link { priority: u8, options: u32, link_local_address: Ipv6addr, prefixes: Vec<Ipv6Prefix> },
+    IntraAreaPrefix { referenced_ls_type: u16, referenced_link_state_id: u32, referenced_advertising_router: Ipv4addr, prefixes: Vec<Ipv6Prefix> },
+
+    Unknown(Vec<u8>),
+}
+
+
+
+// the reader of a packet knows its ospf version
+impl Reader<'_, u8> {
+    fn version(&self) -> u8 {
+        self.context()
+    }
+
+    // bandwidths are ieee floats in bytes per second
+    fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    fn address(&mut self) -> Result<Ipv4addr> {
+        Ok(Ipv4addr(self.u32()?))
+    }
+
+    fn hello(&mut self) -> Result<Hello> {
+        let mut hello = Hello::default();
+
+        match self.version() {
+            2 => {
+                hello.network_mask = Some(self.address()?);
+                hello.hello_interval = self.u16()?;
+                hello.options = self.u8()? as u32;
+                hello.priority = self.u8()?;
+                hello.dead_interval = self.u32()?;
+            },
+            _ => {
+                hello.interface_id = Some(self.u32()?);
+                hello.priority = self.u8()?;
+                hello.options = self.u24()?;
+                hello.hello_interval = self.u16()?;
+                hello.dead_interval = self.u16()? as u32;
+            },
+        }
+        hello.designated_router = self.address()?;
+        hello.backup_designated_router = self.address()?;
+        hello.neighbors = self.until_empty(Self::address)?;
+
+        Ok(hello)
+    }
+
+    fn database_description(&mut self) -> Result<DatabaseDescription> {
+        let (interface_mtu, options, flags) = match self.version() {
+            2 => (self.u16()?, self.u8()? as u32, self.u8()?),
+            _ => {
+                self.u8()?;
+                let options = self.u24()?;
+                let interface_mtu = self.u16()?;
+                self.u8()?;
+                (interface_mtu, options, self.u8()?)
+            },
+        };
+
+        Ok(
+            DatabaseDescription {
+                interface_mtu,
+                options,
+                flags,
+                sequence: self.u32()?,
+                lsa_headers: self.until_empty(Self::lsa_header)?,
+            }
+        )
+    }
+
+    fn lsa_request(&mut self) -> Result<LsaRequest> {
+        let ls_type = match self.version() {
+            2 => self.u32()? as u16,
+            _ => (self.u32()? & 0xffff) as u16,
+        };
+
+        Ok(LsaRequest { ls_type, link_state_id: self.address()?, advertising_router: self.address()? })
+    }
+
+    fn lsa_header(&mut self) -> Result<LsaHeader> {
+        let age = self.u16()?;
+        let (options, ls_type) = match self.version() {
+            2 => (Some(self.u8()?), self.u8()? as u16),
+            _ => (None, self.u16()?),
+        };
+
+        Ok(
+            LsaHeader {
+                age,
+                options,
+                ls_type,
+                link_state_id: self.address()?,
+                advertising_router: self.address()?,
+                sequence: self.u32()?,
+                checksum: self.u16()?,
+                length: self.u16()?,
+            }
+        )
+    }
+
+    fn lsa(&mut self) -> Result<Lsa> {
+        let header = self.lsa_header()?;
+        let length = (header.length as usize)
+            .checked_sub(LsaHeader::LENGTH)
+            .ok_or(anyhow!("LSA length {} is shorter than its header", header.length))?;
+        let mut body = self.sub_reader(length)?;
+
+        let body = match self.version() {
+            2 => body.lsa_body(&header)?,
+            _ => body.lsa_body_v3(&header)?,
+        };
+
+        Ok(Lsa { header, body })
+    }
+
+    fn lsa_body(&mut self, header: &LsaHeader) -> Result<LsaBody> {
+        Ok(
+            match header.ls_type {
+                lsa_type::ROUTER => {
+                    let flags = self.u8()?;
+                    self.u8()?;
+                    let count = self.u16()?;
+                    let links = (0..count)
+                        .map(|_| {
+                            let link_id = self.address()?;
+                            let link_data = self.address()?;
+                            let link_type = self.u8()?;
+                            let tos_count = self.u8()?;
+                            let metric = self.u16()?;
+                            // the metrics of other types of service are obsolete
+                            self.take(tos_count as usize * 4)?;
+                            Ok(RouterLink { link_type, link_id, link_data, metric })
+                        })
+                        .collect::<Result<_>>()?;
+                    LsaBody::Router { flags, links }
+                },
+                lsa_type::NETWORK => LsaBody::Network { mask: self.address()?, attached_routers: self.until_empty(Self::address)? },
+                lsa_type::SUMMARY_NETWORK | lsa_type::SUMMARY_ASBR => {
+                    let mask = self.address()?;
+                    let metric = self.u32()? & 0x00ff_ffff;
+                    self.rest();
+                    LsaBody::Summary { mask, metric }
+                },
+                lsa_type::AS_EXTERNAL | lsa_type::NSSA => {
+                    let mask = self.address()?;
+                    let metric = self.u32()?;
+                    let forwarding_address = self.address()?;
+                    let route_tag = self.u32()?;
+                    self.rest();
+                    LsaBody::External { mask, type_2_metric: metric & 0x8000_0000 != 0, metric: metric & 0x00ff_ffff, forwarding_address, route_tag }
+                },
+                lsa_type::OPAQUE_LINK | lsa_type::OPAQUE_AREA | lsa_type::OPAQUE_AS => match header.opaque_type() {
+                    OPAQUE_TRAFFIC_ENGINEERING => LsaBody::TrafficEngineering(self.until_empty(Self::te_tlv)?),
+                    _ => LsaBody::Opaque(self.rest().to_vec()),
+                },
+                _ => LsaBody::Unknown(self.rest().to_vec()),
+            }
+        )
+    }
+
+    fn lsa_body_v3(&mut self, header: &LsaHeader) -> Result<LsaBody> {
+        Ok(
+            match header.ls_type {
+                lsa_type::V3_ROUTER => {
+                    let flags = self.u8()?;
+                    let options = self.u24()?;
+                    let interfaces = self.until_empty(|reader| {
+                        let interface_type = reader.u8()?;
+                        reader.u8()?;
+                        Ok(
+                            RouterInterface {
+                                interface_type,
+                                metric: reader.u16()?,
+                                interface_id: reader.u32()?,
+                                neighbor_interface_id: reader.u32()?,
+                                neighbor_router_id: reader.address()?,
+                            }
+                        )
+                    })?;
+                    LsaBody::RouterV3 { flags, options, interfaces }
+                },
+                lsa_type::V3_NETWORK => {
+                    self.u8()?;
+                    LsaBody::NetworkV3 { options: self.u24()?, attached_routers: self.until_empty(Self::address)? }
+                },
+                lsa_type::V3_INTER_AREA_PREFIX => {
+                    self.u8()?;
+                    LsaBody::InterAreaPrefix { metric: self.u24()?, prefix: self.ipv6_prefix()? }
+                },
+                lsa_type::V3_INTER_AREA_ROUTER => {
+                    self.u8()?;
+                    let options = self.u24()?;
+                    self.u8()?;
+                    LsaBody::InterAreaRouter { options, metric: self.u24()?, destination_router: self.address()? }
+                },
+                lsa_type::V3_AS_EXTERNAL | lsa_type::V3_NSSA => {
+                    const FORWARDING: u8 = 0x02;
+                    const TAG: u8 = 0x01;
+
+                    let flags = self.u8()?;
+                    let metric = self.u24()?;
+                    // the metric field of the prefix is the referenced ls type here
+                    let prefix = self.ipv6_prefix()?;
+                    let forwarding_address = match flags & FORWARDING {
+                        0 => None,
+                        _ => Some(Ipv6addr(self.take(16)?.to_u128())),
+                    };
+                    let route_tag = match flags & TAG {
+                        0 => None,
+                        _ => Some(self.u32()?),
+                    };
+                    let referenced_link_state_id = match prefix.metric {
+                        0 => None,
+                        _ => Some(self.u32()?),
+                    };
+                    LsaBody::ExternalV3 { flags, metric, prefix, forwarding_address, route_tag, referenced_link_state_id }
+                },
+                lsa_type::V3_LINK => {
+                    let priority = self.u8()?;
+                    let options = self.u24()?;
+                    let link_local_address = Ipv6addr(self.take(16)?.to_u128());
+                    let count = self.u32()?;
+                    let prefixes = (0..count).map(|_| self.ipv6_prefix()).collect::<Result<_>>()?;
+                    LsaBody::Link { priority, options, link_local_address, prefixes }
+                },
+                lsa_type::V3_INTRA_AREA_PREFIX => {
+                    let count = self.u16()?;
+                    let referenced_ls_type = self.u16()?;
+                    let referenced_link_state_id = self.u32()?;
+                    let referenced_advertising_router = self.address()?;
+                    let prefixes = (0..count).map(|_| self.ipv6_prefix()).collect::<Result<_>>()?;
+                    LsaBody::IntraAreaPrefix { referenced_ls_type, referenced_link_state_id, referenced_advertising_router, prefixes }
+                },
+                _ => LsaBody::Unknown(self.rest().to_vec()),
+            }
+        )
+    }
+
+    // the prefix is only as many 32 bit words as its length needs
+    fn ipv6_prefix(&mut self) -> Result<Ipv6Prefix> {
+        let length = self.u8()?;
+        if length > 128 {
+            return Err(anyhow!("OSPFv3 prefix length {length} is longer than 128"));
+        }
+        let options = self.u8()?;
+        let metric = self.u16()?;
+        let words = self.take((length as usize).div_ceil(32) * 4)?;
+        let mut address = [0; 16];
+        address[..words.len()].copy_from_slice(words);
+
+        Ok(Ipv6Prefix { length, options, metric, address: Ipv6addr(u128::from_be_bytes(address)) })
+    }
+
+    // tlvs are padded to 32 bits
+    fn tlv(&mut self) -> Result<(u16, Self)> {
+        let tlv_type = self.u16()?;
+        let length = self.u16()? as usize;
+        let value = self.sub_reader(length)?;
+        // the last tlv may leave out its padding
+        self.take((length.next_multiple_of(4) - length).min(self.remaining()))?;
+
+        Ok((tlv_type, value))
+    }
+
+    fn te_tlv(&mut self) -> Result<TeTlv> {
+        let (tlv_type, mut value) = self.tlv()?;
+
+        Ok(
+            match tlv_type {
+                1 => TeTlv::RouterAddress(value.address()?),
+                2 => TeTlv::Link(value.until_empty(Self::te_link_tlv)?),
+                tlv_type => TeTlv::Unknown { tlv_type, value: value.rest().to_vec() },
+            }
+        )
+    }
+
+    fn te_link_tlv(&mut self) -> Result<TeLinkTlv> {
+        let (tlv_type, mut value) = self.tlv()?;
+        Ok(
+            match tlv_type {
+                1 => TeLinkTlv::LinkType(value.u8()?),
+                2 => TeLinkTlv::LinkId(value.address()?),
+                3 => TeLinkTlv::LocalAddresses(value.until_empty(Self::address)?),
+                4 => TeLinkTlv::RemoteAddresses(value.until_empty(Self::address)?),
+                5 => TeLinkTlv::TeMetric(value.u32()?),
+                6 => TeLinkTlv::MaximumBandwidth(value.f32()?),
+                7 => TeLinkTlv::MaximumReservableBandwidth(value.f32()?),
+                8 => {
+                    let mut unreserved = [0.0; 8];
+                    for priority in &mut unreserved {
+                        *priority = value.f32()?;
+                    }
+                    TeLinkTlv::UnreservedBandwidth(unreserved)
+                },
+                9 => TeLinkTlv::AdministrativeGroup(value.u32()?),
+                tlv_type => TeLinkTlv::Unknown { tlv_type, value: value.rest().to_vec() },
+            }
+        )
+    }
+}
+
+
+
+pub struct OspfDissector;
+
+impl Dissector for OspfDissector {
+    fn name(&self) -> &str {
+        "ospf"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::NetworkLayer(NetworkLayer::OSPF(OSPF::from_bytes(bytes)?)))
+    }
+}
@@ -0,0 +1,344 @@
+use anyhow::{anyhow, Result};
+use byte_slice::{Bytes, SliceToUnsigned};
+
+use crate::packet::{dissector::{self, Context, Dissector, Key}, Layer, LayerTrait};
+
+use super::NetworkLayer;
+
+
+// Overlay encapsulations. Every one of them ends in a complete frame or packet that is dispatched again,
+// inner ethernet frames go through the ethertype of transparent ethernet bridging so they come out as ETHII.
+
+pub const ETHERTYPE_TEB: u16 = 0x6558;
+pub const ETHERTYPE_ERSPAN_II: u16 = 0x88be;
+pub const ETHERTYPE_ERSPAN_III: u16 = 0x22eb;
+
+
+
+// RFC 2784 and RFC 2890, version 1 is the enhanced gre of pptp from RFC 2637
+#[derive(Debug, Default, Clone)]
+pub struct GRE<'a> {
+    pub flags: u16, // C R K S s Recur A Flags, the lowest 3 bits are the version
+    pub protocol_type: u16, // an ethertype
+    pub checksum: Option<u16>,
+    pub key: Option<u32>,
+    pub sequence: Option<u32>,
+    // enhanced gre only
+    pub acknowledgment: Option<u32>,
+
+    pub next_layer: Box<Layer<'a>>,
+}
+
+impl<'a> GRE<'a> {
+    const CHECKSUM: u16 = 0x8000;
+    const KEY: u16 = 0x2000;
+    const SEQUENCE: u16 = 0x1000;
+    const ACKNOWLEDGMENT: u16 = 0x0080;
+
+    fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(4)?;
+
+        let flags = bytes[0..2].to_u16();
+        let protocol_type = bytes[2..4].to_u16();
+        let has = |flag: u16| flags & flag != 0;
+
+        let mut offset = 4;
+        let mut field = |present: bool| -> Result<Option<u32>> {
+            if !present {
+                return Ok(None);
+            }
+            bytes.require(offset + 4)?;
+            offset += 4;
+            Ok(Some(bytes[offset - 4..offset].to_u32()))
+        };
+
+        // the checksum is followed by 16 reserved bits
+        let checksum = field(has(Self::CHECKSUM))?.map(|checksum| (checksum >> 16) as u16);
+        let key = field(has(Self::KEY))?;
+        let sequence = field(has(Self::SEQUENCE))?;
+        let acknowledgment = field(flags & 0b111 == 1 && has(Self::ACKNOWLEDGMENT))?;
+        bytes.shift_first(offset)?;
+
+        let next_layer = Box::new(
+            match bytes.is_empty() {
+                true => Layer::NoLayer,
+                false => dissector::dispatch(Key::Ethertype(protocol_type), bytes)?,
+            }
+        );
+
+        Ok(
+            Self {
+                flags,
+                protocol_type,
+                checksum,
+                key,
+                sequence,
+                acknowledgment,
+                next_layer,
+            }
+        )
+    }
+
+    pub fn version(&self) -> u8 {
+        (self.flags & 0b111) as u8
+    }
+
+    // RFC 7637 nvgre carries ethernet with a key of a 24 bit virtual subnet id and an 8 bit flow id
+    pub fn nvgre(&self) -> Option<(u32, u8)> {
+        match self.protocol_type {
+            ETHERTYPE_TEB => self.key.map(|key| (key >> 8, key as u8)),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> LayerTrait for GRE<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+
+// cisco's encapsulated remote span, mirrored frames carried over gre
+#[derive(Debug, Default, Clone)]
+pub struct ERSPAN<'a> {
+    // 1 for type II, 2 for type III
+    pub version: u8,
+    pub vlan: u16,
+    pub cos: u8,
+    pub truncated: bool,
+    pub session_id: u16,
+    // type II
+    pub index: Option<u32>,
+    // type III
+    pub timestamp: Option<u32>,
+    pub security_group_tag: Option<u16>,
+    pub hardware_id: Option<u8>,
+
+    pub next_layer: Box<Layer<'a>>,
+}
+
+impl<'a> ERSPAN<'a> {
+    fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(8)?;
+
+        let version = bytes[0] >> 4;
+        let vlan = bytes[0..2].to_u16() & 0x0fff;
+        let cos = bytes[2] >> 5;
+        let truncated = bytes[2] & 0x04 != 0;
+        let session_id = bytes[2..4].to_u16() & 0x03ff;
+
+        let mut erspan = Self { version, vlan, cos, truncated, session_id, ..Default::default() };
+        let length = match version {
+            1 => {
+                erspan.index = Some(bytes[4..8].to_u32() & 0x000f_ffff);
+                8
+            },
+            2 => {
+                bytes.require(12)?;
+                erspan.timestamp = Some(bytes[4..8].to_u32());
+                erspan.security_group_tag = Some(bytes[8..10].to_u16());
+                erspan.hardware_id = Some(((bytes[10..12].to_u16() >> 4) & 0x3f) as u8);
+                // the optional platform specific subheader
+                match bytes[11] & 1 {
+                    1 => 20,
+                    _ => 12,
+                }
+            },
+            version => return Err(anyhow!("Unknown ERSPAN version {version}")),
+        };
+        bytes.require(length)?;
+        bytes.shift_first(length)?;
+
+        erspan.next_layer = Box::new(ethernet(bytes)?);
+
+        Ok(erspan)
+    }
+}
+
+impl<'a> LayerTrait for ERSPAN<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+
+// RFC 7348, udp port 4789
+#[derive(Debug, Default, Clone)]
+pub struct VXLAN<'a> {
+    pub flags: u8,
+    // 24 bits
+    pub vni: u32,
+
+    pub next_layer: Box<Layer<'a>>,
+}
+
+impl<'a> VXLAN<'a> {
+    const VALID_VNI: u8 = 0x08;
+
+    fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(8)?;
+
+        let flags = bytes[0];
+        if flags & Self::VALID_VNI == 0 {
+            return Err(anyhow!("VXLAN header without a valid VNI"));
+        }
+        let vni = bytes[4..7].to_u32();
+        bytes.shift_first(8)?;
+
+        Ok(
+            Self {
+                flags,
+                vni,
+                next_layer: Box::new(ethernet(bytes)?),
+            }
+        )
+    }
+}
+
+impl<'a> LayerTrait for VXLAN<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GeneveOption {
+    pub class: u16,
+    // the high bit marks options a tunnel endpoint has to understand
+    pub option_type: u8,
+    pub data: Vec<u8>,
+}
+
+impl GeneveOption {
+    pub fn is_critical(&self) -> bool {
+        self.option_type & 0x80 != 0
+    }
+}
+
+// RFC 8926, udp port 6081
+#[derive(Debug, Default, Clone)]
+pub struct Geneve<'a> {
+    pub version: u8,
+    // an oam frame that carries control messages
+    pub oam: bool,
+    pub critical_options: bool,
+    pub protocol_type: u16,
+    pub vni: u32,
+    pub options: Vec<GeneveOption>,
+
+    pub next_layer: Box<Layer<'a>>,
+}
+
+impl<'a> Geneve<'a> {
+    fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(8)?;
+
+        let version = bytes[0] >> 6;
+        if version != 0 {
+            return Err(anyhow!("Unknown Geneve version {version}"));
+        }
+        let options_length = (bytes[0] & 0x3f) as usize * 4;
+        let oam = bytes[1] & 0x80 != 0;
+        let critical_options = bytes[1] & 0x40 != 0;
+        let protocol_type = bytes[2..4].to_u16();
+        let vni = bytes[4..7].to_u32();
+        bytes.require(8 + options_length)?;
+
+        let mut options = Vec::new();
+        let mut data = &bytes[8..8 + options_length];
+        while !data.is_empty() {
+            let length = 4 + data.get(3).map_or(0, |length| (length & 0x1f) as usize * 4);
+            if data.len() < length {
+                return Err(anyhow!("Geneve option of {length} bytes is truncated"));
+            }
+            options.push(GeneveOption { class: data[0..2].to_u16(), option_type: data[2], data: data[4..length].to_vec() });
+            data = &data[length..];
+        }
+        bytes.shift_first(8 + options_length)?;
+
+        let next_layer = Box::new(
+            match bytes.is_empty() {
+                true => Layer::NoLayer,
+                false => dissector::dispatch(Key::Ethertype(protocol_type), bytes)?,
+            }
+        );
+
+        Ok(
+            Self {
+                version,
+                oam,
+                critical_options,
+                protocol_type,
+                vni,
+                options,
+                next_layer,
+            }
+        )
+    }
+}
+
+impl<'a> LayerTrait for Geneve<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+
+fn ethernet<'a>(bytes: &mut Bytes<'a>) -> Result<Layer<'a>> {
+    match bytes.is_empty() {
+        true => Ok(Layer::NoLayer),
+        false => dissector::dispatch(Key::Ethertype(ETHERTYPE_TEB), bytes),
+    }
+}
+
+
+
+pub struct GreDissector;
+
+impl Dissector for GreDissector {
+    fn name(&self) -> &str {
+        "gre"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::NetworkLayer(NetworkLayer::GRE(GRE::from_bytes(bytes)?)))
+    }
+}
+
+pub struct ErspanDissector;
+
+impl Dissector for ErspanDissector {
+    fn name(&self) -> &str {
+        "erspan"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::NetworkLayer(NetworkLayer::ERSPAN(ERSPAN::from_bytes(bytes)?)))
+    }
+}
+
+pub struct VxlanDissector;
+
+impl Dissector for VxlanDissector {
+    fn name(&self) -> &str {
+        "vxlan"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::NetworkLayer(NetworkLayer::VXLAN(VXLAN::from_bytes(bytes)?)))
+    }
+}
+
+pub struct GeneveDissector;
+
+impl Dissector for GeneveDissector {
+    fn name(&self) -> &str {
+        "geneve"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::NetworkLayer(NetworkLayer::Geneve(Geneve::from_bytes(bytes)?)))
+    }
+}
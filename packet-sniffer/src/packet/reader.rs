@@ -0,0 +1,117 @@
+use anyhow::{anyhow, Result};
+use byte_slice::SliceToUnsigned;
+
+
+// A cursor over a message whose fields follow one another with lengths taken from the message itself, like dns,
+// bgp, ospf or tls. Every read is bounds checked and fails with the protocol's name instead of panicking on a
+// message that is cut short. Protocols add the reads of their own structures in impl blocks of their modules,
+// a context like the ospf version rides along for those whose structures depend on it.
+
+
+
+pub struct Reader<'m, C = ()> {
+    // names the message in errors
+    protocol: &'static str,
+    message: &'m [u8],
+    offset: usize,
+    context: C,
+}
+
+impl<'m> Reader<'m> {
+    pub fn new(protocol: &'static str, message: &'m [u8]) -> Self {
+        Self::at(protocol, message, 0)
+    }
+
+    // starts at `offset`, for formats that point back into the message
+    pub fn at(protocol: &'static str, message: &'m [u8], offset: usize) -> Self {
+        Self {
+            protocol,
+            message,
+            offset,
+            context: (),
+        }
+    }
+}
+
+impl<'m, C: Copy> Reader<'m, C> {
+    pub fn with_context(protocol: &'static str, message: &'m [u8], context: C) -> Self {
+        Self {
+            protocol,
+            message,
+            offset: 0,
+            context,
+        }
+    }
+
+    pub fn context(&self) -> C {
+        self.context
+    }
+
+    // the whole message, including what was read already
+    pub fn message(&self) -> &'m [u8] {
+        self.message
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn seek(&mut self, offset: usize) {
+        self.offset = offset;
+    }
+
+    pub fn take(&mut self, length: usize) -> Result<&'m [u8]> {
+        let bytes = self.offset
+            .checked_add(length)
+            .and_then(|end| self.message.get(self.offset..end))
+            .ok_or(anyhow!("{} message is truncated at offset {}", self.protocol, self.offset))?;
+        self.offset += length;
+
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        Ok(self.take(2)?.to_u16())
+    }
+
+    pub fn u24(&mut self) -> Result<u32> {
+        Ok(self.take(3)?.to_u32())
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        Ok(self.take(4)?.to_u32())
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.message.len().saturating_sub(self.offset)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    // everything that was not read yet
+    pub fn rest(&mut self) -> &'m [u8] {
+        let rest = &self.message[self.offset.min(self.message.len())..];
+        self.offset = self.message.len();
+        rest
+    }
+
+    // reads items until the message ends, an item that does not fit is an error
+    pub fn until_empty<T>(&mut self, mut read: impl FnMut(&mut Self) -> Result<T>) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        while !self.is_empty() {
+            items.push(read(self)?);
+        }
+        Ok(items)
+    }
+
+    // a reader over the next `length` bytes, this one continues after them
+    pub fn sub_reader(&mut self, length: usize) -> Result<Self> {
+        Ok(Self::with_context(self.protocol, self.take(length)?, self.context))
+    }
+}
@@ -1,38 +1,56 @@
 use byte_slice::{Bytes, SliceToUnsigned};
+use crate::packet::dissector::{self, Context, Dissector, Key, Table};
 use anyhow::Result;
 
+use super::{Layer, LayerTrait};
+
+pub mod igmp;
+pub mod sctp;
+
 
 // https://www.iana.org/assignments/protocol-numbers/protocol-numbers.xhtml
 
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub enum TransportLayer<'a> {
     #[default]
     NULL,
 
-    UndefinedData(&'a Bytes<'a>),
+    UndefinedData(Bytes<'a>),
     ICMP(ICMP<'a>),
     TCP(TCP<'a>),
     UDP(UDP<'a>),
+    SCTP(sctp::SCTP<'a>),
+    IGMP(igmp::IGMP),
 }
 
 impl<'a> TransportLayer<'a> {
-    pub fn from_data(layer_type: u8, bytes: &'a mut Bytes) -> Result<Self> {
-        Ok(
-            match layer_type {
-                0x01 => Self::ICMP(ICMP::from_bytes(bytes)?),
-                0x06 => Self::TCP(TCP::from_bytes(bytes)?),
-                0x11 => Self::UDP(UDP::from_bytes(bytes)?),
-                _ => Self::UndefinedData(bytes)
-            }
-        )
+    // dissects with whatever is registered for the ip protocol, payloads that are not a transport layer stay data
+    pub fn from_data(layer_type: u8, bytes: &mut Bytes<'a>) -> Result<Self> {
+        let data = *bytes;
+
+        match dissector::dispatch(Key::IpProtocol(layer_type), bytes)? {
+            Layer::TransportLayer(layer) => Ok(layer),
+            _ => Ok(TransportLayer::UndefinedData(data)),
+        }
     }
 }
 
 
 
 
-#[derive(Debug, Default)]
+// empty payloads have no application layer
+fn application_layer<'a>(table: Table, port_src: u16, port_dst: u16, bytes: &mut Bytes<'a>) -> Layer<'a> {
+    match bytes.is_empty() {
+        true => Layer::NoLayer,
+        false => dissector::dispatch_ports(table, port_src, port_dst, bytes),
+    }
+}
+
+
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub enum MessageType {
     EchoReply             = 0,
     // covers many different types, 
@@ -125,7 +143,7 @@ impl From<u8> for MessageType {
 
 
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub enum MessageCode {
     // used when a type with multiple codes is provided but code is not defined
     UndefinedCode,
@@ -246,7 +264,7 @@ macro_rules! code_0_defined {
 }
 
 impl MessageCode {
-    fn from_type_and_code(message_type: &MessageType, message_code: u8) -> Self {
+    pub fn from_type_and_code(message_type: &MessageType, message_code: u8) -> Self {
         match message_type {
             MessageType::EchoReply =>                       code_0_defined!(MessageCode::EchoReply, message_code),
             MessageType::Unassigned =>                      code_0_defined!(MessageCode::Reserved, message_code),
@@ -326,45 +344,100 @@ impl MessageCode {
             }
         }
     }
+
+    // inverse of from_type_and_code, codes that are not listed above are 0
+    pub fn to_code(&self) -> u8 {
+        match self {
+            MessageCode::DestinationNetworkUnreachable =>               0,
+            MessageCode::DestinationHostUnreachable =>                  1,
+            MessageCode::DestinationProtocolUnreachable =>              2,
+            MessageCode::DestinationPortUnreachable =>                  3,
+            MessageCode::FragmentationIsNeededAndTheDfFlagSet =>        4,
+            MessageCode::SourceRouteFailed =>                           5,
+            MessageCode::DestinationNetworkUnknown =>                   6,
+            MessageCode::DestinationHostUnknown =>                      7,
+            MessageCode::SourceHostIsolated =>                          8,
+            MessageCode::NetworkAdministrativelyProhibited =>           9,
+            MessageCode::HostAdministrativelyProhibited =>              10,
+            MessageCode::NetworkUnreachableForTos =>                    11,
+            MessageCode::HostUnreachableForTos =>                       12,
+            MessageCode::CommunicationAdministrativelyProhibited =>     13,
+            MessageCode::HostPrecedenceViolation =>                     14,
+            MessageCode::PrecedenceCutoffInEffect =>                    15,
+
+            MessageCode::RedirectTheDatagramForTheHost =>               1,
+            MessageCode::RedirectTheDatagramForTheServiceAndNetwork =>  2,
+            MessageCode::RedirectTheDatagramForTheServiceAndHost =>     3,
+
+            MessageCode::FragmentReassemblyTimeExceeded =>              1,
+
+            MessageCode::MissingARequiredOption =>                      1,
+            MessageCode::BadLength =>                                   2,
+
+            MessageCode::MalformedQuery =>                              1,
+            MessageCode::NoSuchInterface =>                             2,
+            MessageCode::NoSuchEntry =>                                 3,
+            MessageCode::MultipleInterfacesSatisfyQuery =>              4,
+
+            _ => 0,
+        }
+    }
 }
 
 
 
 
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct ICMP<'a> {
     pub message_type: MessageType,
     pub code: MessageCode,
     pub checksum: u16,
+    pub rest_of_header: u32, // meaning depends on the message type, e.g. identifier and sequence number for echo
 
 
-    pub payload: &'a Bytes<'a>
+    pub payload: Bytes<'a>
 }
 
 impl<'a> ICMP<'a> {
-    fn from_bytes(bytes: &'a mut Bytes) -> Result<Self> {
+    fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(8)?;
+
         let message_type = MessageType::from(bytes[0]);
         let code = MessageCode::from_type_and_code(&message_type, bytes[1]);
-        let checksum = 0;
+        let checksum = bytes[2..4].to_u16();
+        let rest_of_header = bytes[4..8].to_u32();
+
+        bytes.shift_first(8)?;
 
         Ok(
             Self {
                 message_type,
                 code,
                 checksum,
+                rest_of_header,
 
-                payload: bytes
+                payload: *bytes
             }
         )
     }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8);
+        bytes.push(self.message_type as u8);
+        bytes.push(self.code.to_code());
+        bytes.extend(self.checksum.to_be_bytes());
+        bytes.extend(self.rest_of_header.to_be_bytes());
+
+        bytes
+    }
 }
 
 
 
 
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct TCP<'a> {
     pub port_src: u16,
     pub port_dst: u16,
@@ -379,11 +452,13 @@ pub struct TCP<'a> {
     // options
 
 
-    pub payload: &'a Bytes<'a>
+    pub payload: Bytes<'a>,
+    pub next_layer: Box<Layer<'a>>,
 }
 
 impl<'a> TCP<'a> {
-    pub fn from_bytes(bytes: &'a mut Bytes) -> Result<Self> {
+    pub fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(20)?;
 
         let port_src = bytes[0..2].to_u16();
         let port_dst = bytes[2..4].to_u16();
@@ -396,6 +471,10 @@ impl<'a> TCP<'a> {
         let checksum = bytes[16..18].to_u16();
         let urgent_ptr = bytes[18..20].to_u16();
 
+        // TODO parse options, for now they are skipped over
+        bytes.shift_first((data_offset as usize * 4).max(20))?;
+        let payload = *bytes;
+        let next_layer = Box::new(application_layer(Table::TcpPort, port_src, port_dst, bytes));
 
         Ok(
             Self {
@@ -409,35 +488,65 @@ impl<'a> TCP<'a> {
                 window,
                 checksum,
                 urgent_ptr,
-                payload: bytes,
-
-                ..Default::default()
+                payload,
+                next_layer,
             }
         )
     }
+
+    // header only, options are written as zeroed padding up to data_offset
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(20);
+        bytes.extend(self.port_src.to_be_bytes());
+        bytes.extend(self.port_dst.to_be_bytes());
+        bytes.extend(self.sequence_num.to_be_bytes());
+        bytes.extend(self.acknowledgement_num.to_be_bytes());
+        bytes.extend((((self.data_offset as u16) << 12) | (self.control_bits as u16 & 0x3f)).to_be_bytes());
+        bytes.extend(self.window.to_be_bytes());
+        bytes.extend(self.checksum.to_be_bytes());
+        bytes.extend(self.urgent_ptr.to_be_bytes());
+        bytes.resize((self.data_offset as usize * 4).max(20), 0);
+
+        bytes
+    }
 }
 
+impl<'a> LayerTrait for TCP<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
 
 
 
-#[derive(Debug, Default)]
+
+#[derive(Debug, Default, Clone)]
 pub struct UDP<'a> {
     pub port_src: u16,
     pub port_dst: u16,
     pub length: u16,
     pub checksum: u16,
 
-    pub payload: &'a Bytes<'a>,
+    pub payload: Bytes<'a>,
+    pub next_layer: Box<Layer<'a>>,
 }
 
 impl<'a> UDP<'a> {
-    fn from_bytes(bytes: &'a mut Bytes) -> Result<Self> {
+    fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(8)?;
+
         let port_src = bytes[0..2].to_u16();
         let port_dst = bytes[2..4].to_u16();
         let length = bytes[4..6].to_u16();
         let checksum = bytes[6..8].to_u16();
 
         bytes.shift_first(8)?;
+        // anything past the length is padding of the frame
+        if (8..bytes.len() + 8).contains(&(length as usize)) {
+            bytes.shift_last(bytes.len() + 8 - length as usize)?;
+        }
+        let payload = *bytes;
+        let next_layer = Box::new(application_layer(Table::UdpPort, port_src, port_dst, bytes));
 
         Ok(
             Self{
@@ -446,8 +555,65 @@ impl<'a> UDP<'a> {
                 length,
                 checksum,
 
-                payload: bytes
+                payload,
+                next_layer,
             }
         )
     }
-}
\ No newline at end of file
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8);
+        bytes.extend(self.port_src.to_be_bytes());
+        bytes.extend(self.port_dst.to_be_bytes());
+        bytes.extend(self.length.to_be_bytes());
+        bytes.extend(self.checksum.to_be_bytes());
+
+        bytes
+    }
+}
+
+impl<'a> LayerTrait for UDP<'a> {
+    fn next_layer(&self) -> &Layer<'_> {
+        &self.next_layer
+    }
+}
+
+
+
+
+
+pub struct IcmpDissector;
+
+impl Dissector for IcmpDissector {
+    fn name(&self) -> &str {
+        "icmp"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::TransportLayer(TransportLayer::ICMP(ICMP::from_bytes(bytes)?)))
+    }
+}
+
+pub struct TcpDissector;
+
+impl Dissector for TcpDissector {
+    fn name(&self) -> &str {
+        "tcp"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::TransportLayer(TransportLayer::TCP(TCP::from_bytes(bytes)?)))
+    }
+}
+
+pub struct UdpDissector;
+
+impl Dissector for UdpDissector {
+    fn name(&self) -> &str {
+        "udp"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::TransportLayer(TransportLayer::UDP(UDP::from_bytes(bytes)?)))
+    }
+}
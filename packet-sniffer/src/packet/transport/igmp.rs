@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use byte_slice::{Bytes, Ipv4addr, SliceToUnsigned};
+
+use crate::packet::{dissector::{Context, Dissector}, Layer};
+
+use super::TransportLayer;
+
+
+// IGMP (RFC 1112, RFC 2236, RFC 3376) is ip protocol 2. Every version shares the membership query type, its
+// version follows from the length of the message and the max response code: 8 bytes with a code of 0 is v1,
+// 8 bytes otherwise v2, 12 bytes or more v3.
+
+
+
+pub mod message_type {
+    pub const MEMBERSHIP_QUERY: u8 = 0x11;
+    pub const V1_MEMBERSHIP_REPORT: u8 = 0x12;
+    pub const V2_MEMBERSHIP_REPORT: u8 = 0x16;
+    pub const LEAVE_GROUP: u8 = 0x17;
+    pub const V3_MEMBERSHIP_REPORT: u8 = 0x22;
+}
+
+pub mod record_type {
+    // the current state, sent in answer to queries
+    pub const MODE_IS_INCLUDE: u8 = 1;
+    pub const MODE_IS_EXCLUDE: u8 = 2;
+    // changes of the filter mode
+    pub const CHANGE_TO_INCLUDE_MODE: u8 = 3;
+    pub const CHANGE_TO_EXCLUDE_MODE: u8 = 4;
+    // changes of the source list
+    pub const ALLOW_NEW_SOURCES: u8 = 5;
+    pub const BLOCK_OLD_SOURCES: u8 = 6;
+}
+
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub version: u8,
+    pub max_response_time: Duration,
+    // 0.0.0.0 for general queries
+    pub group: Ipv4addr,
+    // v3 only
+    pub suppress_router_processing: bool,
+    pub robustness: u8,
+    pub query_interval: Duration,
+    pub sources: Vec<Ipv4addr>,
+}
+
+impl Query {
+    pub fn is_general(&self) -> bool {
+        self.group == Ipv4addr(0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupRecord {
+    pub record_type: u8,
+    pub group: Ipv4addr,
+    pub sources: Vec<Ipv4addr>,
+    pub auxiliary_data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IgmpMessage {
+    Query(Query),
+    ReportV1(Ipv4addr),
+    ReportV2(Ipv4addr),
+    Leave(Ipv4addr),
+    ReportV3(Vec<GroupRecord>),
+    Unknown(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IGMP {
+    pub message_type: u8,
+    pub checksum: u16,
+    pub message: IgmpMessage,
+}
+
+impl IGMP {
+    pub fn from_bytes(bytes: &mut Bytes) -> Result<Self> {
+        bytes.require(8)?;
+
+        let message_type = bytes[0];
+        let code = bytes[1];
+        let checksum = bytes[2..4].to_u16();
+        let group = Ipv4addr(bytes[4..8].to_u32());
+
+        let (message, length) = match message_type {
+            message_type::MEMBERSHIP_QUERY if bytes.len() >= 12 => {
+                let sources = bytes[10..12].to_u16() as usize;
+                bytes.require(12 + sources * 4)?;
+
+                let query = Query {
+                    version: 3,
+                    max_response_time: Duration::from_millis(decode_time(code) as u64 * 100),
+                    group,
+                    suppress_router_processing: bytes[8] & 0x08 != 0,
+                    robustness: bytes[8] & 0x07,
+                    query_interval: Duration::from_secs(decode_time(bytes[9]) as u64),
+                    sources: addresses(&bytes[12..12 + sources * 4]),
+                };
+                (IgmpMessage::Query(query), 12 + sources * 4)
+            },
+            message_type::MEMBERSHIP_QUERY => {
+                let query = Query {
+                    version: match code { 0 => 1, _ => 2 },
+                    // v1 hosts answer within 10 seconds
+                    max_response_time: match code {
+                        0 => Duration::from_secs(10),
+                        code => Duration::from_millis(code as u64 * 100),
+                    },
+                    group,
+                    suppress_router_processing: false,
+                    robustness: 0,
+                    query_interval: Duration::ZERO,
+                    sources: Vec::new(),
+                };
+                (IgmpMessage::Query(query), 8)
+            },
+            message_type::V1_MEMBERSHIP_REPORT => (IgmpMessage::ReportV1(group), 8),
+            message_type::V2_MEMBERSHIP_REPORT => (IgmpMessage::ReportV2(group), 8),
+            message_type::LEAVE_GROUP => (IgmpMessage::Leave(group), 8),
+            message_type::V3_MEMBERSHIP_REPORT => {
+                let count = bytes[6..8].to_u16();
+                let mut records = *bytes;
+                records.shift_first(8)?;
+
+                let mut group_records = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    group_records.push(Self::group_record(&mut records)?);
+                }
+                (IgmpMessage::ReportV3(group_records), bytes.len() - records.len())
+            },
+            _ => (IgmpMessage::Unknown(bytes[4..].to_vec()), bytes.len()),
+        };
+        bytes.shift_first(length)?;
+
+        Ok(
+            Self {
+                message_type,
+                checksum,
+                message,
+            }
+        )
+    }
+
+    fn group_record(bytes: &mut Bytes) -> Result<GroupRecord> {
+        bytes.require(8)?;
+
+        let sources = bytes[2..4].to_u16() as usize;
+        // in 32 bit words
+        let auxiliary_length = bytes[1] as usize * 4;
+        let length = 8 + sources * 4 + auxiliary_length;
+        bytes.require(length)?;
+
+        let record = GroupRecord {
+            record_type: bytes[0],
+            group: Ipv4addr(bytes[4..8].to_u32()),
+            sources: addresses(&bytes[8..8 + sources * 4]),
+            auxiliary_data: bytes[8 + sources * 4..length].to_vec(),
+        };
+        bytes.shift_first(length)?;
+
+        Ok(record)
+    }
+}
+
+// codes from 128 on are a floating point number with a 3 bit exponent and a 4 bit mantissa
+fn decode_time(code: u8) -> u32 {
+    match code {
+        0..=127 => code as u32,
+        _ => ((code as u32 & 0x0f) | 0x10) << (((code as u32 >> 4) & 0x07) + 3),
+    }
+}
+
+fn addresses(bytes: &[u8]) -> Vec<Ipv4addr> {
+    bytes.chunks_exact(4).map(|address| Ipv4addr(address.to_u32())).collect()
+}
+
+
+
+pub struct IgmpDissector;
+
+impl Dissector for IgmpDissector {
+    fn name(&self) -> &str {
+        "igmp"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::TransportLayer(TransportLayer::IGMP(IGMP::from_bytes(bytes)?)))
+    }
+}
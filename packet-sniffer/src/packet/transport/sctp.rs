@@ -0,0 +1,542 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use byte_slice::{Bytes, Ipv4addr, Ipv6addr, SliceToUnsigned};
+
+use crate::{packet::{dissector::{Context, Dissector}, network::{IpAddress, NetworkLayer}, reader::Reader, Layer, Packet}, reassembly::Flow};
+
+use super::TransportLayer;
+
+
+// SCTP (RFC 9260) is ip protocol 132. A 12 byte common header with a crc32c over the whole packet is followed
+// by chunks padded to 4 bytes, user messages travel in DATA chunks on numbered streams and are fragmented
+// over consecutive TSNs when they are too large. SctpAssociations follows the associations of a capture,
+// counts what every stream carried and puts fragmented messages back together.
+
+
+
+pub mod chunk_type {
+    pub const DATA: u8 = 0;
+    pub const INIT: u8 = 1;
+    pub const INIT_ACK: u8 = 2;
+    pub const SACK: u8 = 3;
+    pub const HEARTBEAT: u8 = 4;
+    pub const HEARTBEAT_ACK: u8 = 5;
+    pub const ABORT: u8 = 6;
+    pub const SHUTDOWN: u8 = 7;
+    pub const SHUTDOWN_ACK: u8 = 8;
+    pub const ERROR: u8 = 9;
+    pub const COOKIE_ECHO: u8 = 10;
+    pub const COOKIE_ACK: u8 = 11;
+    pub const SHUTDOWN_COMPLETE: u8 = 14;
+    pub const FORWARD_TSN: u8 = 192;
+}
+
+// flags of data chunks
+const UNORDERED: u8 = 0x04;
+const BEGINNING: u8 = 0x02;
+const ENDING: u8 = 0x01;
+// abort and shutdown complete carry the tag of the receiver when set
+const TAG_REFLECTED: u8 = 0x01;
+
+
+
+// https://www.iana.org/assignments/sctp-parameters/sctp-parameters.xhtml#sctp-parameters-2
+#[derive(Debug, Clone)]
+pub enum Parameter<'a> {
+    Ipv4Address(Ipv4addr),
+    Ipv6Address(Ipv6addr),
+    StateCookie(Bytes<'a>),
+    // milliseconds the sender asks the cookie to live longer
+    CookiePreservative(u32),
+    HostName(String),
+    SupportedAddressTypes(Vec<u16>),
+    EcnCapable,
+    ForwardTsnSupported,
+    Unknown { parameter_type: u16, value: Bytes<'a> },
+}
+
+impl<'a> Parameter<'a> {
+    fn from_reader(reader: &mut Reader<'a>) -> Result<Self> {
+        let (parameter_type, value) = reader.tlv()?;
+
+        Ok(
+            match (parameter_type, value.len()) {
+                (5, 4) => Parameter::Ipv4Address(Ipv4addr(value[..].to_u32())),
+                (6, 16) => Parameter::Ipv6Address(Ipv6addr(value[..].to_u128())),
+                (7, _) => Parameter::StateCookie(value),
+                (9, 4) => Parameter::CookiePreservative(value[..].to_u32()),
+                (11, _) => Parameter::HostName(String::from_utf8_lossy(&value[..]).trim_end_matches('\0').to_owned()),
+                (12, _) => Parameter::SupportedAddressTypes(value[..].chunks_exact(2).map(|address_type| address_type.to_u16()).collect()),
+                (0x8000, _) => Parameter::EcnCapable,
+                (0xc000, _) => Parameter::ForwardTsnSupported,
+                (parameter_type, _) => Parameter::Unknown { parameter_type, value },
+            }
+        )
+    }
+}
+
+// https://www.iana.org/assignments/sctp-parameters/sctp-parameters.xhtml#sctp-parameters-24
+#[derive(Debug, Clone)]
+pub struct ErrorCause<'a> {
+    pub code: u16,
+    pub information: Bytes<'a>,
+}
+
+impl ErrorCause<'_> {
+    pub fn code_name(&self) -> &'static str {
+        match self.code {
+            1 => "Invalid Stream Identifier",
+            2 => "Missing Mandatory Parameter",
+            3 => "Stale Cookie Error",
+            4 => "Out of Resource",
+            5 => "Unresolvable Address",
+            6 => "Unrecognized Chunk Type",
+            7 => "Invalid Mandatory Parameter",
+            8 => "Unrecognized Parameters",
+            9 => "No User Data",
+            10 => "Cookie Received While Shutting Down",
+            11 => "Restart of an Association with New Addresses",
+            12 => "User Initiated Abort",
+            13 => "Protocol Violation",
+            _ => "Unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Data<'a> {
+    pub unordered: bool,
+    pub beginning: bool,
+    pub ending: bool,
+    pub tsn: u32,
+    pub stream_id: u16,
+    pub stream_sequence: u16,
+    // the payload protocol identifier, e.g. 3 for M3UA, 46 for Diameter, 18 for S1AP
+    pub payload_protocol: u32,
+    pub user_data: Bytes<'a>,
+}
+
+// init and init ack
+#[derive(Debug, Clone)]
+pub struct Init<'a> {
+    // the verification tag the peer puts in every packet it sends
+    pub initiate_tag: u32,
+    pub receiver_window: u32,
+    pub outbound_streams: u16,
+    pub inbound_streams: u16,
+    pub initial_tsn: u32,
+    pub parameters: Vec<Parameter<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sack {
+    pub cumulative_tsn_ack: u32,
+    pub receiver_window: u32,
+    // offsets from the cumulative tsn ack of blocks received after a gap
+    pub gap_blocks: Vec<(u16, u16)>,
+    pub duplicate_tsns: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Chunk<'a> {
+    Data(Data<'a>),
+    Init(Init<'a>),
+    InitAck(Init<'a>),
+    Sack(Sack),
+    Heartbeat(Bytes<'a>),
+    HeartbeatAck(Bytes<'a>),
+    Abort { tag_reflected: bool, causes: Vec<ErrorCause<'a>> },
+    Shutdown { cumulative_tsn_ack: u32 },
+    ShutdownAck,
+    Error(Vec<ErrorCause<'a>>),
+    CookieEcho(Bytes<'a>),
+    CookieAck,
+    ShutdownComplete { tag_reflected: bool },
+    // the streams and their last sequence numbers the receiver should stop waiting for
+    ForwardTsn { new_cumulative_tsn: u32, streams: Vec<(u16, u16)> },
+    Unknown { chunk_type: u8, flags: u8, value: Bytes<'a> },
+}
+
+impl<'a> Chunk<'a> {
+    fn from_reader(reader: &mut Reader<'a>) -> Result<Self> {
+        let chunk_type = reader.u8()?;
+        let flags = reader.u8()?;
+        let length = reader.u16()? as usize;
+        if length < 4 {
+            return Err(anyhow!("SCTP chunk length {length} is shorter than its header"));
+        }
+
+        let mut value = reader.sub_reader(length - 4)?;
+        // the last chunk may go without its padding
+        reader.take(padding(length).min(reader.remaining()))?;
+
+        let chunk = match chunk_type {
+            chunk_type::DATA => Chunk::Data(Data {
+                unordered: flags & UNORDERED != 0,
+                beginning: flags & BEGINNING != 0,
+                ending: flags & ENDING != 0,
+                tsn: value.u32()?,
+                stream_id: value.u16()?,
+                stream_sequence: value.u16()?,
+                payload_protocol: value.u32()?,
+                user_data: value.bytes(),
+            }),
+            chunk_type::INIT | chunk_type::INIT_ACK => {
+                let init = Init {
+                    initiate_tag: value.u32()?,
+                    receiver_window: value.u32()?,
+                    outbound_streams: value.u16()?,
+                    inbound_streams: value.u16()?,
+                    initial_tsn: value.u32()?,
+                    parameters: value.until_empty(Parameter::from_reader)?,
+                };
+                match chunk_type {
+                    chunk_type::INIT => Chunk::Init(init),
+                    _ => Chunk::InitAck(init),
+                }
+            },
+            chunk_type::SACK => {
+                let cumulative_tsn_ack = value.u32()?;
+                let receiver_window = value.u32()?;
+                let gap_blocks = value.u16()?;
+                let duplicate_tsns = value.u16()?;
+                Chunk::Sack(Sack {
+                    cumulative_tsn_ack,
+                    receiver_window,
+                    gap_blocks: (0..gap_blocks).map(|_| Ok((value.u16()?, value.u16()?))).collect::<Result<_>>()?,
+                    duplicate_tsns: (0..duplicate_tsns).map(|_| value.u32()).collect::<Result<_>>()?,
+                })
+            },
+            // the heartbeat info parameter is opaque to everyone but its sender
+            chunk_type::HEARTBEAT | chunk_type::HEARTBEAT_ACK => {
+                let (_, info) = value.tlv()?;
+                match chunk_type {
+                    chunk_type::HEARTBEAT => Chunk::Heartbeat(info),
+                    _ => Chunk::HeartbeatAck(info),
+                }
+            },
+            chunk_type::ABORT => Chunk::Abort { tag_reflected: flags & TAG_REFLECTED != 0, causes: value.until_empty(Reader::error_cause)? },
+            chunk_type::SHUTDOWN => Chunk::Shutdown { cumulative_tsn_ack: value.u32()? },
+            chunk_type::SHUTDOWN_ACK => Chunk::ShutdownAck,
+            chunk_type::ERROR => Chunk::Error(value.until_empty(Reader::error_cause)?),
+            chunk_type::COOKIE_ECHO => Chunk::CookieEcho(value.bytes()),
+            chunk_type::COOKIE_ACK => Chunk::CookieAck,
+            chunk_type::SHUTDOWN_COMPLETE => Chunk::ShutdownComplete { tag_reflected: flags & TAG_REFLECTED != 0 },
+            chunk_type::FORWARD_TSN => Chunk::ForwardTsn {
+                new_cumulative_tsn: value.u32()?,
+                streams: value.rest().chunks_exact(4).map(|stream| (stream[0..2].to_u16(), stream[2..4].to_u16())).collect(),
+            },
+            chunk_type => Chunk::Unknown { chunk_type, flags, value: value.bytes() },
+        };
+
+        Ok(chunk)
+    }
+}
+
+fn padding(length: usize) -> usize {
+    (4 - length % 4) % 4
+}
+
+impl<'a> Reader<'a> {
+    // the rest of the message, chunks keep pointing into the packet
+    fn bytes(&mut self) -> Bytes<'a> {
+        Bytes::from_slice(self.rest())
+    }
+
+    // parameters and error causes share their layout with chunks, a type, a length including the header and padding
+    fn tlv(&mut self) -> Result<(u16, Bytes<'a>)> {
+        let tlv_type = self.u16()?;
+        let length = self.u16()? as usize;
+        if length < 4 {
+            return Err(anyhow!("SCTP parameter length {length} is shorter than its header"));
+        }
+
+        let value = Bytes::from_slice(self.take(length - 4)?);
+        self.take(padding(length).min(self.remaining()))?;
+
+        Ok((tlv_type, value))
+    }
+
+    fn error_cause(&mut self) -> Result<ErrorCause<'a>> {
+        let (code, information) = self.tlv()?;
+        Ok(ErrorCause { code, information })
+    }
+}
+
+
+
+#[derive(Debug, Default, Clone)]
+pub struct SCTP<'a> {
+    pub port_src: u16,
+    pub port_dst: u16,
+    pub verification_tag: u32,
+    // the crc32c is sent least significant byte first
+    pub checksum: u32,
+    // some stacks leave the checksum to the nic and capture it as 0
+    pub checksum_valid: bool,
+    pub chunks: Vec<Chunk<'a>>,
+}
+
+impl<'a> SCTP<'a> {
+    pub fn from_bytes(bytes: &mut Bytes<'a>) -> Result<Self> {
+        bytes.require(12)?;
+
+        let port_src = bytes[0..2].to_u16();
+        let port_dst = bytes[2..4].to_u16();
+        let verification_tag = bytes[4..8].to_u32();
+        let checksum = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+        let checksum_valid = Self::checksum(&bytes[..]) == checksum;
+        bytes.shift_first(12)?;
+
+        let chunks = Reader::new("SCTP", bytes.as_slice()).until_empty(Chunk::from_reader)?;
+        bytes.shift_first(bytes.len())?;
+
+        Ok(
+            Self {
+                port_src,
+                port_dst,
+                verification_tag,
+                checksum,
+                checksum_valid,
+                chunks,
+            }
+        )
+    }
+
+    // the crc32c of a packet with its checksum field zeroed
+    pub fn checksum(packet: &[u8]) -> u32 {
+        let crc = crc32c::crc32c(&packet[..8.min(packet.len())]);
+        let crc = crc32c::crc32c_append(crc, &[0; 4]);
+        crc32c::crc32c_append(crc, packet.get(12..).unwrap_or_default())
+    }
+
+    pub fn data(&self) -> impl Iterator<Item = &Data<'a>> {
+        self.chunks.iter().filter_map(|chunk| match chunk {
+            Chunk::Data(data) => Some(data),
+            _ => None,
+        })
+    }
+}
+
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AssociationState {
+    // an init was sent
+    #[default]
+    CookieWait,
+    CookieEchoed,
+    Established,
+    ShuttingDown,
+    Closed,
+}
+
+// a user message, put back together when it was fragmented
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserMessage {
+    pub stream_id: u16,
+    pub stream_sequence: u16,
+    pub payload_protocol: u32,
+    pub unordered: bool,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StreamStatistics {
+    pub messages: usize,
+    pub bytes: usize,
+    // ordered messages whose stream sequence number skipped ahead of the last one
+    pub sequence_gaps: usize,
+    pub last_sequence: Option<u16>,
+    pub payload_protocols: Vec<u32>,
+}
+
+impl StreamStatistics {
+    fn count(&mut self, message: &UserMessage) {
+        self.messages += 1;
+        self.bytes += message.data.len();
+        if !message.unordered {
+            if self.last_sequence.is_some_and(|last| message.stream_sequence != last.wrapping_add(1)) {
+                self.sequence_gaps += 1;
+            }
+            self.last_sequence = Some(message.stream_sequence);
+        }
+        if !self.payload_protocols.contains(&message.payload_protocol) {
+            self.payload_protocols.push(message.payload_protocol);
+        }
+    }
+}
+
+// a fragment waiting for the rest of its message
+#[derive(Debug, Clone)]
+struct Fragment {
+    beginning: bool,
+    ending: bool,
+    message: UserMessage,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Association {
+    pub state: AssociationState,
+    // the tags each side expects in the packets sent to it, from the init and init ack
+    pub initiator_tag: Option<u32>,
+    pub responder_tag: Option<u32>,
+    pub packets: usize,
+    // by the direction the stream is sent in and its id
+    pub streams: HashMap<(Flow, u16), StreamStatistics>,
+    // tsns already seen per direction, retransmitted data is only counted once
+    tsns: HashSet<(Flow, u32)>,
+    fragments: HashMap<(Flow, u32), Fragment>,
+}
+
+impl Association {
+    // the complete user messages the chunk finished
+    fn push(&mut self, flow: Flow, data: &Data) -> Option<UserMessage> {
+        if !self.tsns.insert((flow, data.tsn)) {
+            return None;
+        }
+
+        let message = UserMessage {
+            stream_id: data.stream_id,
+            stream_sequence: data.stream_sequence,
+            payload_protocol: data.payload_protocol,
+            unordered: data.unordered,
+            data: data.user_data[..].to_vec(),
+        };
+        let message = match (data.beginning, data.ending) {
+            (true, true) => message,
+            _ => {
+                self.fragments.insert((flow, data.tsn), Fragment { beginning: data.beginning, ending: data.ending, message });
+                self.reassemble(flow, data.tsn)?
+            },
+        };
+
+        self.streams.entry((flow, message.stream_id)).or_default().count(&message);
+        Some(message)
+    }
+
+    // fragments of a message take consecutive tsns from the one flagged beginning to the one flagged ending
+    fn reassemble(&mut self, flow: Flow, tsn: u32) -> Option<UserMessage> {
+        // running into the end or the beginning of another message means fragments are still missing
+        let mut first = tsn;
+        while !self.fragments.get(&(flow, first))?.beginning {
+            first = first.wrapping_sub(1);
+            if self.fragments.get(&(flow, first))?.ending {
+                return None;
+            }
+        }
+        let mut last = tsn;
+        while !self.fragments.get(&(flow, last))?.ending {
+            last = last.wrapping_add(1);
+            if self.fragments.get(&(flow, last))?.beginning {
+                return None;
+            }
+        }
+
+        let mut message = self.fragments.remove(&(flow, first))?.message;
+        let mut tsn = first;
+        while tsn != last {
+            tsn = tsn.wrapping_add(1);
+            message.data.extend(self.fragments.remove(&(flow, tsn))?.message.data);
+        }
+
+        Some(message)
+    }
+}
+
+// follows the sctp associations of a capture, keyed by the flow from the side that started them
+#[derive(Debug, Default, Clone)]
+pub struct SctpAssociations {
+    associations: HashMap<Flow, Association>,
+}
+
+impl SctpAssociations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the user messages the packet completed with the direction they were sent in
+    pub fn observe(&mut self, packet: &Packet) -> Vec<(Flow, UserMessage)> {
+        let Some((flow, sctp)) = Self::of_packet(packet) else {
+            return Vec::new();
+        };
+
+        let starts = sctp.chunks.iter().any(|chunk| matches!(chunk, Chunk::Init(_)));
+        let key = match (self.associations.contains_key(&flow.reversed()), starts) {
+            (true, false) => flow.reversed(),
+            _ => flow,
+        };
+        // an init restarts an association that was known before
+        if starts {
+            self.associations.remove(&flow.reversed());
+            self.associations.insert(flow, Association::default());
+        }
+        let association = self.associations.entry(key).or_default();
+        association.packets += 1;
+
+        let mut messages = Vec::new();
+        for chunk in &sctp.chunks {
+            match chunk {
+                Chunk::Init(init) => association.initiator_tag = Some(init.initiate_tag),
+                Chunk::InitAck(init) => association.responder_tag = Some(init.initiate_tag),
+                Chunk::CookieEcho(_) => association.state = AssociationState::CookieEchoed,
+                Chunk::CookieAck => association.state = AssociationState::Established,
+                Chunk::Shutdown { .. } | Chunk::ShutdownAck => association.state = AssociationState::ShuttingDown,
+                Chunk::ShutdownComplete { .. } | Chunk::Abort { .. } => association.state = AssociationState::Closed,
+                Chunk::Data(data) => {
+                    // associations whose handshake was not captured are taken as established
+                    if association.state == AssociationState::CookieWait && association.initiator_tag.is_none() {
+                        association.state = AssociationState::Established;
+                    }
+                    messages.extend(association.push(flow, data).map(|message| (flow, message)));
+                },
+                _ => {},
+            }
+        }
+
+        messages
+    }
+
+    // the association between the two sides of the flow, whichever started it
+    pub fn get(&self, flow: &Flow) -> Option<&Association> {
+        self.associations.get(flow).or_else(|| self.associations.get(&flow.reversed()))
+    }
+
+    pub fn associations(&self) -> impl Iterator<Item = (&Flow, &Association)> {
+        self.associations.iter()
+    }
+
+    // the innermost sctp packet and the flow the ip header around it belongs to
+    fn of_packet<'p>(packet: &'p Packet) -> Option<(Flow, &'p SCTP<'p>)> {
+        let mut addresses = None;
+        let mut found = None;
+
+        for layer in packet.layers() {
+            match layer {
+                Layer::NetworkLayer(NetworkLayer::Ipv4(ipv4)) => addresses = Some((IpAddress::V4(ipv4.address_src), IpAddress::V4(ipv4.address_dst))),
+                Layer::NetworkLayer(NetworkLayer::Ipv6(ipv6)) => addresses = Some((IpAddress::V6(ipv6.address_src), IpAddress::V6(ipv6.address_dst))),
+                Layer::TransportLayer(TransportLayer::SCTP(sctp)) => if let Some((address_src, address_dst)) = addresses {
+                    found = Some((Flow { address_src, port_src: sctp.port_src, address_dst, port_dst: sctp.port_dst }, sctp));
+                },
+                _ => {},
+            }
+        }
+
+        found
+    }
+}
+
+
+
+pub struct SctpDissector;
+
+impl Dissector for SctpDissector {
+    fn name(&self) -> &str {
+        "sctp"
+    }
+
+    fn dissect<'a>(&self, bytes: &mut Bytes<'a>, _: &Context) -> Result<Layer<'a>> {
+        Ok(Layer::TransportLayer(TransportLayer::SCTP(SCTP::from_bytes(bytes)?)))
+    }
+}
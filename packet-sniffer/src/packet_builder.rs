@@ -1,14 +1,387 @@
-use std::io::Write;
+use std::{io::Write, path::Path};
 
-use crate::packet::{data_link::{DataLinkLayer, ETHII}, network::NetworkLayer, transport::TransportLayer, *};
-use anyhow::Result;
-use crossterm::{cursor, event::{Event, KeyCode}, execute, terminal};
+use crate::{craft::{Craft, CraftLayer}, inject::{self, SendOptions, Target}, pcap, packet::{data_link::{MacHeader, Tag802_1Q, VlanTag, TPID_802_1AD}, network::{Ipv4, Ipv6, ARP}, transport::{MessageCode, MessageType, ICMP, TCP, UDP}, *}};
+use anyhow::{anyhow, Result};
+use byte_slice::{Bytes, Hex, Ipv4addr, Ipv6addr, MacAddress};
+use crossterm::{cursor, event::{Event, KeyCode}, queue, terminal};
+use self_cell::self_cell;
 
 
-macro_rules! move_cursor_to {
-    ($column:expr, $row:expr) => {
-        print!("\x1B[{};{}H", $column, $row)
+const HELP: &str = r"\a <eth|vlan|qinq|ipv4|ipv6|arp|tcp|udp|icmp|raw> add  \r remove  \mu \md move  \v verbatim  \w <file> save  \o <file> open  \x <hex> import  \p <pcap> [n] import  \s <iface|tap:name> [count= interval= pps= mbps=] send  \l layers  \d debug  \q finish";
+
+
+
+// parses a decimal or 0x prefixed hex number that has to fit in `bits` bits
+fn parse_number(value: &str, bits: u32) -> Result<u128> {
+    let value = value.trim();
+    let number = match value.strip_prefix("0x") {
+        Some(hex) => u128::from_str_radix(hex, 16),
+        None => value.parse::<u128>(),
+    }
+    .map_err(|_| anyhow!("{value} is not a number"))?;
+
+    match bits >= 128 || number >> bits == 0 {
+        true => Ok(number),
+        false => Err(anyhow!("{value} does not fit in {bits} bits")),
+    }
+}
+
+fn parse_hex_stream(value: &str) -> Result<Vec<u8>> {
+    let value: String = value.split_whitespace().collect();
+    let value = value.strip_prefix("0x").unwrap_or(&value);
+
+    match value.len().is_multiple_of(2) && value.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => Ok(value.hex_stream_to_vec()),
+        false => Err(anyhow!("Payload must be an even number of hex digits")),
+    }
+}
+
+macro_rules! set_number {
+    ($field:expr, $value:expr, $bits:expr) => {
+        $field = parse_number($value, $bits)? as _
+    };
+}
+
+
+
+fn layer_from_name(name: &str) -> Result<CraftLayer<'static>> {
+    Ok(
+        match name.to_lowercase().as_str() {
+            "eth" | "ethernet" => CraftLayer::Ether(MacHeader::default()),
+            "vlan" | "dot1q" => CraftLayer::Dot1Q(VlanTag::default()),
+            "qinq" | "stag" | "dot1ad" => CraftLayer::Dot1Q(VlanTag::new(TPID_802_1AD, Tag802_1Q::default())),
+            "ipv4" | "ip" => CraftLayer::Ipv4(Ipv4 { version: 4, IHL: 5, ttl: 64, ..Default::default() }),
+            "ipv6" => CraftLayer::Ipv6(Ipv6 { version: 6, hop_limit: 64, ..Default::default() }),
+            "arp" => CraftLayer::Arp(ARP { hardware_type: 1, protocol_type: 0x0800, hardware_len: 6, protocol_len: 4, operation: 1, ..Default::default() }),
+            "tcp" => CraftLayer::Tcp(TCP { data_offset: 5, ..Default::default() }),
+            "udp" => CraftLayer::Udp(UDP::default()),
+            "icmp" => CraftLayer::Icmp(ICMP { message_type: MessageType::EchoRequest, code: MessageCode::EchoRequest, ..Default::default() }),
+            "raw" | "payload" => CraftLayer::Raw(Vec::new()),
+            "802.1q" => CraftLayer::Dot1Q(VlanTag::default()),
+            "802.1ad" => CraftLayer::Dot1Q(VlanTag::new(TPID_802_1AD, Tag802_1Q::default())),
+            _ => return Err(anyhow!("Unknown layer {name}")),
+        }
+    )
+}
+
+// editing support for the layers of the stack
+trait LayerFields {
+    fn name(&self) -> &str;
+    fn fields(&self) -> Vec<(&'static str, String)>;
+    // validates and stores `value` into the field at index `field` of `fields()`
+    fn set_field(&mut self, field: usize, value: &str) -> Result<()>;
+}
+
+impl LayerFields for CraftLayer<'static> {
+    fn name(&self) -> &str {
+        match self {
+            CraftLayer::Ether(_) => "Ethernet",
+            CraftLayer::Dot1Q(tag) if tag.tpid == TPID_802_1AD => "802.1ad",
+            CraftLayer::Dot1Q(_) => "802.1Q",
+            CraftLayer::Ipv4(_) => "Ipv4",
+            CraftLayer::Ipv6(_) => "Ipv6",
+            CraftLayer::Arp(_) => "ARP",
+            CraftLayer::Tcp(_) => "TCP",
+            CraftLayer::Udp(_) => "UDP",
+            CraftLayer::Icmp(_) => "ICMP",
+            CraftLayer::Raw(_) => "Payload",
+        }
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        match self {
+            CraftLayer::Ether(mac_header) => vec![
+                ("address_dst", mac_header.address_dst.to_string()),
+                ("address_src", mac_header.address_src.to_string()),
+                ("ethertype", format!("0x{:04x}", mac_header.ethertype)),
+            ],
+            CraftLayer::Dot1Q(tag) => vec![
+                ("TPID", format!("0x{:04x}", tag.tpid)),
+                ("PCP", tag.tci.pcp().to_string()),
+                ("DEI", tag.tci.dei().to_string()),
+                ("VID", tag.tci.vid().to_string()),
+            ],
+            CraftLayer::Ipv4(ipv4) => vec![
+                ("version", ipv4.version.to_string()),
+                ("IHL", ipv4.IHL.to_string()),
+                ("DSPC", ipv4.DSPC.to_string()),
+                ("total_length", ipv4.total_length.to_string()),
+                ("identification", format!("0x{:04x}", ipv4.identification)),
+                ("flags", ipv4.flags.to_string()),
+                ("fragment_offset", ipv4.fragment_offset.to_string()),
+                ("ttl", ipv4.ttl.to_string()),
+                ("protocol", ipv4.protocol.to_string()),
+                ("header_checksum", format!("0x{:04x}", ipv4.header_checksum)),
+                ("address_src", ipv4.address_src.to_string()),
+                ("address_dst", ipv4.address_dst.to_string()),
+            ],
+            CraftLayer::Ipv6(ipv6) => vec![
+                ("version", ipv6.version.to_string()),
+                ("traffic_class", ipv6.traffic_class.to_string()),
+                ("flow_label", format!("0x{:05x}", ipv6.flow_label)),
+                ("payload_length", ipv6.payload_length.to_string()),
+                ("next_header", ipv6.next_header.to_string()),
+                ("hop_limit", ipv6.hop_limit.to_string()),
+                ("address_src", ipv6.address_src.to_string()),
+                ("address_dst", ipv6.address_dst.to_string()),
+            ],
+            CraftLayer::Arp(arp) => vec![
+                ("hardware_type", arp.hardware_type.to_string()),
+                ("protocol_type", format!("0x{:04x}", arp.protocol_type)),
+                ("hardware_len", arp.hardware_len.to_string()),
+                ("protocol_len", arp.protocol_len.to_string()),
+                ("operation", arp.operation.to_string()),
+                ("sender_hardware_address", arp.sender_hardware_address.to_string()),
+                ("sender_protocol_address", arp.sender_protocol_address.to_string()),
+                ("target_hardware_address", arp.target_hardware_address.to_string()),
+                ("target_protocol_address", arp.target_protocol_address.to_string()),
+            ],
+            CraftLayer::Tcp(tcp) => vec![
+                ("port_src", tcp.port_src.to_string()),
+                ("port_dst", tcp.port_dst.to_string()),
+                ("sequence_num", tcp.sequence_num.to_string()),
+                ("acknowledgement_num", tcp.acknowledgement_num.to_string()),
+                ("data_offset", tcp.data_offset.to_string()),
+                ("control_bits", format!("0x{:02x}", tcp.control_bits)),
+                ("window", tcp.window.to_string()),
+                ("checksum", format!("0x{:04x}", tcp.checksum)),
+                ("urgent_ptr", tcp.urgent_ptr.to_string()),
+            ],
+            CraftLayer::Udp(udp) => vec![
+                ("port_src", udp.port_src.to_string()),
+                ("port_dst", udp.port_dst.to_string()),
+                ("length", udp.length.to_string()),
+                ("checksum", format!("0x{:04x}", udp.checksum)),
+            ],
+            CraftLayer::Icmp(icmp) => vec![
+                ("message_type", format!("{} ({:?})", icmp.message_type as u8, icmp.message_type)),
+                ("code", format!("{} ({:?})", icmp.code.to_code(), icmp.code)),
+                ("checksum", format!("0x{:04x}", icmp.checksum)),
+                ("rest_of_header", format!("0x{:08x}", icmp.rest_of_header)),
+            ],
+            CraftLayer::Raw(payload) => vec![
+                ("data", payload.iter().map(|byte| format!("{byte:02x}")).collect()),
+            ],
+        }
+    }
+
+    fn set_field(&mut self, field: usize, value: &str) -> Result<()> {
+        match self {
+            CraftLayer::Ether(mac_header) => match field {
+                0 => mac_header.address_dst = value.parse()?,
+                1 => mac_header.address_src = value.parse()?,
+                2 => set_number!(mac_header.ethertype, value, 16),
+                _ => return Err(anyhow!("No field {field}")),
+            },
+            CraftLayer::Dot1Q(tag) => {
+                let (pcp, dei, vid) = (tag.tci.pcp() as u16, tag.tci.dei() as u16, tag.tci.vid());
+                match field {
+                    0 => set_number!(tag.tpid, value, 16),
+                    1 => tag.tci.0 = (parse_number(value, 3)? as u16) << 13 | dei << 12 | vid,
+                    2 => tag.tci.0 = pcp << 13 | (parse_number(value, 1)? as u16) << 12 | vid,
+                    3 => tag.tci.0 = pcp << 13 | dei << 12 | parse_number(value, 12)? as u16,
+                    _ => return Err(anyhow!("No field {field}")),
+                }
+            },
+            CraftLayer::Ipv4(ipv4) => match field {
+                0 => set_number!(ipv4.version, value, 4),
+                1 => set_number!(ipv4.IHL, value, 4),
+                2 => set_number!(ipv4.DSPC, value, 8),
+                3 => set_number!(ipv4.total_length, value, 16),
+                4 => set_number!(ipv4.identification, value, 16),
+                5 => set_number!(ipv4.flags, value, 3),
+                6 => set_number!(ipv4.fragment_offset, value, 13),
+                7 => set_number!(ipv4.ttl, value, 8),
+                8 => set_number!(ipv4.protocol, value, 8),
+                9 => set_number!(ipv4.header_checksum, value, 16),
+                10 => ipv4.address_src = value.parse::<Ipv4addr>()?,
+                11 => ipv4.address_dst = value.parse::<Ipv4addr>()?,
+                _ => return Err(anyhow!("No field {field}")),
+            },
+            CraftLayer::Ipv6(ipv6) => match field {
+                0 => set_number!(ipv6.version, value, 4),
+                1 => set_number!(ipv6.traffic_class, value, 8),
+                2 => set_number!(ipv6.flow_label, value, 20),
+                3 => set_number!(ipv6.payload_length, value, 16),
+                4 => set_number!(ipv6.next_header, value, 8),
+                5 => set_number!(ipv6.hop_limit, value, 8),
+                6 => ipv6.address_src = value.parse::<Ipv6addr>()?,
+                7 => ipv6.address_dst = value.parse::<Ipv6addr>()?,
+                _ => return Err(anyhow!("No field {field}")),
+            },
+            CraftLayer::Arp(arp) => match field {
+                0 => set_number!(arp.hardware_type, value, 16),
+                1 => set_number!(arp.protocol_type, value, 16),
+                2 => set_number!(arp.hardware_len, value, 8),
+                3 => set_number!(arp.protocol_len, value, 8),
+                4 => set_number!(arp.operation, value, 16),
+                5 => arp.sender_hardware_address = value.parse::<MacAddress>()?,
+                6 => arp.sender_protocol_address = value.parse::<Ipv4addr>()?,
+                7 => arp.target_hardware_address = value.parse::<MacAddress>()?,
+                8 => arp.target_protocol_address = value.parse::<Ipv4addr>()?,
+                _ => return Err(anyhow!("No field {field}")),
+            },
+            CraftLayer::Tcp(tcp) => match field {
+                0 => set_number!(tcp.port_src, value, 16),
+                1 => set_number!(tcp.port_dst, value, 16),
+                2 => set_number!(tcp.sequence_num, value, 32),
+                3 => set_number!(tcp.acknowledgement_num, value, 32),
+                4 => set_number!(tcp.data_offset, value, 4),
+                5 => set_number!(tcp.control_bits, value, 6),
+                6 => set_number!(tcp.window, value, 16),
+                7 => set_number!(tcp.checksum, value, 16),
+                8 => set_number!(tcp.urgent_ptr, value, 16),
+                _ => return Err(anyhow!("No field {field}")),
+            },
+            CraftLayer::Udp(udp) => match field {
+                0 => set_number!(udp.port_src, value, 16),
+                1 => set_number!(udp.port_dst, value, 16),
+                2 => set_number!(udp.length, value, 16),
+                3 => set_number!(udp.checksum, value, 16),
+                _ => return Err(anyhow!("No field {field}")),
+            },
+            // the type and code are displayed with their name, only the number is read back
+            CraftLayer::Icmp(icmp) => match (field, value.split_whitespace().next().unwrap_or_default()) {
+                (0, value) => {
+                    icmp.message_type = MessageType::from(parse_number(value, 8)? as u8);
+                    icmp.code = MessageCode::from_type_and_code(&icmp.message_type, icmp.code.to_code());
+                },
+                (1, value) => icmp.code = MessageCode::from_type_and_code(&icmp.message_type, parse_number(value, 8)? as u8),
+                (2, value) => set_number!(icmp.checksum, value, 16),
+                (3, value) => set_number!(icmp.rest_of_header, value, 32),
+                _ => return Err(anyhow!("No field {field}")),
+            },
+            CraftLayer::Raw(payload) => match field {
+                0 => *payload = parse_hex_stream(value)?,
+                _ => return Err(anyhow!("No field {field}")),
+            },
+        }
+
+        Ok(())
+    }
+}
+
+
+// templates are toml files with one [[layer]] table per layer of the stack
+//
+// verbatim = false
+//
+// [[layer]]
+// type = "ipv4"
+// ttl = 64
+// address_dst = "10.0.0.1"
+//
+// the keys are the field names of the layer structs, fields that are left out keep their defaults
+pub fn template_to_string(craft: &Craft<'static>) -> String {
+    let layers: Vec<toml::Value> = craft.layers
+        .iter()
+        .map(|layer| {
+            let mut table = toml::Table::new();
+            table.insert("type".to_owned(), layer.name().to_lowercase().into());
+            for (name, value) in layer.fields() {
+                // only plain decimals become integers so hex strings like a payload of 00 keep their digits
+                let value = match value.parse::<i64>() {
+                    Ok(number) if number.to_string() == value => number.into(),
+                    _ => value.into(),
+                };
+                table.insert(name.to_owned(), value);
+            }
+
+            toml::Value::Table(table)
+        })
+        .collect();
+
+    let mut template = toml::Table::new();
+    template.insert("verbatim".to_owned(), craft.verbatim.into());
+    template.insert("layer".to_owned(), layers.into());
+
+    template.to_string()
+}
+
+pub fn template_from_str(template: &str) -> Result<Craft<'static>> {
+    let template: toml::Table = template.parse()?;
+    let mut craft = Craft::new();
+    craft.verbatim = template.get("verbatim").and_then(toml::Value::as_bool).unwrap_or_default();
+
+    let layers = match template.get("layer") {
+        Some(layers) => layers.as_array().ok_or(anyhow!("layer has to be an array of tables"))?.as_slice(),
+        None => &[],
     };
+
+    for (layer_idx, layer) in layers.iter().enumerate() {
+        let table = layer.as_table().ok_or(anyhow!("Layer {layer_idx} is not a table"))?;
+        let layer_type = table.get("type")
+            .and_then(toml::Value::as_str)
+            .ok_or(anyhow!("Layer {layer_idx} has no type"))?;
+
+        let mut craft_layer = layer_from_name(layer_type)?;
+        let field_names: Vec<&str> = craft_layer.fields().into_iter().map(|(name, _)| name).collect();
+
+        for (name, value) in table.iter().filter(|(name, _)| *name != "type") {
+            let field = field_names
+                .iter()
+                .position(|field_name| field_name == name)
+                .ok_or(anyhow!("{layer_type} has no field {name}"))?;
+            let value = match value {
+                toml::Value::String(value) => value.clone(),
+                toml::Value::Integer(value) => value.to_string(),
+                _ => return Err(anyhow!("{layer_type}.{name} has to be a string or an integer")),
+            };
+
+            craft_layer.set_field(field, &value).map_err(|error| anyhow!("{layer_type}.{name}: {error}"))?;
+        }
+
+        craft.layers.push(craft_layer);
+    }
+
+    Ok(craft)
+}
+
+pub fn save_template(craft: &Craft<'static>, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    std::fs::write(path, template_to_string(craft)).map_err(|error| anyhow!("Failed to write {}: {error}", path.display()))
+}
+
+pub fn load_template(path: impl AsRef<Path>) -> Result<Craft<'static>> {
+    let path = path.as_ref();
+    let template = std::fs::read_to_string(path).map_err(|error| anyhow!("Failed to read {}: {error}", path.display()))?;
+
+    template_from_str(&template)
+}
+
+// decodes an ethernet frame into a layer stack, frames that do not decode become a single raw layer
+pub fn import_frame(frame: &[u8]) -> Craft<'static> {
+    match Packet::from_slice(frame) {
+        Ok(packet) => Craft::from_packet(&packet),
+        Err(_) => Craft::new().payload(frame),
+    }
+}
+
+// `record` counts from 1 like the packet numbers shown by wireshark
+pub fn import_pcap_record(path: impl AsRef<Path>, record: usize) -> Result<Craft<'static>> {
+    let records = pcap::read_file(path)?;
+    let record = record
+        .checked_sub(1)
+        .and_then(|idx| records.get(idx))
+        .ok_or(anyhow!("The capture has {} records", records.len()))?;
+
+    Ok(
+        match record.link_type {
+            pcap::LINKTYPE_ETHERNET => import_frame(&record.data),
+            _ => Craft::new().payload(&record.data),
+        }
+    )
+}
+
+fn hex_lines(frame: &[u8]) -> Vec<String> {
+    frame
+        .chunks(16)
+        .enumerate()
+        .map(|(line, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{byte:02x}")).collect();
+            format!("{:04x}  {}", line * 16, hex.join(" "))
+        })
+        .collect()
 }
 
 
@@ -17,21 +390,25 @@ macro_rules! move_cursor_to {
 struct DisplayRow {
     name: String,
     offx: usize,
-    offy: usize
+    offy: usize,
+    layer_idx: usize,
+    field_idx: Option<usize>,
 }
 
 macro_rules! display_row {
-    ($name:expr) => {
+    ($name:expr, $layer_idx:expr) => {
         DisplayRow {
             name: $name,
+            layer_idx: $layer_idx,
             ..Default::default()
         }
     };
-    ($name:expr, $offx:expr, $offy:expr) => {
+    ($name:expr, $offx:expr, $layer_idx:expr, $field_idx:expr) => {
         DisplayRow {
             name: $name,
             offx: $offx,
-            offy: $offy,
+            layer_idx: $layer_idx,
+            field_idx: Some($field_idx),
             ..Default::default()
         }
     };
@@ -45,169 +422,289 @@ enum DisplayScope {
     LayerDisplay,
 }
 
-// #[derive(Debug)]
-struct Display<'a> {
-    // cur_x: usize,
-    // cur_y: usize,
+#[derive(Default)]
+struct Display {
+    cur_y: usize,
     display_scope: DisplayScope,
 
-    show: &'a dyn Fn(&'a mut Self),
-
-    layers: Vec<Layer<'a>>,
+    craft: Craft<'static>,
 
-    display_rows: Vec<DisplayRow>
+    display_rows: Vec<DisplayRow>,
+    status: String,
 }
 
-impl<'a> Display<'a> {
-    fn change_scope(&'a mut self, display_scope: DisplayScope) {
-        match display_scope {
-            DisplayScope::Default => {
-                self.show = &|display: &mut Self| {
-                    println!("{:#?}", display.layers);
-                }
-            },
-            DisplayScope::LayerDisplay => {
-                self.show = &|display: &mut Self| {
-                    if display.layers.len() == 0 {
-                        println!("No layers");
-                    } else {
-                        display.display_rows = display.layers
-                            .iter()
-                            .map(|layer| {
-                                match layer {
-                                    Layer::NoLayer => display_row!("NoLayer".to_owned()),
-                                    Layer::Data(bytes) => display_row!(format!("{bytes:x?}")),
-                                    Layer::DataLinkLayer(data_link_layer) => display_row!(data_link_layer.get_class_name().to_owned()),
-                                    Layer::NetworkLayer(network_layer) => todo!(),
-                                    Layer::TransportLayer(transport_layer) => todo!(),
-                                }
-                            })
-                            .collect();
-                        println!("{:?}", display.display_rows);
-                    }
-                }
-            },
+impl Display {
+    fn change_scope(&mut self, display_scope: DisplayScope) {
+        self.display_scope = display_scope;
+    }
+
+    // rebuilds the selectable rows, one per layer followed by one per field
+    fn update_rows(&mut self) {
+        self.display_rows = self.craft.layers
+            .iter()
+            .enumerate()
+            .flat_map(|(layer_idx, layer)| {
+                std::iter::once(display_row!(layer.name().to_owned(), layer_idx))
+                    .chain(
+                        layer.fields()
+                            .into_iter()
+                            .enumerate()
+                            .map(move |(field_idx, (name, value))| display_row!(format!("{name}: {value}"), 4, layer_idx, field_idx))
+                    )
+            })
+            .collect();
+
+        for (offy, row) in self.display_rows.iter_mut().enumerate() {
+            row.offy = offy;
         }
 
-        self.display_scope = display_scope;
+        self.cur_y = self.cur_y.min(self.display_rows.len().saturating_sub(1));
     }
 
+    fn selected_layer(&self) -> Option<usize> {
+        self.display_rows.get(self.cur_y).map(|row| row.layer_idx)
+    }
 
-    fn show(&'a mut self) {
-        (self.show)(self);
+    fn select_layer(&mut self, layer_idx: usize) {
+        self.update_rows();
+        if let Some(row) = self.display_rows.iter().find(|row| row.layer_idx == layer_idx && row.field_idx.is_none()) {
+            self.cur_y = row.offy;
+        }
     }
 
-    fn cursor_down() {
+    fn add_layer(&mut self, name: &str) -> Result<()> {
+        let layer = layer_from_name(name)?;
+        let layer_idx = self.selected_layer().map_or(self.craft.layers.len(), |idx| idx + 1);
+        self.craft.layers.insert(layer_idx, layer);
+        self.select_layer(layer_idx);
 
+        Ok(())
     }
 
-    fn cursor_up() {
+    fn remove_layer(&mut self) -> Result<()> {
+        let layer_idx = self.selected_layer().ok_or(anyhow!("No layer selected"))?;
+        self.craft.layers.remove(layer_idx);
+        self.select_layer(layer_idx.saturating_sub(1));
 
+        Ok(())
+    }
+
+    fn move_layer(&mut self, up: bool) -> Result<()> {
+        let layer_idx = self.selected_layer().ok_or(anyhow!("No layer selected"))?;
+        let other_idx = match up {
+            true => layer_idx.checked_sub(1),
+            false => Some(layer_idx + 1).filter(|idx| *idx < self.craft.layers.len()),
+        }
+        .ok_or(anyhow!("Layer can not be moved further"))?;
+
+        self.craft.layers.swap(layer_idx, other_idx);
+        self.select_layer(other_idx);
+
+        Ok(())
+    }
+
+    fn replace_layers(&mut self, craft: Craft<'static>) {
+        self.craft = craft;
+        self.cur_y = 0;
+        self.update_rows();
     }
-}
 
-impl<'a> Default for Display<'a> {
-    fn default() -> Self {
-        let mut display = Self { 
-            // cur_x: Default::default(), 
-            // cur_y: Default::default(), 
-            display_scope: Default::default(), 
-            show: &|_: &mut Self| {}, 
-            layers: Default::default() ,
-            display_rows: Default::default()
+    fn set_field(&mut self, value: &str) -> Result<()> {
+        let row = self.display_rows.get(self.cur_y).ok_or(anyhow!("No layer selected"))?;
+        let field_idx = row.field_idx.ok_or(anyhow!("Select a field to edit with the arrow keys"))?;
+        self.craft.layers[row.layer_idx].set_field(field_idx, value)?;
+        self.update_rows();
+
+        Ok(())
+    }
+
+    fn run_command(&mut self, input: &str) -> Result<()> {
+        let mut args = input.split_whitespace();
+        self.status.clear();
+        match args.next() {
+            None => {},
+            Some(r"\l") => self.change_scope(DisplayScope::LayerDisplay),
+            Some(r"\d") => self.change_scope(DisplayScope::Default),
+            Some(r"\h") => self.status = HELP.to_owned(),
+            Some(r"\v") => self.craft.verbatim = !self.craft.verbatim,
+            Some(r"\a") => self.add_layer(args.next().ok_or(anyhow!(r"Usage: \a <layer>"))?)?,
+            Some(r"\r") => self.remove_layer()?,
+            Some(r"\mu") => self.move_layer(true)?,
+            Some(r"\md") => self.move_layer(false)?,
+            Some(r"\w") => save_template(&self.craft, args.next().ok_or(anyhow!(r"Usage: \w <file>"))?)?,
+            Some(r"\o") => self.replace_layers(load_template(args.next().ok_or(anyhow!(r"Usage: \o <file>"))?)?),
+            Some(r"\x") => self.replace_layers(import_frame(&parse_hex_stream(&args.collect::<String>())?)),
+            Some(r"\p") => {
+                let path = args.next().ok_or(anyhow!(r"Usage: \p <pcap> [record]"))?;
+                let record = args.next().map_or(Ok(1), str::parse).map_err(|_| anyhow!("Record has to be a number"))?;
+                self.replace_layers(import_pcap_record(path, record)?);
+            },
+            Some(r"\s") => {
+                let target: Target = args.next().ok_or(anyhow!(r"Usage: \s <interface|tap:name> [count=n] [interval=ms] [pps=n] [mbps=n]"))?.parse()?;
+                let stats = inject::send_to(&target, &self.craft.to_bytes(), &SendOptions::from_args(args)?)?;
+                self.status = format!("Sent {} frames ({} bytes) to {target}", stats.frames, stats.bytes);
+            },
+            Some(command) if command.starts_with('\\') => return Err(anyhow!("Unknown command {command}")),
+            Some(_) => self.set_field(input)?,
+        }
+
+        Ok(())
+    }
+
+    fn show(&self, out: &mut impl Write, input: &str) -> std::io::Result<()> {
+        let mut lines: Vec<String> = match self.display_scope {
+            DisplayScope::Default => format!("{:#?}", self.craft.layers).lines().map(str::to_owned).collect(),
+            DisplayScope::LayerDisplay => match self.display_rows.is_empty() {
+                true => vec!["No layers".to_owned()],
+                false => self.display_rows
+                    .iter()
+                    .map(|row| {
+                        let marker = if row.offy == self.cur_y { ">" } else { " " };
+                        format!("{marker} {:offx$}{}", "", row.name, offx = row.offx)
+                    })
+                    .collect(),
+            },
         };
 
-        display.change_scope(DisplayScope::default());
+        let frame = self.craft.to_bytes();
+        let fill = if self.craft.verbatim { "verbatim" } else { "derived fields filled in" };
+        lines.push(String::new());
+        lines.push(format!("Hex ({} bytes, {fill})", frame.len()));
+        lines.extend(hex_lines(&frame));
+
+        queue!(out, terminal::Clear(terminal::ClearType::All), cursor::MoveTo(0, 1))?;
+        write!(out, "{}", self.status)?;
+        for (row, line) in lines.iter().enumerate() {
+            queue!(out, cursor::MoveTo(0, row as u16 + 3))?;
+            write!(out, "{line}")?;
+        }
+        queue!(out, cursor::MoveTo(0, 0))?;
+        write!(out, " $ {input}")?;
+
+        out.flush()
+    }
 
-        return display;
+    fn cursor_down(&mut self) {
+        if self.cur_y + 1 < self.display_rows.len() {
+            self.cur_y += 1;
+        }
+    }
+
+    fn cursor_up(&mut self) {
+        self.cur_y = self.cur_y.saturating_sub(1);
+    }
+
+    fn finish(&self) -> BuiltPacket {
+        BuiltPacket::from_frame(self.craft.to_bytes())
     }
 }
 
 
 
+self_cell!(
+    // the finished packet together with the frame it was dissected from
+    pub struct BuiltPacket {
+        owner: Vec<u8>,
 
-pub fn build_packet<'a>() -> Result<Packet<'a>> {
-    print!("\x1B[2J\x1B[H");
+        #[covariant]
+        dependent: Packet,
+    }
 
-    #[allow(unused)]
-    let mut input = String::new();
-    let mut input_trimmed = "";
+    impl {Debug}
+);
 
-    #[allow(unused)]
-    let mut text_cur_x = 0usize;
-    #[allow(unused)]
-    let mut text_cur_y = 0usize;
-    #[allow(unused)]
-    let mut prev_char = ' ';
+impl BuiltPacket {
+    // frames that do not start with an ethernet header stay data
+    pub fn from_frame(frame: Vec<u8>) -> Self {
+        Self::new(frame, |frame| {
+            Packet::from_slice(frame).unwrap_or(
+                Packet {
+                    layer: Layer::Data(Bytes::from_slice(frame))
+                }
+            )
+        })
+    }
 
-    let mut stdout = std::io::stdout();
+    pub fn frame(&self) -> &[u8] {
+        self.borrow_owner()
+    }
 
-    let mut display = Display::default();
+    pub fn packet(&self) -> &Packet<'_> {
+        self.borrow_dependent()
+    }
+}
+
+// raw mode for as long as it lives, the terminal is restored however the builder ends, panics included
+struct RawMode;
 
-    display.layers.push(Layer::DataLinkLayer(DataLinkLayer::ETHII(ETHII::default())));
+impl RawMode {
+    fn enable() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(Self)
+    }
+}
 
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// interactive builder on the current terminal, returns the packet that was built
+pub fn build_packet() -> Result<BuiltPacket> {
+    let _raw_mode = RawMode::enable()?;
+
+    build_packet_with_events(
+        std::iter::from_fn(|| crossterm::event::read().ok()),
+        &mut std::io::stdout(),
+    )
+}
+
+// drives the builder from any source of terminal events, the packet is finished on \q or when events run out
+pub fn build_packet_with_events(events: impl IntoIterator<Item = Event>, out: &mut impl Write) -> Result<BuiltPacket> {
+    let mut events = events.into_iter();
+    let mut input = String::new();
+
+    let mut display = Display::default();
+    display.craft.layers.push(CraftLayer::Ether(MacHeader::default()));
+    display.change_scope(DisplayScope::LayerDisplay);
+    display.update_rows();
+    display.status = HELP.to_owned();
 
     loop {
-        match input_trimmed {
-            r"\q" => break,
-            r"\l" => display.change_scope(DisplayScope::LayerDisplay),
-            r"\d" => display.change_scope(DisplayScope::Default),
-            _ => {}
-        }
-
-        
-
-
-        input = String::new();
-        text_cur_x = 0;
-        text_cur_y = 0;
-        let _ = execute!(stdout, cursor::MoveTo(0, 0));
-        print!(" $ ");
-        let _ = execute!(stdout, cursor::MoveDown(1));
-        display.show();
-        let _ = execute!(stdout, cursor::MoveTo(3, 0));
-        // let _ = std::io::stdout().flush();
-
-        loop {
-            if let Event::Key(key_event) = crossterm::event::read().unwrap() {
-                if key_event.is_press() {
-                    match key_event.code {
-                        KeyCode::Enter => {
-                            let _ = execute!(stdout, terminal::Clear(terminal::ClearType::All), cursor::MoveTo(0, 0));
-                            print!(" $ ");
-                            let _ = std::io::stdout().flush(); 
-                            break
-                        }
-                        KeyCode::Backspace => { 
-                            if text_cur_x == 0 {
-                                continue;
-                            }
-
-                            input.pop(); 
-                            text_cur_x -= 1;
-
-                            print!("\x1b[2J\x1b[H $ {input}");
-                            let _ = std::io::stdout().flush(); 
-                        }
-                        KeyCode::Char(c) => { 
-                            input.push(c); 
-                            prev_char = c; 
-                            text_cur_x += 1; 
-
-                            print!("\x1B[{text_cur_y};0H\x1b[{}C{prev_char}", text_cur_x + 2);
-                            let _ = std::io::stdout().flush();
-                        }
-                        KeyCode::Down => display.cursor_down(),
-                        KeyCode::Up => display.cursor_up(),
-                        _ => println!("what the")
-                    }
+        display.show(out, &input)?;
+
+        let Some(event) = events.next() else {
+            break;
+        };
+        let Event::Key(key_event) = event else {
+            continue;
+        };
+        if !key_event.is_press() {
+            continue;
+        }
+
+        match key_event.code {
+            KeyCode::Enter => {
+                let command = std::mem::take(&mut input);
+                if command.trim() == r"\q" {
+                    break;
                 }
-            }
+                if let Err(error) = display.run_command(command.trim()) {
+                    display.status = error.to_string();
+                }
+            },
+            KeyCode::Backspace => { input.pop(); },
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Down => display.cursor_down(),
+            KeyCode::Up => display.cursor_up(),
+            _ => {},
         }
-        input_trimmed = &input;
     }
 
-    
+    // the shell prompt starts on a line of its own
+    write!(out, "\r\n")?;
+    out.flush()?;
 
-    todo!()
-}
\ No newline at end of file
+    Ok(display.finish())
+}
@@ -0,0 +1,246 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::{anyhow, Result};
+
+
+// https://www.tcpdump.org/linktypes.html
+pub const LINKTYPE_ETHERNET: u16 = 1;
+pub const LINKTYPE_PPP: u16 = 9;
+// ppp in hdlc-like framing, RFC 1662
+pub const LINKTYPE_PPP_HDLC: u16 = 50;
+// pppoe without the ethernet header
+pub const LINKTYPE_PPP_ETHER: u16 = 51;
+// cisco hdlc, RFC 1547 section 4.3.1
+pub const LINKTYPE_C_HDLC: u16 = 104;
+
+
+#[derive(Debug, Clone, Default)]
+pub struct Record {
+    pub timestamp: Duration, // since the unix epoch
+    pub original_length: u32,
+    pub link_type: u16,
+    pub data: Vec<u8>,
+}
+
+
+// reads every record of a pcap or pcapng file
+pub fn read_file(path: impl AsRef<Path>) -> Result<Vec<Record>> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).map_err(|error| anyhow!("Failed to read {}: {error}", path.display()))?;
+
+    read_records(&data)
+}
+
+pub fn read_records(data: &[u8]) -> Result<Vec<Record>> {
+    if data.len() < 4 {
+        return Err(anyhow!("Capture is too short to contain a header"));
+    }
+
+    match data[0..4] {
+        [0x0a, 0x0d, 0x0d, 0x0a] => read_pcapng(data),
+        _ => read_pcap(data),
+    }
+}
+
+
+
+// integer reader for files that can be written in either byte order
+#[derive(Clone, Copy)]
+struct Endian {
+    little: bool,
+}
+
+impl Endian {
+    fn u16(&self, data: &[u8], offset: usize) -> Result<u16> {
+        let bytes: [u8; 2] = data.get(offset..offset+2)
+            .ok_or(anyhow!("Capture is truncated"))?
+            .try_into()?;
+
+        Ok(if self.little { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32(&self, data: &[u8], offset: usize) -> Result<u32> {
+        let bytes: [u8; 4] = data.get(offset..offset+4)
+            .ok_or(anyhow!("Capture is truncated"))?
+            .try_into()?;
+
+        Ok(if self.little { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+}
+
+
+
+// https://www.ietf.org/archive/id/draft-ietf-opsawg-pcap-04.html
+fn read_pcap(data: &[u8]) -> Result<Vec<Record>> {
+    let (little, nanoseconds) = match data[0..4] {
+        [0xd4, 0xc3, 0xb2, 0xa1] => (true, false),
+        [0xa1, 0xb2, 0xc3, 0xd4] => (false, false),
+        [0x4d, 0x3c, 0xb2, 0xa1] => (true, true),
+        [0xa1, 0xb2, 0x3c, 0x4d] => (false, true),
+        _ => return Err(anyhow!("Not a pcap or pcapng file")),
+    };
+    let endian = Endian { little };
+    let link_type = endian.u32(data, 20)? as u16;
+
+    let mut records = Vec::new();
+    let mut offset = 24;
+    while offset < data.len() {
+        let seconds = endian.u32(data, offset)?;
+        let fraction = endian.u32(data, offset + 4)?;
+        let captured_length = endian.u32(data, offset + 8)? as usize;
+        let original_length = endian.u32(data, offset + 12)?;
+        let packet = data.get(offset+16..offset+16+captured_length).ok_or(anyhow!("Capture is truncated"))?;
+
+        let fraction = match nanoseconds {
+            true => Duration::from_nanos(fraction as u64),
+            false => Duration::from_micros(fraction as u64),
+        };
+
+        records.push(
+            Record {
+                timestamp: Duration::from_secs(seconds as u64) + fraction,
+                original_length,
+                link_type,
+                data: packet.to_vec(),
+            }
+        );
+        offset += 16 + captured_length;
+    }
+
+    Ok(records)
+}
+
+
+
+#[derive(Clone, Copy)]
+struct Interface {
+    link_type: u16,
+    // timestamp units per second
+    resolution: u64,
+}
+
+// https://www.ietf.org/archive/id/draft-ietf-opsawg-pcapng-02.html
+fn read_pcapng(data: &[u8]) -> Result<Vec<Record>> {
+    let mut records = Vec::new();
+    let mut interfaces: Vec<Interface> = Vec::new();
+    let mut endian = Endian { little: true };
+
+    let mut offset = 0;
+    while offset + 12 <= data.len() {
+        if data[offset..offset+4] == [0x0a, 0x0d, 0x0d, 0x0a] {
+            // section header, sets the byte order for the blocks that follow
+            endian.little = match data.get(offset+8..offset+12) {
+                Some([0x4d, 0x3c, 0x2b, 0x1a]) => true,
+                Some([0x1a, 0x2b, 0x3c, 0x4d]) => false,
+                _ => return Err(anyhow!("Invalid pcapng byte order magic")),
+            };
+            interfaces.clear();
+        }
+
+        let block_type = endian.u32(data, offset)?;
+        let block_length = endian.u32(data, offset + 4)? as usize;
+        if block_length < 12 || offset + block_length > data.len() {
+            return Err(anyhow!("Invalid pcapng block length {block_length}"));
+        }
+        let body = &data[offset+8..offset+block_length-4];
+
+        match block_type {
+            // interface description
+            0x0000_0001 => interfaces.push(
+                Interface {
+                    link_type: endian.u16(body, 0)?,
+                    resolution: interface_resolution(endian, body.get(8..).unwrap_or_default())?,
+                }
+            ),
+            // enhanced packet
+            0x0000_0006 => {
+                let interface = *interfaces.get(endian.u32(body, 0)? as usize).ok_or(anyhow!("Packet references an unknown interface"))?;
+                let timestamp = ((endian.u32(body, 4)? as u64) << 32) | endian.u32(body, 8)? as u64;
+                let captured_length = endian.u32(body, 12)? as usize;
+                let original_length = endian.u32(body, 16)?;
+                let packet = body.get(20..20+captured_length).ok_or(anyhow!("Capture is truncated"))?;
+
+                records.push(
+                    Record {
+                        timestamp: timestamp_duration(timestamp, interface.resolution),
+                        original_length,
+                        link_type: interface.link_type,
+                        data: packet.to_vec(),
+                    }
+                );
+            },
+            // simple packet, always from the first interface and without a timestamp
+            0x0000_0003 => {
+                let interface = *interfaces.first().ok_or(anyhow!("Packet references an unknown interface"))?;
+                let original_length = endian.u32(body, 0)?;
+                let captured_length = (original_length as usize).min(body.len() - 4);
+
+                records.push(
+                    Record {
+                        timestamp: Duration::ZERO,
+                        original_length,
+                        link_type: interface.link_type,
+                        data: body[4..4+captured_length].to_vec(),
+                    }
+                );
+            },
+            // obsolete packet block
+            0x0000_0002 => {
+                let interface = *interfaces.get(endian.u16(body, 0)? as usize).ok_or(anyhow!("Packet references an unknown interface"))?;
+                let timestamp = ((endian.u32(body, 4)? as u64) << 32) | endian.u32(body, 8)? as u64;
+                let captured_length = endian.u32(body, 12)? as usize;
+                let original_length = endian.u32(body, 16)?;
+                let packet = body.get(20..20+captured_length).ok_or(anyhow!("Capture is truncated"))?;
+
+                records.push(
+                    Record {
+                        timestamp: timestamp_duration(timestamp, interface.resolution),
+                        original_length,
+                        link_type: interface.link_type,
+                        data: packet.to_vec(),
+                    }
+                );
+            },
+            _ => {},
+        }
+
+        offset += block_length;
+    }
+
+    Ok(records)
+}
+
+// reads the if_tsresol option, the default resolution is microseconds
+fn interface_resolution(endian: Endian, mut options: &[u8]) -> Result<u64> {
+    while options.len() >= 4 {
+        let code = endian.u16(options, 0)?;
+        let length = endian.u16(options, 2)? as usize;
+        if options.len() < 4 + length {
+            return Err(anyhow!("pcapng option {code} is truncated"));
+        }
+
+        match code {
+            0 => break,
+            9 if length >= 1 => {
+                let resolution = options[4];
+                return match resolution & 0x80 {
+                    0 => 10u64.checked_pow(resolution as u32),
+                    _ => 1u64.checked_shl((resolution & 0x7f) as u32),
+                }
+                .ok_or(anyhow!("Unsupported timestamp resolution {resolution:#x}"));
+            },
+            _ => {},
+        }
+
+        options = options.get(4 + length.next_multiple_of(4)..).unwrap_or_default();
+    }
+
+    Ok(1_000_000)
+}
+
+fn timestamp_duration(timestamp: u64, resolution: u64) -> Duration {
+    let seconds = timestamp / resolution;
+    let nanoseconds = (timestamp % resolution) as u128 * 1_000_000_000 / resolution as u128;
+
+    Duration::new(seconds, nanoseconds as u32)
+}
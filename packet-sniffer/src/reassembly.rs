@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::packet::{network::{IpAddress, NetworkLayer}, transport::{TransportLayer, TCP}, Layer, Packet};
+
+
+// Puts the payloads of tcp segments back in order per direction of a connection. Retransmitted bytes are
+// dropped, segments that arrive ahead of a gap wait until it is filled. A gap the capture never fills would
+// keep every later segment waiting, so once more than MAX_OUT_OF_ORDER bytes wait the stream forgets what it
+// buffered and starts over with the next segment. Protocols whose messages span
+// segments read the in order bytes with `data` and `consume` what they decoded, or let `messages` split
+// them off when every message starts with its length.
+
+const FIN: u8 = 0x01;
+const SYN: u8 = 0x02;
+const RST: u8 = 0x04;
+
+// bytes a stream buffers ahead of a gap before it gives up on the gap
+pub const MAX_OUT_OF_ORDER: usize = 1 << 20;
+
+
+
+// one direction of a tcp connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Flow {
+    pub address_src: IpAddress,
+    pub port_src: u16,
+    pub address_dst: IpAddress,
+    pub port_dst: u16,
+}
+
+impl Flow {
+    // the other direction of the connection
+    pub fn reversed(&self) -> Self {
+        Self {
+            address_src: self.address_dst,
+            port_src: self.port_dst,
+            address_dst: self.address_src,
+            port_dst: self.port_src,
+        }
+    }
+
+    // the innermost tcp segment of a packet and the flow the ip header around it belongs to
+    pub fn of_segment<'p>(packet: &'p Packet) -> Option<(Self, &'p TCP<'p>)> {
+        let mut addresses = None;
+        let mut segment = None;
+
+        for layer in packet.layers() {
+            match layer {
+                Layer::NetworkLayer(NetworkLayer::Ipv4(ipv4)) => addresses = Some((IpAddress::V4(ipv4.address_src), IpAddress::V4(ipv4.address_dst))),
+                Layer::NetworkLayer(NetworkLayer::Ipv6(ipv6)) => addresses = Some((IpAddress::V6(ipv6.address_src), IpAddress::V6(ipv6.address_dst))),
+                Layer::TransportLayer(TransportLayer::TCP(tcp)) => if let Some((address_src, address_dst)) = addresses {
+                    segment = Some((Self { address_src, port_src: tcp.port_src, address_dst, port_dst: tcp.port_dst }, tcp));
+                },
+                _ => {},
+            }
+        }
+
+        segment
+    }
+}
+
+
+
+#[derive(Debug, Default, Clone)]
+struct Stream {
+    // the sequence number of the next byte in order, known from the first segment on
+    next_sequence: Option<u32>,
+    // in order and not consumed yet
+    data: Vec<u8>,
+    out_of_order: Vec<(u32, Vec<u8>)>,
+    // the bytes in out_of_order
+    buffered: usize,
+    closed: bool,
+}
+
+impl Stream {
+    // returns how many bytes were added in order
+    fn push(&mut self, sequence: u32, control_bits: u8, payload: &[u8]) -> usize {
+        // the syn takes up a sequence number of its own
+        let sequence = match control_bits & SYN {
+            0 => sequence,
+            _ => {
+                self.next_sequence = Some(sequence.wrapping_add(1));
+                sequence.wrapping_add(1)
+            },
+        };
+        self.closed |= control_bits & (FIN | RST) != 0;
+
+        let mut added = self.insert(sequence, payload);
+        while let Some(index) = self.out_of_order.iter().position(|(sequence, _)| !self.is_ahead(*sequence)) {
+            let (sequence, payload) = self.out_of_order.swap_remove(index);
+            self.buffered -= payload.len();
+            added += self.insert(sequence, &payload);
+        }
+
+        added
+    }
+
+    fn insert(&mut self, sequence: u32, payload: &[u8]) -> usize {
+        let next_sequence = *self.next_sequence.get_or_insert(sequence);
+        if payload.is_empty() {
+            return 0;
+        }
+
+        if self.is_ahead(sequence) {
+            if !self.out_of_order.iter().any(|(buffered, _)| *buffered == sequence) {
+                self.out_of_order.push((sequence, payload.to_vec()));
+                self.buffered += payload.len();
+            }
+            // the bytes before the gap can not be completed either, the next segment starts the stream anew
+            if self.buffered > MAX_OUT_OF_ORDER {
+                *self = Self { closed: self.closed, ..Self::default() };
+            }
+            return 0;
+        }
+
+        // a retransmission can overlap bytes that are already in order
+        let retransmitted = next_sequence.wrapping_sub(sequence) as usize;
+        let Some(payload) = payload.get(retransmitted..) else {
+            return 0;
+        };
+        self.data.extend_from_slice(payload);
+        self.next_sequence = Some(next_sequence.wrapping_add(payload.len() as u32));
+
+        payload.len()
+    }
+
+    // sequence numbers wrap, so what is ahead is whatever lies less than half the number space ahead
+    fn is_ahead(&self, sequence: u32) -> bool {
+        self.next_sequence.is_some_and(|next_sequence| (sequence.wrapping_sub(next_sequence) as i32) > 0)
+    }
+}
+
+
+
+#[derive(Debug, Default, Clone)]
+pub struct TcpReassembler {
+    streams: HashMap<Flow, Stream>,
+}
+
+impl TcpReassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // adds the payload of the packet's tcp segment to its flow, None for packets without one
+    pub fn observe(&mut self, packet: &Packet) -> Option<Flow> {
+        let (flow, tcp) = Flow::of_segment(packet)?;
+        self.push(flow, tcp.sequence_num, tcp.control_bits, &tcp.payload[..]);
+
+        Some(flow)
+    }
+
+    // returns how many bytes the segment added in order
+    pub fn push(&mut self, flow: Flow, sequence: u32, control_bits: u8, payload: &[u8]) -> usize {
+        self.streams.entry(flow).or_default().push(sequence, control_bits, payload)
+    }
+
+    // the bytes received in order and not consumed yet
+    pub fn data(&self, flow: &Flow) -> &[u8] {
+        self.streams.get(flow).map_or(&[], |stream| &stream.data)
+    }
+
+    pub fn consume(&mut self, flow: &Flow, length: usize) {
+        if let Some(stream) = self.streams.get_mut(flow) {
+            stream.data.drain(..length.min(stream.data.len()));
+        }
+    }
+
+    // splits the complete messages off the flow, `length` returns the length of the message a stream starts
+    // with or None while too little of it arrived to tell. A stream that fails to frame is dropped since
+    // there is no telling where the next message starts, the messages framed before it are returned with
+    // the error.
+    pub fn messages(&mut self, flow: &Flow, length: impl Fn(&[u8]) -> Result<Option<usize>>) -> (Vec<Vec<u8>>, Option<anyhow::Error>) {
+        let Some(stream) = self.streams.get_mut(flow) else {
+            return (Vec::new(), None);
+        };
+
+        let mut messages = Vec::new();
+        let mut offset = 0;
+        loop {
+            match length(&stream.data[offset..]) {
+                Ok(Some(length)) if length > 0 && offset + length <= stream.data.len() => {
+                    messages.push(stream.data[offset..offset + length].to_vec());
+                    offset += length;
+                },
+                Ok(_) => break,
+                Err(error) => {
+                    stream.data.clear();
+                    return (messages, Some(error));
+                },
+            }
+        }
+        stream.data.drain(..offset);
+
+        (messages, None)
+    }
+
+    // whether a fin or rst was seen in the flow
+    pub fn is_closed(&self, flow: &Flow) -> bool {
+        self.streams.get(flow).is_some_and(|stream| stream.closed)
+    }
+
+    pub fn remove(&mut self, flow: &Flow) {
+        self.streams.remove(flow);
+    }
+
+    pub fn flows(&self) -> impl Iterator<Item = &Flow> {
+        self.streams.keys()
+    }
+}
@@ -0,0 +1,245 @@
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use anyhow::{anyhow, Result};
+use byte_slice::{Bytes, Ipv4addr, Ipv6addr, MacAddress, SliceToUnsigned};
+
+use crate::{craft::{internet_checksum, update_checksum}, inject::{number_above_zero, FrameSink, Injector, Pacer, Rate, SendStats, Target}, packet::{data_link::{DataLinkLayer, MacHeader}, network::NetworkLayer, Layer, Packet}, pcap::{self, Record}};
+
+
+
+// rewrites the addresses of ethernet frames before they are sent again
+// ip addresses are mapped old to new wherever they show up as source or destination
+#[derive(Debug, Clone, Default)]
+pub struct Rewrite {
+    pub mac_src: Option<MacAddress>,
+    pub mac_dst: Option<MacAddress>,
+    pub ipv4: HashMap<Ipv4addr, Ipv4addr>,
+    pub ipv6: HashMap<Ipv6addr, Ipv6addr>,
+}
+
+impl Rewrite {
+    pub fn is_empty(&self) -> bool {
+        self.mac_src.is_none() && self.mac_dst.is_none() && self.ipv4.is_empty() && self.ipv6.is_empty()
+    }
+
+    // frames that do not decode are left as they are past the parts that did
+    pub fn apply(&self, frame: &mut [u8]) {
+        let Ok(mut mac_header) = MacHeader::from_bytes(&mut Bytes::from_slice(frame)) else {
+            return;
+        };
+        mac_header.address_src = self.mac_src.unwrap_or(mac_header.address_src);
+        mac_header.address_dst = self.mac_dst.unwrap_or(mac_header.address_dst);
+        let mac_bytes = mac_header.to_bytes();
+        frame[..mac_bytes.len()].copy_from_slice(&mac_bytes);
+
+        let ip_offset = mac_bytes.len();
+        let Some(addresses) = self.ip_addresses(frame, ip_offset) else {
+            return;
+        };
+
+        frame[addresses.offset..addresses.offset + addresses.new.len()].copy_from_slice(&addresses.new);
+
+        if let Some(header_length) = addresses.ipv4_header_length
+            && let Some(header) = frame.get_mut(ip_offset..ip_offset + header_length) {
+            header[10..12].fill(0);
+            let checksum = internet_checksum(header);
+            header[10..12].copy_from_slice(&checksum.to_be_bytes());
+        }
+
+        // the transport checksum covers the addresses through the pseudo header, it is adjusted
+        // rather than recomputed so frames cut short by the capture's snap length still come out right
+        if let Some((offset, udp)) = addresses.transport_checksum
+            && let Some(field) = frame.get_mut(offset..offset + 2) {
+            let checksum = field.to_u16();
+            // a udp checksum of 0 means none was computed
+            if !(udp && checksum == 0) {
+                let checksum = match update_checksum(checksum, &addresses.old, &addresses.new) {
+                    0 if udp => 0xffff,
+                    checksum => checksum,
+                };
+                field.copy_from_slice(&checksum.to_be_bytes());
+            }
+        }
+    }
+
+    fn ip_addresses(&self, frame: &[u8], ip_offset: usize) -> Option<AddressRewrite> {
+        let packet = Packet::from_slice(frame).ok()?;
+        let Layer::DataLinkLayer(DataLinkLayer::ETHII(ethii)) = &packet.layer else {
+            return None;
+        };
+        let Layer::NetworkLayer(network_layer) = ethii.next_layer.as_ref() else {
+            return None;
+        };
+
+        let addresses = match network_layer {
+            NetworkLayer::Ipv4(ipv4) => {
+                let map = |address| self.ipv4.get(&address).copied().unwrap_or(address);
+                let header_length = (ipv4.IHL as usize * 4).max(20);
+                let transport = ip_offset + header_length;
+
+                AddressRewrite {
+                    offset: ip_offset + 12,
+                    old: [ipv4.address_src.0.to_be_bytes(), ipv4.address_dst.0.to_be_bytes()].concat(),
+                    new: [map(ipv4.address_src).0.to_be_bytes(), map(ipv4.address_dst).0.to_be_bytes()].concat(),
+                    ipv4_header_length: Some(header_length),
+                    // only the first fragment carries the transport header
+                    transport_checksum: match (ipv4.protocol, ipv4.fragment_offset) {
+                        (6, 0) => Some((transport + 16, false)),
+                        (17, 0) => Some((transport + 6, true)),
+                        _ => None,
+                    },
+                }
+            },
+            NetworkLayer::Ipv6(ipv6) => {
+                let map = |address| self.ipv6.get(&address).copied().unwrap_or(address);
+                let transport = ip_offset + 40;
+
+                AddressRewrite {
+                    offset: ip_offset + 8,
+                    old: [ipv6.address_src.0.to_be_bytes(), ipv6.address_dst.0.to_be_bytes()].concat(),
+                    new: [map(ipv6.address_src).0.to_be_bytes(), map(ipv6.address_dst).0.to_be_bytes()].concat(),
+                    ipv4_header_length: None,
+                    transport_checksum: match ipv6.next_header {
+                        6 => Some((transport + 16, false)),
+                        17 => Some((transport + 6, true)),
+                        58 => Some((transport + 2, false)),
+                        _ => None,
+                    },
+                }
+            },
+            _ => return None,
+        };
+
+        (addresses.old != addresses.new).then_some(addresses)
+    }
+}
+
+struct AddressRewrite {
+    // where the source address starts, the destination follows it
+    offset: usize,
+    old: Vec<u8>,
+    new: Vec<u8>,
+    ipv4_header_length: Option<usize>,
+    // offset of the checksum and whether it is udp's
+    transport_checksum: Option<(usize, bool)>,
+}
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timing {
+    // the gaps between the capture timestamps divided by the multiplier, 1.0 is the original timing
+    Multiplier(f64),
+    Rate(Rate),
+    TopSpeed,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayOptions {
+    pub timing: Timing,
+    // how many times the capture is sent, 0 repeats it until an error
+    pub loops: u64,
+    pub rewrite: Rewrite,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            timing: Timing::Multiplier(1.0),
+            loops: 1,
+            rewrite: Rewrite::default(),
+        }
+    }
+}
+
+impl ReplayOptions {
+    // reads speed=<x> pps=<n> mbps=<n> topspeed loop=<n> mac-src=<mac> mac-dst=<mac> ip-map=<old>,<new> arguments
+    pub fn from_args<'a>(args: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let mut options = Self::default();
+
+        for arg in args {
+            if arg == "topspeed" {
+                options.timing = Timing::TopSpeed;
+                continue;
+            }
+
+            let (key, value) = arg.split_once('=').ok_or(anyhow!("Expected key=value but got {arg}"))?;
+            match key {
+                "speed" => options.timing = Timing::Multiplier(number_above_zero(key, value)?),
+                "pps" => options.timing = Timing::Rate(Rate::Pps(number_above_zero(key, value)?)),
+                "mbps" => options.timing = Timing::Rate(Rate::Mbps(number_above_zero(key, value)?)),
+                "loop" => options.loops = value.parse().map_err(|_| anyhow!("loop has to be a whole number"))?,
+                "mac-src" => options.rewrite.mac_src = Some(value.parse()?),
+                "mac-dst" => options.rewrite.mac_dst = Some(value.parse()?),
+                "ip-map" => {
+                    let (old, new) = value.split_once(',').ok_or(anyhow!("ip-map expects <old>,<new>"))?;
+                    match (old.parse::<Ipv4addr>(), new.parse::<Ipv4addr>()) {
+                        (Ok(old), Ok(new)) => { options.rewrite.ipv4.insert(old, new); },
+                        _ => { options.rewrite.ipv6.insert(old.parse()?, new.parse()?); },
+                    }
+                },
+                _ => return Err(anyhow!("Unknown replay option {key}")),
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn gap(&self, records: &[&Record], idx: usize) -> Result<Duration> {
+        match self.timing {
+            Timing::Multiplier(multiplier) => match records.get(idx + 1) {
+                Some(next) => {
+                    let gap = next.timestamp.saturating_sub(records[idx].timestamp);
+                    Duration::try_from_secs_f64(gap.as_secs_f64() / multiplier).map_err(|_| anyhow!("speed {multiplier} is too slow to replay at"))
+                },
+                None => Ok(Duration::ZERO),
+            },
+            Timing::Rate(rate) => rate.gap(records[idx].data.len()),
+            Timing::TopSpeed => Ok(Duration::ZERO),
+        }
+    }
+}
+
+
+
+// sends the records in capture order, the gap after the last record of a loop is 0. Only ethernet records can
+// go out of an interface or tap, the others are skipped and counted
+pub fn replay(sink: &mut impl FrameSink, records: &[Record], options: &ReplayOptions) -> Result<SendStats> {
+    let skipped = records.iter().filter(|record| record.link_type != pcap::LINKTYPE_ETHERNET).count() as u64;
+    let records: Vec<&Record> = records.iter().filter(|record| record.link_type == pcap::LINKTYPE_ETHERNET).collect();
+    let frames: Vec<Vec<u8>> = records
+        .iter()
+        .map(|record| {
+            let mut frame = record.data.clone();
+            if !options.rewrite.is_empty() {
+                options.rewrite.apply(&mut frame);
+            }
+            frame
+        })
+        .collect();
+
+    let mut stats = SendStats { skipped, ..Default::default() };
+    if frames.is_empty() {
+        return Ok(stats);
+    }
+
+    let mut pacer = Pacer::new();
+    let mut loops = 0;
+    while options.loops == 0 || loops < options.loops {
+        for (idx, frame) in frames.iter().enumerate() {
+            pacer.wait(options.gap(&records, idx)?)?;
+            sink.send_frame(frame)?;
+
+            stats.frames += 1;
+            stats.bytes += frame.len() as u64;
+        }
+        loops += 1;
+    }
+
+    Ok(stats)
+}
+
+pub fn replay_file(path: impl AsRef<Path>, target: &Target, options: &ReplayOptions) -> Result<SendStats> {
+    let records = pcap::read_file(path)?;
+    replay(&mut Injector::open(target)?, &records, options)
+}